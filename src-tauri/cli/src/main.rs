@@ -0,0 +1,554 @@
+use log::{error, info, warn, LevelFilter};
+use sprite_packer_core::packer::edges::{EdgeOptions, EdgeSettings};
+use sprite_packer_core::packer::format::AtlasFormat;
+use sprite_packer_core::packer::preview::{self, PreviewFormat, PreviewOptions};
+use sprite_packer_core::packer::output::AtlasOutput;
+use sprite_packer_core::packer::{batch, get_collection, lint, load, repack, unpack, verify};
+use sprite_packer_core::packer::repack::RepackOptions;
+use sprite_packer_core::packer::sheet::{self, SheetLayout};
+use sprite_packer_core::packer::texture::{BlockCompression, TextureOptions};
+use sprite_packer_core::tk2d::clip::Clip;
+use sprite_packer_core::tk2d::cln::Collection;
+use sprite_packer_core::{PackerError, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "Usage: sprite-packer-cli [options] <sprites path> <output directory> [collection name...]
+       sprite-packer-cli --unpack [--overwrite] <sprites path> <output directory> [animation name...]
+       sprite-packer-cli --verify <sprites path> [collection name...]
+       sprite-packer-cli --lint <sprites path> [collection name...]
+       sprite-packer-cli --repack [repack options] <sprites path> <output directory> [collection name...]
+       sprite-packer-cli --preview=<format> [preview options] <sprites path> <output directory> <animation name> [clip name...]
+       sprite-packer-cli --sheet=<layout> [--columns=<count>] <sprites path> <output directory> <animation name> [clip name...]
+
+Packs the given collections, or every collection if none are given, from a
+GODump sprites folder and writes each atlas to <output directory>/<collection>.png.
+
+With --unpack, regenerates the frames of the given animations, or every
+animation if none are given, from their atlases and sprite info into the
+sprites folder <output directory>, skipping frames that already exist unless
+--overwrite is given.
+
+With --verify, packs the given collections, or every collection, in memory
+and reports sprites whose frames overflow their rect, which would be written
+outside the atlas, or which don't match the original atlas, exiting with 1 if
+any sprite's pixels would be lost.
+
+With --repack, lays out the sprites of the given collections, or every
+collection, in new atlases, trimming each frame to its painted pixels, and
+writes each atlas and its updated SpriteInfo.json into the sprites folder
+<output directory>, which may be <sprites path> itself.
+
+With --lint, reports frames of the given collections, or every collection,
+with painted pixels outside their sprite's rect, which are never packed,
+exiting with 1 if there are any.
+
+With --preview, exports the given clips of an animation, or every clip, to
+<output directory>/<clip>.<extension> as a looping gif, apng or webp.
+
+With --sheet, composes the frames of the given clips of an animation, or every
+clip, into a strip or grid sprite sheet at <output directory>/<clip>.png with
+the rect of each frame, the fps and the loop start in <clip>.json. A grid has
+--columns cells in each row, or is as close to a square as possible.
+
+Options:
+  --incremental          Only repack sprites whose frame files changed since the
+                         atlas in <output directory> was generated
+  --template=<template>  Write atlases to <output directory>/<template>, where
+                         {animation}, {collection} and {timestamp} are replaced
+  --backup               Back up existing atlases before overwriting them
+  --format=<format>      Write atlases as original (default), rgba8, rgba16 or rgb8
+  --dds=<compression>    Also write each atlas as a DDS texture compressed with
+                         bc3 or bc7
+  --ktx2=<compression>   Also write each atlas as a KTX2 texture compressed with
+                         bc3 or bc7
+  --mipmaps              Generate mipmaps in the DDS and KTX2 textures
+  --linear               Mark the DDS and KTX2 textures as linear instead of sRGB
+  --check-padding=<pixels>
+                         Warn about sprites without this many transparent pixels
+                         around their rect in the atlas
+  --extrude=<pixels>     Repeat the edge pixels of each sprite this many pixels
+                         outwards, without writing over other sprites
+  --bleed                Give fully transparent pixels the color of their nearest
+                         painted pixels
+
+Repack options:
+  --padding=<pixels>     Leave this many pixels between sprites (default 2)
+  --allow-flip           Allow sprites to be rotated into the atlas
+  --npot                 Crop the atlas instead of using power of two sizes
+
+Preview options:
+  --scale=<factor>       Enlarge each pixel this many times (default 1)
+  --loop-repeats=<count> Play the frames from the clip's loop start this many
+                         times before starting over (default 3)";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let unpack = take_flag(&mut args, "--unpack");
+    let verify = take_flag(&mut args, "--verify");
+    let lint = take_flag(&mut args, "--lint");
+    let repack = take_flag(&mut args, "--repack");
+    let preview_format = match take_option(&mut args, "--preview=").as_deref() {
+        None => None,
+        Some("gif") => Some(PreviewFormat::Gif),
+        Some("apng") => Some(PreviewFormat::Apng),
+        Some("webp") => Some(PreviewFormat::Webp),
+        Some(format) => {
+            eprintln!("Invalid preview format: {}\n\n{}", format, USAGE);
+            process::exit(2);
+        }
+    };
+    let sheet_layout = match take_option(&mut args, "--sheet=").as_deref() {
+        None => None,
+        Some("strip") => Some(SheetLayout::Strip),
+        Some("grid") => Some(SheetLayout::Grid { columns: 0 }),
+        Some(layout) => {
+            eprintln!("Invalid sheet layout: {}\n\n{}", layout, USAGE);
+            process::exit(2);
+        }
+    };
+    let mut columns = 0;
+    let mut preview_options = PreviewOptions::default();
+    for (name, value) in [
+        ("--scale=", &mut preview_options.scale),
+        ("--loop-repeats=", &mut preview_options.loop_repeats),
+        ("--columns=", &mut columns),
+    ] {
+        if let Some(count) = take_option(&mut args, name) {
+            match count.parse() {
+                Ok(count) => *value = count,
+                Err(_) => {
+                    eprintln!("Invalid {}: {}\n\n{}", name.trim_start_matches("--").trim_end_matches('='), count, USAGE);
+                    process::exit(2);
+                }
+            }
+        }
+    }
+    let mut repack_options = RepackOptions {
+        allow_flip: take_flag(&mut args, "--allow-flip"),
+        power_of_two: !take_flag(&mut args, "--npot"),
+        ..RepackOptions::default()
+    };
+    if let Some(padding) = take_option(&mut args, "--padding=") {
+        match padding.parse() {
+            Ok(padding) => repack_options.padding = padding,
+            Err(_) => {
+                eprintln!("Invalid padding: {}\n\n{}", padding, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+    let overwrite = take_flag(&mut args, "--overwrite");
+    let incremental = take_flag(&mut args, "--incremental");
+    let backup = take_flag(&mut args, "--backup");
+    let template = take_option(&mut args, "--template=");
+    let format = match take_option(&mut args, "--format=").as_deref() {
+        None | Some("original") => AtlasFormat::Original,
+        Some("rgba8") => AtlasFormat::Rgba8,
+        Some("rgba16") => AtlasFormat::Rgba16,
+        Some("rgb8") => AtlasFormat::Rgb8,
+        Some(format) => {
+            eprintln!("Invalid format: {}\n\n{}", format, USAGE);
+            process::exit(2);
+        }
+    };
+    let mut textures = TextureOptions {
+        mipmaps: take_flag(&mut args, "--mipmaps"),
+        linear: take_flag(&mut args, "--linear"),
+        ..TextureOptions::default()
+    };
+    for (name, value) in [("--dds=", &mut textures.dds), ("--ktx2=", &mut textures.ktx2)] {
+        *value = match take_option(&mut args, name).as_deref() {
+            None => None,
+            Some("bc3") => Some(BlockCompression::Bc3),
+            Some("bc7") => Some(BlockCompression::Bc7),
+            Some(compression) => {
+                eprintln!("Invalid {}: {}\n\n{}", name.trim_start_matches("--").trim_end_matches('='), compression, USAGE);
+                process::exit(2);
+            }
+        };
+    }
+    let mut edge_options = EdgeOptions {
+        bleed: take_flag(&mut args, "--bleed"),
+        ..EdgeOptions::default()
+    };
+    for (name, value) in [("--check-padding=", &mut edge_options.padding), ("--extrude=", &mut edge_options.extrude)] {
+        if let Some(pixels) = take_option(&mut args, name) {
+            match pixels.parse() {
+                Ok(pixels) => *value = pixels,
+                Err(_) => {
+                    eprintln!("Invalid {}: {}\n\n{}", name.trim_start_matches("--").trim_end_matches('='), pixels, USAGE);
+                    process::exit(2);
+                }
+            }
+        }
+    }
+    if let Some(option) = args.iter().find(|arg| arg.starts_with("--") && *arg != "--help") {
+        eprintln!("Unknown option: {}\n\n{}", option, USAGE);
+        process::exit(2);
+    }
+    let min_args = if verify || lint {
+        1
+    } else if preview_format.is_some() || sheet_layout.is_some() {
+        3
+    } else {
+        2
+    };
+    if args.len() < min_args || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    simple_logging::log_to_stderr(LevelFilter::Info);
+
+    if verify || lint {
+        let result = if verify { run_verify(&args) } else { run_lint(&args) };
+        match result {
+            Ok(true) => {}
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                error!("{}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if repack {
+        if let Err(e) = run_repack(&args, &repack_options, format) {
+            error!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(mut layout) = sheet_layout {
+        if let SheetLayout::Grid { columns: grid_columns } = &mut layout {
+            *grid_columns = columns;
+        }
+        if let Err(e) = run_sheet(&args, &layout) {
+            error!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(format) = preview_format {
+        preview_options.format = format;
+        if let Err(e) = run_preview(&args, &preview_options) {
+            error!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if unpack {
+        if let Err(e) = run_unpack(&args, overwrite) {
+            error!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let mut output = AtlasOutput::new(&args[1]);
+    output.backup_existing = backup;
+    output.format = format;
+    output.textures = textures;
+    if let Some(template) = template {
+        output.template = template;
+    }
+
+    let edges = EdgeSettings {
+        default: edge_options,
+        ..EdgeSettings::default()
+    };
+
+    match run(&args, &output, &edges, incremental) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Pack the collections requested on the command line
+/// # Arguments
+/// * `args` - The command line arguments, excluding the program name and options
+/// * `output` - Where to write the atlases
+/// * `edges` - How to treat the edges of the sprites in each atlas
+/// * `incremental` - Whether to only repack sprites that changed since the last pack
+/// # Returns
+/// * `bool` - Whether every collection was packed
+fn run(args: &[String], output: &AtlasOutput, edges: &EdgeSettings, incremental: bool) -> Result<bool> {
+    let sprites_path = PathBuf::from(&args[0]);
+    let collection_names = &args[2..];
+
+    let collections = select_collections(&sprites_path, collection_names)?;
+
+    if collections.is_empty() {
+        warn!("No collections found in {:?}.", sprites_path.display());
+    }
+
+    let summary = batch::pack_collections(&collections, &sprites_path, output, edges, incremental, |_| {}, || false)?;
+    for packed in &summary.packed {
+        info!(
+            "Packed collection {:?} to {:?} in {} ms",
+            packed.collection_name,
+            packed.path.display(),
+            packed.millis
+        );
+        for texture_path in &packed.textures {
+            info!("Wrote texture {:?}", texture_path.display());
+        }
+        for warning in &packed.format_warnings {
+            warn!(
+                "Frame {:?} in collection {:?} loses {:?} in the atlas's pixel format",
+                warning.sprite.path, packed.collection_name, warning.losses,
+            );
+        }
+        for violation in &packed.padding_violations {
+            warn!(
+                "Sprite {:?} in collection {:?} has {} painted pixels within its padding",
+                violation.sprite.path, packed.collection_name, violation.pixels,
+            );
+        }
+    }
+    for failed in &summary.failed {
+        error!("Failed to pack collection {:?}: {}", failed.collection_name, failed.error);
+    }
+    info!("Packed {} of {} collections in {} ms", summary.packed.len(), collections.len(), summary.millis);
+
+    Ok(summary.failed.is_empty())
+}
+
+/// Verify the collections requested on the command line
+/// # Arguments
+/// * `args` - The command line arguments, excluding the program name and options
+/// # Returns
+/// * `bool` - Whether no sprite's pixels would be lost when packing
+fn run_verify(args: &[String]) -> Result<bool> {
+    let sprites_path = PathBuf::from(&args[0]);
+    let collections = select_collections(&sprites_path, &args[1..])?;
+
+    let mut ok = true;
+    for collection in &collections {
+        let report = verify::verify_collection(collection, &sprites_path)?;
+        for sprite in &report.sprites {
+            let message = format!(
+                "Sprite {:?} in collection {:?}: {} pixels overflow its frame, {} pixels are outside the atlas, \
+                 {} pixels differ from its frame and {} pixels differ from the original atlas",
+                sprite.sprite.path, report.collection_name, sprite.overflow_pixels, sprite.out_of_bounds_pixels,
+                sprite.frame_mismatches, sprite.atlas_mismatches,
+            );
+            if sprite.has_errors() {
+                error!("{}", message);
+            } else {
+                warn!("{}", message);
+            }
+        }
+        info!("Verified {} sprites of collection {:?}", report.checked_sprites, report.collection_name);
+        ok &= report.is_ok();
+    }
+
+    Ok(ok)
+}
+
+/// Lint the collections requested on the command line
+/// # Arguments
+/// * `args` - The command line arguments, excluding the program name and options
+/// # Returns
+/// * `bool` - Whether no frame has painted pixels outside its sprite's rect
+fn run_lint(args: &[String]) -> Result<bool> {
+    let sprites_path = PathBuf::from(&args[0]);
+    let collections = select_collections(&sprites_path, &args[1..])?;
+
+    let reports = lint::lint_collections(&collections, &sprites_path)?;
+    for report in &reports {
+        let rect = &report.bounding_box;
+        warn!(
+            "Frame {:?} has {} painted pixels outside its rect that will not be packed, within {}x{} at ({}, {}), e.g. at {:?}",
+            report.sprite.path, report.pixels, rect.width, rect.height, rect.x, rect.y, report.coordinates[0],
+        );
+    }
+    info!("Linted {} collections, {} frames have pixels outside their rect", collections.len(), reports.len());
+
+    Ok(reports.is_empty())
+}
+
+/// Repack the collections requested on the command line
+/// # Arguments
+/// * `args` - The command line arguments, excluding the program name and options
+/// * `options` - How to lay out the sprites
+/// * `format` - The pixel format to write the atlases in
+fn run_repack(args: &[String], options: &RepackOptions, format: AtlasFormat) -> Result<()> {
+    let sprites_path = PathBuf::from(&args[0]);
+    let output_path = PathBuf::from(&args[1]);
+    let collections = select_collections(&sprites_path, &args[2..])?;
+
+    for collection in &collections {
+        let repack = repack::repack_collection(collection, &sprites_path, options, format)?;
+        let files = repack::write_repack(&repack, collection, &output_path)?;
+        info!(
+            "Repacked collection {:?} into a {}x{} atlas at {:?}, updated {:?} and wrote sprite definitions to {:?}",
+            collection.name,
+            repack.atlas.width(),
+            repack.atlas.height(),
+            files.atlas.display(),
+            files.sprite_info.display(),
+            files.definitions.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Export the clips requested on the command line to animated images
+/// # Arguments
+/// * `args` - The command line arguments, excluding the program name and options
+/// * `options` - The format and scale to export to
+fn run_preview(args: &[String], options: &PreviewOptions) -> Result<()> {
+    let sprites_path = PathBuf::from(&args[0]);
+    let output_path = PathBuf::from(&args[1]);
+    let clips = select_clips(&sprites_path, &args[2], &args[3..])?;
+
+    fs::create_dir_all(&output_path).map_err(|e| PackerError::io(&output_path, e))?;
+    for clip in &clips {
+        let path = output_path.join(format!("{}.{}", clip.name, options.format.extension()));
+        preview::export_clip(clip, &sprites_path, options, &path)?;
+        info!("Exported clip {:?} to {:?}", clip.name, path.display());
+    }
+
+    Ok(())
+}
+
+/// Compose the clips requested on the command line into sprite sheets
+/// # Arguments
+/// * `args` - The command line arguments, excluding the program name and options
+/// * `layout` - How to arrange the cells of each sprite sheet
+fn run_sheet(args: &[String], layout: &SheetLayout) -> Result<()> {
+    let sprites_path = PathBuf::from(&args[0]);
+    let output_path = PathBuf::from(&args[1]);
+    let clips = select_clips(&sprites_path, &args[2], &args[3..])?;
+
+    fs::create_dir_all(&output_path).map_err(|e| PackerError::io(&output_path, e))?;
+    for clip in &clips {
+        let path = output_path.join(format!("{}.png", clip.name));
+        let metadata = sheet::write_sheet(clip, &sprites_path, layout, &path)?;
+        info!(
+            "Composed clip {:?} into a {}x{} sheet of {}x{} cells at {:?}",
+            clip.name,
+            metadata.columns,
+            metadata.rows,
+            metadata.cell_width,
+            metadata.cell_height,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Unpack the animations requested on the command line
+/// # Arguments
+/// * `args` - The command line arguments, excluding the program name and options
+/// * `overwrite` - Whether to overwrite frames that already exist
+fn run_unpack(args: &[String], overwrite: bool) -> Result<()> {
+    let sprites_path = PathBuf::from(&args[0]);
+    let output_path = PathBuf::from(&args[1]);
+    let animation_names = if args.len() > 2 {
+        args[2..].to_vec()
+    } else {
+        let mut animation_names = Vec::new();
+        let anim_paths = fs::read_dir(&sprites_path).map_err(|e| PackerError::io(&sprites_path, e))?;
+        for anim_path in anim_paths {
+            let anim_path = anim_path.map_err(|e| PackerError::io(&sprites_path, e))?.path();
+            if anim_path.join("0.Atlases").join("SpriteInfo.json").exists() {
+                animation_names.push(anim_path.file_name().unwrap_or_default().to_string_lossy().to_string());
+            }
+        }
+        animation_names.sort();
+        animation_names
+    };
+
+    for animation_name in &animation_names {
+        let summary = unpack::unpack_animation(&sprites_path, animation_name, &output_path, overwrite)?;
+        if !summary.skipped.is_empty() {
+            warn!("Skipped {} existing frames of animation {:?}.", summary.skipped.len(), animation_name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the collections with the given names from a sprites folder
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `collection_names` - The names of the collections, or none for every collection
+/// # Returns
+/// * `Vec<Collection>` - The collections
+fn select_collections(sprites_path: &Path, collection_names: &[String]) -> Result<Vec<Collection>> {
+    // Broken animation folders are skipped with a warning, so that they don't stop the other collections
+    let (collections, _, _) = load::load_collections_and_animations_with_report(sprites_path)?;
+    if collection_names.is_empty() {
+        return Ok(collections);
+    }
+
+    collection_names
+        .iter()
+        .map(|collection_name| get_collection(collection_name, &collections).cloned())
+        .collect()
+}
+
+/// Load the clips with the given names from an animation in a sprites folder
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `animation_name` - The name of the animation
+/// * `clip_names` - The names of the clips, or none for every clip
+/// # Returns
+/// * `Vec<Clip>` - The clips, skipping names that aren't in the animation
+fn select_clips(sprites_path: &Path, animation_name: &str, clip_names: &[String]) -> Result<Vec<Clip>> {
+    let (_, animations, _) = load::load_collections_and_animations_with_report(sprites_path)?;
+    let animation = animations
+        .into_iter()
+        .find(|animation| animation.name == animation_name)
+        .ok_or_else(|| PackerError::MissingAnimation(animation_name.to_string()))?;
+    for clip_name in clip_names {
+        if !animation.clips.iter().any(|clip| &clip.name == clip_name) {
+            warn!("Clip {:?} not found in animation {:?}.", clip_name, animation.name);
+        }
+    }
+
+    Ok(animation
+        .clips
+        .into_iter()
+        .filter(|clip| clip_names.is_empty() || clip_names.contains(&clip.name))
+        .collect())
+}
+
+/// Remove a flag from the command line arguments
+/// # Arguments
+/// * `args` - The command line arguments
+/// * `flag` - The flag, e.g. `--backup`
+/// # Returns
+/// * `bool` - Whether the flag was given
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let len = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != len
+}
+
+/// Remove an option from the command line arguments
+/// # Arguments
+/// * `args` - The command line arguments
+/// * `prefix` - The option's prefix, e.g. `--template=`
+/// # Returns
+/// * `Option<String>` - The value of the last occurrence of the option
+fn take_option(args: &mut Vec<String>, prefix: &str) -> Option<String> {
+    let value = args.iter().rev().find_map(|arg| arg.strip_prefix(prefix)).map(str::to_string);
+    args.retain(|arg| !arg.starts_with(prefix));
+    value
+}
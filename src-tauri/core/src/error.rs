@@ -0,0 +1,115 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// An error that occurred while loading, checking or packing sprites
+#[derive(Debug)]
+pub enum PackerError {
+    /// A file or directory could not be read or written
+    Io { path: PathBuf, source: io::Error },
+    /// A JSON file could not be parsed
+    Json { path: PathBuf, source: serde_json::Error },
+    /// An image could not be decoded or encoded
+    Image { path: PathBuf, source: image::ImageError },
+    /// No animation with the given name is loaded
+    MissingAnimation(String),
+    /// No clip with the given name is in the animation
+    MissingClip { animation: String, clip: String },
+    /// No collection with the given name is loaded
+    MissingCollection(String),
+    /// No sprite file exists at the given path
+    MissingSprite(String),
+    /// No backup set with the given ID exists
+    MissingBackup(String),
+    /// A sprite's ID could not be parsed from its file name
+    InvalidSpriteId(String),
+    /// A sprite's rect does not fit inside its frame or atlas
+    Geometry(String),
+    /// A group of differing sprites cannot be resolved with the chosen strategy
+    Unresolved(String),
+    /// Packing the given collection stopped before every sprite was packed
+    Cancelled(String),
+    /// A collection's atlas was written after one of its frames was edited, so it no longer holds the frame's old pixels
+    StaleAtlas { collection: String, sprite: String },
+}
+
+impl fmt::Display for PackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackerError::Io { path, source } => write!(f, "Failed to access {:?}: {}", path.display(), source),
+            PackerError::Json { path, source } => write!(f, "Failed to parse {:?}: {}", path.display(), source),
+            PackerError::Image { path, source } => write!(f, "Failed to process image at {:?}: {}", path.display(), source),
+            PackerError::MissingAnimation(name) => write!(f, "Failed to find animation: {}", name),
+            PackerError::MissingClip { animation, clip } => write!(f, "Failed to find clip {} in animation {}", clip, animation),
+            PackerError::MissingCollection(name) => write!(f, "Failed to find collection: {}", name),
+            PackerError::MissingSprite(path) => write!(f, "Failed to find sprite at {}", path),
+            PackerError::MissingBackup(id) => write!(f, "Failed to find backup: {}", id),
+            PackerError::InvalidSpriteId(name) => write!(f, "Failed to parse sprite ID from {}", name),
+            PackerError::Geometry(message) => write!(f, "Invalid sprite geometry: {}", message),
+            PackerError::Unresolved(message) => write!(f, "Failed to resolve sprites: {}", message),
+            PackerError::Cancelled(name) => write!(f, "Packing of collection {} was cancelled", name),
+            PackerError::StaleAtlas { collection, sprite } => write!(
+                f,
+                "The atlas of collection {} was written after sprite {} was edited, so it no longer holds the sprite's pixels from before the edit",
+                collection, sprite
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PackerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PackerError::Io { source, .. } => Some(source),
+            PackerError::Json { source, .. } => Some(source),
+            PackerError::Image { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes as `{ kind, message }` so the frontend can both branch on and display the error
+impl Serialize for PackerError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PackerError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl PackerError {
+    /// Get the name of the error's variant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PackerError::Io { .. } => "Io",
+            PackerError::Json { .. } => "Json",
+            PackerError::Image { .. } => "Image",
+            PackerError::MissingAnimation(_) => "MissingAnimation",
+            PackerError::MissingClip { .. } => "MissingClip",
+            PackerError::MissingCollection(_) => "MissingCollection",
+            PackerError::MissingSprite(_) => "MissingSprite",
+            PackerError::MissingBackup(_) => "MissingBackup",
+            PackerError::InvalidSpriteId(_) => "InvalidSpriteId",
+            PackerError::Geometry(_) => "Geometry",
+            PackerError::Unresolved(_) => "Unresolved",
+            PackerError::Cancelled(_) => "Cancelled",
+            PackerError::StaleAtlas { .. } => "StaleAtlas",
+        }
+    }
+
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        PackerError::Io { path: path.into(), source }
+    }
+
+    pub fn json(path: impl Into<PathBuf>, source: serde_json::Error) -> Self {
+        PackerError::Json { path: path.into(), source }
+    }
+
+    pub fn image(path: impl Into<PathBuf>, source: image::ImageError) -> Self {
+        PackerError::Image { path: path.into(), source }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, PackerError>;
@@ -0,0 +1,8 @@
+pub mod error;
+pub mod packer;
+pub mod tk2d;
+
+#[cfg(test)]
+mod test_support;
+
+pub use error::{PackerError, Result};
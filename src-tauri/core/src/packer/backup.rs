@@ -0,0 +1,242 @@
+use crate::error::{PackerError, Result};
+use crate::packer::output::timestamp;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The name of the file describing a backup set inside its folder
+const MANIFEST_NAME: &str = "backup.json";
+
+/// A file saved in a backup set
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// Where the file was backed up from and is restored to
+    pub original: PathBuf,
+    /// The name of the copy inside the backup set's folder
+    pub stored: String,
+}
+
+/// A snapshot of files taken before they were overwritten
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupSet {
+    /// The name of the backup set's folder, its creation time followed by `-2`, `-3` and so on if other backup sets
+    /// were created in the same second
+    pub id: String,
+    /// When the backup set was created, formatted as `YYYYMMDD-HHMMSS` in UTC
+    pub created: String,
+    /// What the files were backed up before
+    pub description: String,
+    pub files: Vec<BackupFile>,
+}
+
+/// A folder of versioned backup sets
+#[derive(Clone, Debug)]
+pub struct BackupStore {
+    root: PathBuf,
+}
+
+impl BackupStore {
+    /// Use a folder as a backup store, creating it when the first backup is made
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Copy files into a new backup set
+    /// # Arguments
+    /// * `paths` - The files to back up
+    /// * `description` - What the files are being backed up before
+    /// # Returns
+    /// * `BackupSet` - The created backup set
+    pub fn snapshot(&self, paths: &[PathBuf], description: &str) -> Result<BackupSet> {
+        let created = timestamp();
+        fs::create_dir_all(&self.root).map_err(|e| PackerError::io(&self.root, e))?;
+        // Creating the folder claims the ID, so snapshots taken at the same time, e.g. by a batch pack, never share one
+        let mut id = created.clone();
+        let mut count = 1;
+        let set_path = loop {
+            let set_path = self.root.join(&id);
+            match fs::create_dir(&set_path) {
+                Ok(()) => break set_path,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    count += 1;
+                    id = format!("{}-{}", created, count);
+                }
+                Err(e) => return Err(PackerError::io(&set_path, e)),
+            }
+        };
+
+        let mut files = Vec::new();
+        for (index, path) in paths.iter().enumerate() {
+            let extension = path.extension().map(|extension| extension.to_string_lossy().to_string()).unwrap_or_default();
+            let stored = format!("{}.{}", index, extension);
+            let stored_path = set_path.join(&stored);
+            fs::copy(path, &stored_path).map_err(|e| PackerError::io(path, e))?;
+            files.push(BackupFile {
+                original: path.clone(),
+                stored,
+            });
+        }
+
+        let backup_set = BackupSet {
+            id,
+            created,
+            description: description.to_string(),
+            files,
+        };
+        let manifest_path = set_path.join(MANIFEST_NAME);
+        let text = serde_json::to_string_pretty(&backup_set).map_err(|e| PackerError::json(&manifest_path, e))?;
+        fs::write(&manifest_path, text).map_err(|e| PackerError::io(&manifest_path, e))?;
+
+        Ok(backup_set)
+    }
+
+    /// List the backup sets in the store
+    /// # Returns
+    /// * `Vec<BackupSet>` - The backup sets, newest first
+    pub fn list(&self) -> Result<Vec<BackupSet>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut backup_sets = Vec::new();
+        let set_paths = fs::read_dir(&self.root).map_err(|e| PackerError::io(&self.root, e))?;
+        for set_path in set_paths {
+            let set_path = set_path.map_err(|e| PackerError::io(&self.root, e))?.path();
+            if set_path.join(MANIFEST_NAME).exists() {
+                backup_sets.push(load_manifest(&set_path)?);
+            }
+        }
+        backup_sets.sort_by(|a, b| (&b.created, sequence(b)).cmp(&(&a.created, sequence(a))));
+
+        Ok(backup_sets)
+    }
+
+    /// Copy the files of a backup set back to where they were backed up from
+    /// # Arguments
+    /// * `id` - The ID of the backup set
+    /// # Returns
+    /// * `Vec<PathBuf>` - The paths of the restored files
+    pub fn restore(&self, id: &str) -> Result<Vec<PathBuf>> {
+        let set_path = self.root.join(id);
+        if id.is_empty() || Path::new(id).components().count() != 1 || !set_path.join(MANIFEST_NAME).exists() {
+            return Err(PackerError::MissingBackup(id.to_string()));
+        }
+
+        let backup_set = load_manifest(&set_path)?;
+        let mut restored_paths = Vec::new();
+        for file in backup_set.files {
+            let stored_path = set_path.join(&file.stored);
+            fs::copy(&stored_path, &file.original).map_err(|e| PackerError::io(&file.original, e))?;
+            restored_paths.push(file.original);
+        }
+
+        Ok(restored_paths)
+    }
+}
+
+/// Get the number that tells a backup set apart from others created in the same second
+///
+/// IDs can't be compared as text, since `-10` would sort before `-2`.
+/// # Arguments
+/// * `backup_set` - The backup set
+/// # Returns
+/// * `u32` - 1 for the first backup set created in a second, 2 for the second and so on
+fn sequence(backup_set: &BackupSet) -> u32 {
+    backup_set
+        .id
+        .strip_prefix(&backup_set.created)
+        .and_then(|suffix| suffix.strip_prefix('-'))
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(1)
+}
+
+/// Read the manifest of a backup set
+/// # Arguments
+/// * `set_path` - The path to the backup set's folder
+fn load_manifest(set_path: &Path) -> Result<BackupSet> {
+    let manifest_path = set_path.join(MANIFEST_NAME);
+    let text = fs::read_to_string(&manifest_path).map_err(|e| PackerError::io(&manifest_path, e))?;
+    serde_json::from_str(&text).map_err(|e| PackerError::json(&manifest_path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn backup_set(id: &str, created: &str) -> BackupSet {
+        BackupSet {
+            id: id.to_string(),
+            created: created.to_string(),
+            description: String::new(),
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sequences_count_backup_sets_created_in_the_same_second() {
+        assert_eq!(sequence(&backup_set("20230102-150405", "20230102-150405")), 1);
+        assert_eq!(sequence(&backup_set("20230102-150405-2", "20230102-150405")), 2);
+        assert_eq!(sequence(&backup_set("20230102-150405-10", "20230102-150405")), 10);
+    }
+
+    #[test]
+    fn snapshots_list_newest_first_and_restore() {
+        let directory = TempDir::new("backup");
+        let store = BackupStore::new(directory.join("backups"));
+        let paths = [directory.join("Knight Cln.png"), directory.join("SpriteInfo.json")];
+
+        // Enough snapshots in quick succession that some share a second and need a two digit count
+        let mut snapshots = Vec::new();
+        for version in 0..12 {
+            for path in &paths {
+                fs::write(path, format!("{} {}", path.display(), version)).unwrap();
+            }
+            snapshots.push(store.snapshot(&paths, &format!("Version {}", version)).unwrap());
+        }
+        let listed = store.list().unwrap();
+        fs::write(&paths[0], "overwritten").unwrap();
+        fs::remove_file(&paths[1]).unwrap();
+        let restored = store.restore(&snapshots[3].id).unwrap();
+        let contents = paths.iter().map(|path| fs::read_to_string(path).unwrap()).collect::<Vec<String>>();
+        let missing = ["", "..", "../backups", "20000101-000000"].map(|id| store.restore(id));
+
+        let listed_ids = listed.iter().map(|backup_set| backup_set.id.as_str()).collect::<Vec<&str>>();
+        let snapshot_ids = snapshots.iter().rev().map(|backup_set| backup_set.id.as_str()).collect::<Vec<&str>>();
+        assert_eq!(listed_ids, snapshot_ids);
+        assert_eq!(listed[0].description, "Version 11");
+        assert_eq!(listed[0].files.len(), 2);
+
+        assert_eq!(restored, paths.to_vec());
+        assert_eq!(contents, paths.iter().map(|path| format!("{} 3", path.display())).collect::<Vec<String>>());
+        assert!(missing.iter().all(|result| matches!(result, Err(PackerError::MissingBackup(_)))));
+    }
+
+    #[test]
+    fn concurrent_snapshots_get_their_own_sets() {
+        use rayon::prelude::*;
+
+        let directory = TempDir::new("backup-concurrent");
+        let store = BackupStore::new(directory.join("backups"));
+        let paths = (0..16).map(|index| directory.join(format!("{}.png", index))).collect::<Vec<PathBuf>>();
+        for path in &paths {
+            fs::write(path, path.display().to_string()).unwrap();
+        }
+
+        let snapshots = paths
+            .par_iter()
+            .map(|path| store.snapshot(std::slice::from_ref(path), "Batch pack").unwrap())
+            .collect::<Vec<BackupSet>>();
+        let stored = snapshots
+            .iter()
+            .map(|backup_set| fs::read_to_string(directory.join("backups").join(&backup_set.id).join(&backup_set.files[0].stored)).unwrap())
+            .collect::<Vec<String>>();
+
+        let mut ids = snapshots.iter().map(|backup_set| backup_set.id.clone()).collect::<Vec<String>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), paths.len());
+        assert_eq!(stored, paths.iter().map(|path| path.display().to_string()).collect::<Vec<String>>());
+    }
+}
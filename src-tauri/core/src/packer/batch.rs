@@ -0,0 +1,188 @@
+use crate::error::{PackerError, Result};
+use crate::packer::edges::{apply_edges, EdgeSettings, PaddingViolation};
+use crate::packer::format::{format_warnings, FormatWarning};
+use crate::packer::incremental::pack_collection_incremental;
+use crate::packer::output::AtlasOutput;
+use crate::packer::pack::pack_collection;
+use crate::packer::texture::write_textures;
+use crate::tk2d::cln::Collection;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The progress of a batch pack after one of its collections made progress
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchProgress {
+    /// The name of the collection that made progress
+    #[serde(rename = "collectionName")]
+    pub collection_name: String,
+    /// The pack progress of that collection as a percentage
+    pub progress: usize,
+    /// The pack progress of the whole batch as a percentage
+    #[serde(rename = "totalProgress")]
+    pub total_progress: usize,
+}
+
+/// A collection that was packed and written to disk
+#[derive(Clone, Debug, Serialize)]
+pub struct PackedCollection {
+    #[serde(rename = "collectionName")]
+    pub collection_name: String,
+    /// The path the atlas was written to
+    pub path: PathBuf,
+    /// The path the atlas previously at `path` was backed up to
+    #[serde(rename = "backupPath")]
+    pub backup_path: Option<PathBuf>,
+    /// The sprites whose padding in the atlas was not transparent, if padding was checked
+    #[serde(rename = "paddingViolations")]
+    pub padding_violations: Vec<PaddingViolation>,
+    /// The packed frames whose pixels lost information in the atlas's pixel format
+    #[serde(rename = "formatWarnings")]
+    pub format_warnings: Vec<FormatWarning>,
+    /// The paths the compressed textures of the atlas were written to
+    pub textures: Vec<PathBuf>,
+    /// The time taken to pack and write the atlas
+    pub millis: u128,
+}
+
+/// A collection that could not be packed
+#[derive(Clone, Debug, Serialize)]
+pub struct FailedCollection {
+    #[serde(rename = "collectionName")]
+    pub collection_name: String,
+    pub error: String,
+}
+
+/// The outcome of a batch pack
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BatchSummary {
+    pub packed: Vec<PackedCollection>,
+    pub failed: Vec<FailedCollection>,
+    /// Whether the batch was cancelled before every collection was packed
+    pub cancelled: bool,
+    /// The time taken by the whole batch
+    pub millis: u128,
+}
+
+/// Pack several collections concurrently and write each atlas where `output` says
+/// # Arguments
+/// * `collections` - The collections to pack
+/// * `sprites_path` - The path to sprite files
+/// * `output` - Where to write the atlases
+/// * `edges` - How to treat the edges of the sprites in each collection's atlas
+/// * `incremental` - Whether to only repack sprites that changed since the atlases at the output paths were generated
+/// * `on_progress` - Called whenever any collection in the batch makes progress
+/// * `is_cancelled` - Polled while packing; the whole batch stops once it returns true
+/// # Returns
+/// * `BatchSummary` - The packed and failed collections and how long they took
+pub fn pack_collections<P, C>(
+    collections: &[Collection],
+    sprites_path: &Path,
+    output: &AtlasOutput,
+    edges: &EdgeSettings,
+    incremental: bool,
+    on_progress: P,
+    is_cancelled: C,
+) -> Result<BatchSummary>
+where
+    P: Fn(BatchProgress) + Sync,
+    C: Fn() -> bool + Sync,
+{
+    let start = Instant::now();
+
+    let cancelled = AtomicBool::new(false);
+    let check_cancelled = || {
+        if cancelled.load(Ordering::Relaxed) {
+            return true;
+        }
+        if is_cancelled() {
+            cancelled.store(true, Ordering::Relaxed);
+            return true;
+        }
+        false
+    };
+
+    let progresses = Mutex::new(vec![0; collections.len()]);
+    let results = collections
+        .par_iter()
+        .enumerate()
+        .map(|(index, collection)| {
+            let collection_start = Instant::now();
+            let report_progress = |progress| {
+                let total_progress = {
+                    let mut progresses = progresses.lock().expect("Failed to lock batch progress");
+                    progresses[index] = progress;
+                    progresses.iter().sum::<usize>() / progresses.len()
+                };
+                on_progress(BatchProgress {
+                    collection_name: collection.name.clone(),
+                    progress,
+                    total_progress,
+                });
+            };
+
+            let edge_options = edges.for_collection(&collection.name);
+            let previous_atlas_path = output.previous_atlas_path(collection);
+            let (atlas_path, backup_path, padding_violations, format_warnings, textures) = if incremental {
+                match pack_collection_incremental(
+                    collection,
+                    sprites_path,
+                    previous_atlas_path.as_deref(),
+                    output.format,
+                    report_progress,
+                    check_cancelled,
+                )? {
+                    Some(mut pack) => {
+                        let format_warnings = format_warnings(&pack.repacked, sprites_path, pack.atlas.color())?;
+                        let padding_violations = apply_edges(&mut pack.atlas, &collection.sprites, &edge_options);
+                        let (atlas_path, backup_path) = output.prepare(collection)?;
+                        pack.save(&atlas_path)?;
+                        let textures = write_textures(&pack.atlas, &atlas_path, &output.textures)?;
+                        (atlas_path, backup_path, padding_violations, format_warnings, textures)
+                    }
+                    None => return Ok(None),
+                }
+            } else {
+                match pack_collection(collection, sprites_path, output.format, report_progress, check_cancelled)? {
+                    Some(mut atlas) => {
+                        let format_warnings = format_warnings(&collection.sprites, sprites_path, atlas.color())?;
+                        let padding_violations = apply_edges(&mut atlas, &collection.sprites, &edge_options);
+                        let (atlas_path, backup_path) = output.prepare(collection)?;
+                        atlas.save(&atlas_path).map_err(|e| PackerError::image(&atlas_path, e))?;
+                        let textures = write_textures(&atlas, &atlas_path, &output.textures)?;
+                        (atlas_path, backup_path, padding_violations, format_warnings, textures)
+                    }
+                    None => return Ok(None),
+                }
+            };
+            Ok(Some(PackedCollection {
+                collection_name: collection.name.clone(),
+                path: atlas_path,
+                backup_path,
+                padding_violations,
+                format_warnings,
+                textures,
+                millis: collection_start.elapsed().as_millis(),
+            }))
+        })
+        .collect::<Vec<Result<Option<PackedCollection>>>>();
+
+    let mut summary = BatchSummary::default();
+    for (collection, result) in collections.iter().zip(results) {
+        match result {
+            Ok(Some(packed)) => summary.packed.push(packed),
+            Ok(None) => {}
+            Err(e) => summary.failed.push(FailedCollection {
+                collection_name: collection.name.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    summary.cancelled = cancelled.load(Ordering::Relaxed);
+    summary.millis = start.elapsed().as_millis();
+
+    Ok(summary)
+}
@@ -0,0 +1,99 @@
+use crate::error::Result;
+use crate::packer::open_sprite;
+use crate::tk2d::cln::Collection;
+use crate::tk2d::compare::CompareMode;
+use crate::tk2d::sprite::{Sprite, SpriteImage};
+use log::warn;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Check whether any sprites and their duplicates are not identical.
+/// # Arguments
+/// * `collections` - The collections to check
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `mode` - How strictly the sprites must match
+/// # Returns
+/// * `Vec<Sprite>` - A list of duplicate sprites that are not identical
+pub fn check(collections: &[Collection], sprites_path: &Path, mode: CompareMode) -> Result<Vec<Sprite>> {
+    let mut problem_sprites = Vec::new();
+    for collection in collections {
+        let mut sprite_map: HashMap<u32, Vec<Sprite>> = HashMap::new();
+        for sprite in &collection.sprites {
+            let existing_sprites = match sprite_map.get_mut(&sprite.id) {
+                Some(existing_sprites) => existing_sprites,
+                None => {
+                    sprite_map.insert(sprite.id, vec![sprite.clone()]);
+                    continue;
+                }
+            };
+
+            if existing_sprites.contains(sprite) {
+                existing_sprites.push(sprite.clone());
+                continue;
+            }
+
+            let (_, image) = open_sprite(sprite, sprites_path)?;
+            let sprite_image = SpriteImage {
+                sprite: sprite.clone(),
+                image,
+            };
+            for existing_sprite in existing_sprites.iter() {
+                let (_, existing_image) = open_sprite(existing_sprite, sprites_path)?;
+                let existing_sprite_image = SpriteImage {
+                    sprite: existing_sprite.clone(),
+                    image: existing_image,
+                };
+
+                if !existing_sprite_image.equals_with(&sprite_image, mode)? {
+                    for sprite in existing_sprites.iter() {
+                        if !problem_sprites.contains(sprite) {
+                            problem_sprites.push(sprite.clone());
+                        }
+                    }
+                    if !problem_sprites.contains(sprite) {
+                        problem_sprites.push(sprite.clone());
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    for sprite in &problem_sprites {
+        warn!("Problem sprite at path {:?} is different from sprites with the same ID.", sprite.path);
+    }
+
+    Ok(problem_sprites)
+}
+
+/// Check whether a sprite differs from any other sprite with the same ID in its collection
+/// # Arguments
+/// * `sprite` - The sprite to check
+/// * `collection` - The collection containing the sprite
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `mode` - How strictly the sprites must match
+/// # Returns
+/// * `bool` - Whether any sprite with the same ID does not match
+pub fn differs_from_duplicates(sprite: &Sprite, collection: &Collection, sprites_path: &Path, mode: CompareMode) -> Result<bool> {
+    let (_, image) = open_sprite(sprite, sprites_path)?;
+    let sprite_image = SpriteImage {
+        sprite: sprite.clone(),
+        image,
+    };
+    for duplicate in &collection.sprites {
+        if duplicate.id != sprite.id || duplicate.path == sprite.path {
+            continue;
+        }
+
+        let (_, duplicate_image) = open_sprite(duplicate, sprites_path)?;
+        let duplicate_image = SpriteImage {
+            sprite: duplicate.clone(),
+            image: duplicate_image,
+        };
+        if !sprite_image.equals_with(&duplicate_image, mode)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,231 @@
+use crate::error::{PackerError, Result};
+use crate::packer::sprite_path;
+use crate::tk2d::sprite::Sprite;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A rect in texture coordinates from 0 to 1, with v measured from the bottom of the atlas like in Unity
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UvRect {
+    #[serde(rename = "uMin")]
+    pub u_min: f32,
+    #[serde(rename = "vMin")]
+    pub v_min: f32,
+    #[serde(rename = "uMax")]
+    pub u_max: f32,
+    #[serde(rename = "vMax")]
+    pub v_max: f32,
+}
+
+/// A rect in pixels, with y measured from the bottom like in the sprite info
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PixelRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// The data a sprite definition of a tk2d sprite collection needs to show a sprite from an atlas
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpriteDefinition {
+    pub id: u32,
+    pub name: String,
+    /// The frame files of every sprite with this ID
+    pub frames: Vec<String>,
+    /// The rect the sprite takes up in the atlas, in texture coordinates
+    pub uv: UvRect,
+    /// The rect the sprite takes up in the atlas, which is its trimmed rect turned sideways if it is flipped
+    #[serde(rename = "atlasRect")]
+    pub atlas_rect: PixelRect,
+    /// The trimmed rect in the frame, i.e. `xr`, `yr`, `width` and `height`
+    #[serde(rename = "trimRect")]
+    pub trim_rect: PixelRect,
+    /// The width and height of the untrimmed frame
+    #[serde(rename = "frameSize")]
+    pub frame_size: (u32, u32),
+    /// Whether the sprite is rotated in the atlas
+    pub flipped: bool,
+    /// The offset of the trimmed rect from the bottom left corner of the frame, in pixels
+    pub offset: (i32, i32),
+    /// The centre of the frame relative to the trimmed rect, from 0 to 1 across the rect
+    pub pivot: (f32, f32),
+}
+
+/// The sprite definitions of a collection laid out in an atlas
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DefinitionsFile {
+    #[serde(rename = "collectionName")]
+    pub collection_name: String,
+    /// The file name of the atlas
+    pub atlas: String,
+    #[serde(rename = "atlasWidth")]
+    pub atlas_width: u32,
+    #[serde(rename = "atlasHeight")]
+    pub atlas_height: u32,
+    /// A definition for each sprite ID, ordered by ID
+    pub sprites: Vec<SpriteDefinition>,
+}
+
+impl DefinitionsFile {
+    /// Get the path of the definitions belonging to an atlas, e.g. `Knight Cln.definitions.json` for `Knight Cln.png`
+    pub fn path_for(atlas_path: &Path) -> PathBuf {
+        atlas_path.with_extension("definitions.json")
+    }
+
+    /// Write the definitions next to their atlas
+    /// # Arguments
+    /// * `atlas_path` - The path to the atlas the definitions belong to
+    /// # Returns
+    /// * `PathBuf` - The path the definitions were written to
+    pub fn save(&self, atlas_path: &Path) -> Result<PathBuf> {
+        let path = Self::path_for(atlas_path);
+        let text = serde_json::to_string_pretty(self).map_err(|e| PackerError::json(&path, e))?;
+        fs::write(&path, text).map_err(|e| PackerError::io(&path, e))?;
+        Ok(path)
+    }
+}
+
+/// Build the sprite definitions of a collection laid out in an atlas
+/// # Arguments
+/// * `collection_name` - The name of the collection
+/// * `sprites` - The collection's sprites, placed in the atlas
+/// * `atlas_name` - The file name of the atlas
+/// * `atlas_size` - The width and height of the atlas
+/// * `sprites_path` - The path to the folder containing the animation folders, to read the size of each frame from
+/// # Returns
+/// * `DefinitionsFile` - A definition for each sprite ID, using the first sprite with the ID
+pub fn build_definitions(
+    collection_name: &str,
+    sprites: &[Sprite],
+    atlas_name: &str,
+    atlas_size: (u32, u32),
+    sprites_path: &Path,
+) -> Result<DefinitionsFile> {
+    let mut groups: BTreeMap<u32, Vec<&Sprite>> = BTreeMap::new();
+    for sprite in sprites {
+        let group = groups.entry(sprite.id).or_default();
+        if !group.iter().any(|existing| existing.path == sprite.path) {
+            group.push(sprite);
+        }
+    }
+
+    let definitions = groups
+        .into_values()
+        .map(|group| {
+            let path = sprite_path(group[0], sprites_path)?;
+            let frame_size = image::image_dimensions(&path).map_err(|e| PackerError::image(&path, e))?;
+            Ok(sprite_definition(&group, frame_size, atlas_size))
+        })
+        .collect::<Result<Vec<SpriteDefinition>>>()?;
+
+    Ok(DefinitionsFile {
+        collection_name: collection_name.to_string(),
+        atlas: atlas_name.to_string(),
+        atlas_width: atlas_size.0,
+        atlas_height: atlas_size.1,
+        sprites: definitions,
+    })
+}
+
+/// Build the definition of a group of sprites with the same ID
+/// # Arguments
+/// * `group` - The sprites with the same ID, whose first sprite's rect is used
+/// * `frame_size` - The width and height of the first sprite's frame
+/// * `atlas_size` - The width and height of the atlas
+/// # Returns
+/// * `SpriteDefinition` - The definition
+fn sprite_definition(group: &[&Sprite], frame_size: (u32, u32), atlas_size: (u32, u32)) -> SpriteDefinition {
+    let sprite = group[0];
+    let (atlas_width, atlas_height) = (atlas_size.0 as f32, atlas_size.1 as f32);
+    let (width, height) = sprite.atlas_size();
+    let atlas_rect = PixelRect { x: sprite.x, y: sprite.y, width, height };
+    let uv = UvRect {
+        u_min: atlas_rect.x as f32 / atlas_width,
+        v_min: atlas_rect.y as f32 / atlas_height,
+        u_max: (atlas_rect.x + atlas_rect.width) as f32 / atlas_width,
+        v_max: (atlas_rect.y + atlas_rect.height) as f32 / atlas_height,
+    };
+    let pivot = (
+        (frame_size.0 as f32 / 2.0 - sprite.xr as f32) / sprite.width.max(1) as f32,
+        (frame_size.1 as f32 / 2.0 - sprite.yr as f32) / sprite.height.max(1) as f32,
+    );
+
+    SpriteDefinition {
+        id: sprite.id,
+        name: sprite.name.clone(),
+        frames: group.iter().map(|sprite| sprite.path.clone()).collect(),
+        uv,
+        atlas_rect,
+        trim_rect: PixelRect { x: sprite.xr, y: sprite.yr, width: sprite.width, height: sprite.height },
+        frame_size,
+        flipped: sprite.flipped,
+        offset: (sprite.xr, sprite.yr),
+        pivot,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+    use image::RgbaImage;
+
+    /// A sprite with a 3x2 rect at (1, 1) in its frame, placed at (4, 2) in an atlas
+    fn sprite(name: &str, id: u32, flipped: bool) -> Sprite {
+        Sprite { id, x: 4, y: 2, flipped, ..test_support::sprite(name) }
+    }
+
+    #[test]
+    fn flipped_sprites_take_up_their_rect_turned_sideways() {
+        let unflipped = sprite_definition(&[&sprite("Idle-0-1.png", 1, false)], (6, 5), (16, 8));
+        let flipped = sprite_definition(&[&sprite("Idle-0-1.png", 1, true)], (6, 5), (16, 8));
+
+        assert_eq!(unflipped.atlas_rect, PixelRect { x: 4, y: 2, width: 3, height: 2 });
+        assert_eq!(unflipped.uv, UvRect { u_min: 0.25, v_min: 0.25, u_max: 0.4375, v_max: 0.5 });
+        assert_eq!(flipped.atlas_rect, PixelRect { x: 4, y: 2, width: 2, height: 3 });
+        assert_eq!(flipped.uv, UvRect { u_min: 0.25, v_min: 0.25, u_max: 0.375, v_max: 0.625 });
+
+        // The trimmed rect and pivot are in frame space, so flipping does not change them
+        for definition in [&unflipped, &flipped] {
+            assert_eq!(definition.trim_rect, PixelRect { x: 1, y: 1, width: 3, height: 2 });
+            assert_eq!(definition.offset, (1, 1));
+            assert_eq!(definition.pivot, (2.0 / 3.0, 0.75));
+        }
+        assert!(flipped.flipped && !unflipped.flipped);
+    }
+
+    #[test]
+    fn pivots_can_lie_outside_the_trimmed_rect() {
+        // A 2x1 rect in the top right corner of an 8x6 frame
+        let corner = Sprite { xr: 6, yr: 5, width: 2, height: 1, ..sprite("Idle-0-1.png", 1, true) };
+        let definition = sprite_definition(&[&corner], (8, 6), (16, 8));
+        assert_eq!(definition.atlas_rect, PixelRect { x: 4, y: 2, width: 1, height: 2 });
+        assert_eq!(definition.pivot, (-1.0, -2.0));
+    }
+
+    #[test]
+    fn builds_a_definition_for_each_id() {
+        let sprites_path = TempDir::new("definitions");
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+        for name in ["Idle-0-2.png", "Idle-1-1.png", "Idle-2-2.png"] {
+            RgbaImage::new(6, 5).save(sprites_path.join("Knight/Idle").join(name)).unwrap();
+        }
+
+        let sprites = [
+            sprite("Idle-0-2.png", 2, true),
+            sprite("Idle-1-1.png", 1, false),
+            sprite("Idle-2-2.png", 2, true),
+            sprite("Idle-0-2.png", 2, true),
+        ];
+        let definitions = build_definitions("Knight Cln", &sprites, "Knight Cln.png", (16, 8), &sprites_path).unwrap();
+
+        assert_eq!(definitions.sprites.iter().map(|definition| definition.id).collect::<Vec<u32>>(), vec![1, 2]);
+        assert_eq!(definitions.sprites[1].name, "Idle-0-2.png");
+        assert_eq!(definitions.sprites[1].frames, vec!["Knight/Idle/Idle-0-2.png", "Knight/Idle/Idle-2-2.png"]);
+        assert!(definitions.sprites.iter().all(|definition| definition.frame_size == (6, 5)));
+        assert_eq!(definitions.sprites[1].uv, UvRect { u_min: 0.25, v_min: 0.25, u_max: 0.375, v_max: 0.625 });
+    }
+}
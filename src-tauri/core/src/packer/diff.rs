@@ -0,0 +1,251 @@
+use crate::error::Result;
+use crate::packer::{open_sprite, png_data_url};
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::{Sprite, SpriteImage};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A rectangle of pixels in an image, measured from the image's top left corner
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct DiffRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// How the trimmed rects of two sprite images differ
+#[derive(Clone, Debug, Serialize)]
+pub struct PixelDiff {
+    /// Whether the trimmed rects have different sizes, in which case no pixels were compared
+    #[serde(rename = "sizeMismatch")]
+    pub size_mismatch: bool,
+    /// The number of pixels that differ
+    #[serde(rename = "differingPixels")]
+    pub differing_pixels: u32,
+    /// The smallest rect containing every differing pixel
+    #[serde(rename = "boundingBox")]
+    pub bounding_box: Option<DiffRect>,
+    /// The largest difference in any channel of any pixel
+    #[serde(rename = "maxChannelDelta")]
+    pub max_channel_delta: u8,
+    /// An image the size of the trimmed rect with differing pixels in opaque red, as a PNG data URL
+    pub mask: Option<String>,
+}
+
+impl PixelDiff {
+    /// Whether any pixels differ
+    pub fn differs(&self) -> bool {
+        self.size_mismatch || self.differing_pixels > 0
+    }
+}
+
+/// A sprite that differs from the first sprite with the same ID
+#[derive(Clone, Debug, Serialize)]
+pub struct VariantDiff {
+    pub sprite: Sprite,
+    pub diff: PixelDiff,
+}
+
+/// The sprites sharing an ID in a collection that differ from each other
+#[derive(Clone, Debug, Serialize)]
+pub struct SpriteDiff {
+    #[serde(rename = "collectionName")]
+    pub collection_name: String,
+    pub id: u32,
+    /// The sprite the others were compared with
+    pub reference: Sprite,
+    /// The sprites that differ from the reference
+    pub variants: Vec<VariantDiff>,
+}
+
+/// Compare the trimmed rects of two sprite images pixel by pixel
+/// # Arguments
+/// * `reference` - The sprite image to compare with
+/// * `other` - The sprite image to compare
+/// * `with_mask` - Whether to render an image of the differing pixels
+/// # Returns
+/// * `PixelDiff` - How the trimmed rects differ
+pub fn diff_sprite_images(reference: &SpriteImage, other: &SpriteImage, with_mask: bool) -> Result<PixelDiff> {
+    let frame1 = reference.trim()?;
+    let frame2 = other.trim()?;
+    if frame1.dimensions() != frame2.dimensions() {
+        return Ok(PixelDiff {
+            size_mismatch: true,
+            differing_pixels: 0,
+            bounding_box: None,
+            max_channel_delta: 0,
+            mask: None,
+        });
+    }
+
+    let (width, height) = frame1.dimensions();
+    let mut mask = if with_mask { Some(RgbaImage::new(width, height)) } else { None };
+    let mut differing_pixels = 0;
+    let mut max_channel_delta = 0;
+    let (mut x_min, mut y_min, mut x_max, mut y_max) = (u32::MAX, u32::MAX, 0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel1 = frame1.get_pixel(x, y);
+            let pixel2 = frame2.get_pixel(x, y);
+            if pixel1 == pixel2 {
+                continue;
+            }
+
+            differing_pixels += 1;
+            for (channel1, channel2) in pixel1.0.iter().zip(pixel2.0.iter()) {
+                max_channel_delta = max_channel_delta.max(channel1.abs_diff(*channel2));
+            }
+            x_min = x_min.min(x);
+            y_min = y_min.min(y);
+            x_max = x_max.max(x);
+            y_max = y_max.max(y);
+            if let Some(mask) = mask.as_mut() {
+                mask.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            }
+        }
+    }
+
+    let bounding_box = if differing_pixels > 0 {
+        Some(DiffRect {
+            x: x_min,
+            y: y_min,
+            width: x_max - x_min + 1,
+            height: y_max - y_min + 1,
+        })
+    } else {
+        None
+    };
+    let mask = match mask {
+        Some(mask) if differing_pixels > 0 => {
+            Some(png_data_url(&DynamicImage::ImageRgba8(mask), Path::new(&other.sprite.path))?)
+        }
+        _ => None,
+    };
+
+    Ok(PixelDiff {
+        size_mismatch: false,
+        differing_pixels,
+        bounding_box,
+        max_channel_delta,
+        mask,
+    })
+}
+
+/// Compare every group of sprites sharing an ID in some collections
+/// # Arguments
+/// * `collections` - The collections to compare the sprites of
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `with_masks` - Whether to render an image of the differing pixels of each variant
+/// # Returns
+/// * `Vec<SpriteDiff>` - The groups containing sprites that differ, ordered by collection and ID
+pub fn diff_collections(collections: &[Collection], sprites_path: &Path, with_masks: bool) -> Result<Vec<SpriteDiff>> {
+    let mut groups = Vec::new();
+    for collection in collections {
+        let mut sprite_map: BTreeMap<u32, Vec<&Sprite>> = BTreeMap::new();
+        for sprite in &collection.sprites {
+            let sprites = sprite_map.entry(sprite.id).or_default();
+            if !sprites.iter().any(|existing| existing.path == sprite.path) {
+                sprites.push(sprite);
+            }
+        }
+        groups.extend(sprite_map.into_values().filter(|sprites| sprites.len() > 1));
+    }
+
+    let diffs = groups
+        .par_iter()
+        .map(|sprites| diff_group(sprites, sprites_path, with_masks))
+        .collect::<Result<Vec<Option<SpriteDiff>>>>()?;
+
+    Ok(diffs.into_iter().flatten().collect())
+}
+
+/// Compare a group of sprites sharing an ID with the first sprite in the group
+/// # Arguments
+/// * `sprites` - The sprites sharing an ID
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `with_masks` - Whether to render an image of the differing pixels of each variant
+/// # Returns
+/// * `Option<SpriteDiff>` - The differing sprites, or `None` if every sprite is identical
+fn diff_group(sprites: &[&Sprite], sprites_path: &Path, with_masks: bool) -> Result<Option<SpriteDiff>> {
+    let reference = sprites[0];
+    let (_, reference_image) = open_sprite(reference, sprites_path)?;
+    let reference_image = SpriteImage {
+        sprite: reference.clone(),
+        image: reference_image,
+    };
+
+    let mut variants = Vec::new();
+    for sprite in &sprites[1..] {
+        let (_, image) = open_sprite(sprite, sprites_path)?;
+        let sprite_image = SpriteImage {
+            sprite: (*sprite).clone(),
+            image,
+        };
+        let diff = diff_sprite_images(&reference_image, &sprite_image, with_masks)?;
+        if diff.differs() {
+            variants.push(VariantDiff {
+                sprite: (*sprite).clone(),
+                diff,
+            });
+        }
+    }
+
+    if variants.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(SpriteDiff {
+        collection_name: reference.collection_name.clone(),
+        id: reference.id,
+        reference: reference.clone(),
+        variants,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{frame, sprite};
+
+    fn image(width: i32, height: i32, frame: RgbaImage) -> SpriteImage {
+        SpriteImage { sprite: Sprite { width, height, ..sprite("Idle-0-1.png") }, image: DynamicImage::ImageRgba8(frame) }
+    }
+
+    #[test]
+    fn identical_rects_do_not_differ() {
+        let mut changed = frame(100).into_rgba8();
+        // Outside the rect, so not compared
+        changed.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        let diff = diff_sprite_images(&image(3, 2, frame(100).into_rgba8()), &image(3, 2, changed), true).unwrap();
+        assert!(!diff.differs());
+        assert_eq!((diff.bounding_box, diff.max_channel_delta), (None, 0));
+        assert!(diff.mask.is_none());
+    }
+
+    #[test]
+    fn finds_the_bounding_box_and_largest_channel_delta() {
+        let mut changed = frame(100).into_rgba8();
+        // The rect covers rows 2 and 3 and columns 1 to 3 of the frame, from its top left
+        changed.put_pixel(1, 2, Rgba([100, 40, 130, 255]));
+        changed.put_pixel(3, 3, Rgba([100, 120, 150, 200]));
+        let diff = diff_sprite_images(&image(3, 2, frame(100).into_rgba8()), &image(3, 2, changed), true).unwrap();
+
+        assert!(diff.differs() && !diff.size_mismatch);
+        assert_eq!(diff.differing_pixels, 2);
+        assert_eq!(diff.bounding_box, Some(DiffRect { x: 0, y: 0, width: 3, height: 2 }));
+        assert_eq!(diff.max_channel_delta, 55);
+        assert!(diff.mask.unwrap().starts_with("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn rects_of_different_sizes_are_not_compared() {
+        let diff = diff_sprite_images(&image(3, 2, frame(100).into_rgba8()), &image(2, 3, frame(100).into_rgba8()), true).unwrap();
+        assert!(diff.differs() && diff.size_mismatch);
+        assert_eq!((diff.differing_pixels, diff.bounding_box, diff.max_channel_delta), (0, None, 0));
+        assert!(diff.mask.is_none());
+    }
+}
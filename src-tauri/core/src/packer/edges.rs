@@ -0,0 +1,389 @@
+use crate::packer::format::{convert_color, is_wide};
+use crate::tk2d::sprite::Sprite;
+use image::{DynamicImage, ImageBuffer, Pixel, Primitive};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How to treat the edges of sprites in a packed atlas, so that bilinear filtering in-game doesn't show seams
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EdgeOptions {
+    /// The number of pixels around each sprite's rect that must be transparent, or 0 to not check
+    pub padding: u32,
+    /// The number of pixels to repeat each sprite's outermost pixels into around its rect
+    pub extrude: u32,
+    /// Whether to give fully transparent pixels the color of their nearest painted pixels
+    pub bleed: bool,
+}
+
+impl EdgeOptions {
+    /// Whether the options leave a packed atlas unchanged and unchecked
+    pub fn is_noop(&self) -> bool {
+        self.padding == 0 && self.extrude == 0 && !self.bleed
+    }
+}
+
+/// Edge options for every collection, with overrides for some collections
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EdgeSettings {
+    /// The options for collections without their own
+    pub default: EdgeOptions,
+    /// The options for specific collections, by collection name
+    pub collections: BTreeMap<String, EdgeOptions>,
+}
+
+impl EdgeSettings {
+    /// Get the edge options of a collection
+    pub fn for_collection(&self, collection_name: &str) -> EdgeOptions {
+        self.collections.get(collection_name).copied().unwrap_or(self.default)
+    }
+}
+
+/// A sprite whose padding in a packed atlas has painted pixels that don't belong to it
+#[derive(Clone, Debug, Serialize)]
+pub struct PaddingViolation {
+    pub sprite: Sprite,
+    /// The number of painted pixels within the padding around the sprite's rect
+    pub pixels: u32,
+}
+
+/// A rect in an atlas from its top left corner, clipped to the atlas
+#[derive(Clone, Copy, Debug)]
+struct AtlasRect {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+/// Check, extrude and bleed the edges of the sprites in a packed atlas, in that order
+///
+/// The atlas keeps its color type, and is edited at 16 bits per channel if it has more than 8.
+/// # Arguments
+/// * `atlas` - The packed atlas
+/// * `sprites` - The sprites packed into the atlas
+/// * `options` - What to do to the edges
+/// # Returns
+/// * `Vec<PaddingViolation>` - The sprites whose padding was not transparent before extruding
+pub fn apply_edges(atlas: &mut DynamicImage, sprites: &[Sprite], options: &EdgeOptions) -> Vec<PaddingViolation> {
+    if options.is_noop() {
+        return Vec::new();
+    }
+
+    let color_type = atlas.color();
+    let (edited, violations) = if is_wide(color_type) {
+        let mut image = atlas.to_rgba16();
+        let violations = apply_to_pixels(&mut image, sprites, options);
+        (DynamicImage::ImageRgba16(image), violations)
+    } else {
+        let mut image = atlas.to_rgba8();
+        let violations = apply_to_pixels(&mut image, sprites, options);
+        (DynamicImage::ImageRgba8(image), violations)
+    };
+    *atlas = convert_color(edited, color_type);
+
+    violations
+}
+
+/// Check, extrude and bleed the edges of the sprites in a packed atlas with a single channel type
+fn apply_to_pixels<Px>(image: &mut ImageBuffer<Px, Vec<Px::Subpixel>>, sprites: &[Sprite], options: &EdgeOptions) -> Vec<PaddingViolation>
+where
+    Px: Pixel,
+    Px::Subpixel: Into<u32> + TryFrom<u32>,
+{
+    let rects = sprite_rects(sprites, image.width(), image.height());
+    let covered = covered_pixels(&rects, image.width(), image.height());
+    let violations = if options.padding > 0 {
+        check_padding(image, &rects, &covered, options)
+    } else {
+        Vec::new()
+    };
+    if options.extrude > 0 {
+        extrude(image, &rects, &covered, options.extrude);
+    }
+    if options.bleed {
+        bleed(image);
+    }
+
+    violations
+}
+
+/// Count the painted pixels within the padding of each sprite's rect that lie outside it
+///
+/// Pixels that extruding is about to overwrite are skipped, so that an atlas that was already extruded
+/// can be packed again.
+/// # Arguments
+/// * `atlas` - The packed atlas
+/// * `rects` - Each sprite with its rect in the atlas
+/// * `covered` - Whether each pixel of the atlas is inside any sprite's rect
+/// * `options` - The padding to check and the number of pixels that will be extruded
+/// # Returns
+/// * `Vec<PaddingViolation>` - The sprites with painted pixels in their padding
+fn check_padding<Px: Pixel>(
+    atlas: &ImageBuffer<Px, Vec<Px::Subpixel>>,
+    rects: &[(Sprite, AtlasRect)],
+    covered: &[bool],
+    options: &EdgeOptions,
+) -> Vec<PaddingViolation> {
+    let width = atlas.width();
+    let mut violations = Vec::new();
+    for (sprite, rect) in rects {
+        let pixels = ring(rect, options.padding, width, atlas.height())
+            .filter(|&(x, y)| covered[(y * width + x) as usize] || distance(rect, x, y) > options.extrude)
+            .filter(|&(x, y)| is_painted(atlas.get_pixel(x, y)))
+            .count() as u32;
+        if pixels > 0 {
+            violations.push(PaddingViolation { sprite: sprite.clone(), pixels });
+        }
+    }
+
+    violations
+}
+
+/// Repeat the outermost pixels of each sprite's rect outwards, without writing over any sprite's rect
+/// # Arguments
+/// * `atlas` - The packed atlas
+/// * `rects` - Each sprite with its rect in the atlas
+/// * `covered` - Whether each pixel of the atlas is inside any sprite's rect
+/// * `extrude` - The number of pixels to extrude by
+fn extrude<Px: Pixel>(atlas: &mut ImageBuffer<Px, Vec<Px::Subpixel>>, rects: &[(Sprite, AtlasRect)], covered: &[bool], extrude: u32) {
+    let (width, height) = atlas.dimensions();
+    for (_, rect) in rects {
+        for (x, y) in ring(rect, extrude, width, height) {
+            if covered[(y * width + x) as usize] {
+                continue;
+            }
+            let source_x = x.clamp(rect.left, rect.right - 1);
+            let source_y = y.clamp(rect.top, rect.bottom - 1);
+            let pixel = *atlas.get_pixel(source_x, source_y);
+            atlas.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+/// Give every fully transparent pixel the average color of its neighbours nearest to a painted pixel,
+/// spreading outwards from the painted pixels, so that filtering doesn't blend in black or stale colors
+/// # Arguments
+/// * `atlas` - The atlas to bleed
+fn bleed<Px>(atlas: &mut ImageBuffer<Px, Vec<Px::Subpixel>>)
+where
+    Px: Pixel,
+    Px::Subpixel: Into<u32> + TryFrom<u32>,
+{
+    let zero = Px::Subpixel::DEFAULT_MIN_VALUE;
+    let (width, height) = atlas.dimensions();
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut filled = atlas.pixels().map(is_painted).collect::<Vec<bool>>();
+    let mut queued = filled.clone();
+
+    let mut frontier = Vec::new();
+    for (x, y, pixel) in atlas.enumerate_pixels() {
+        if !is_painted(pixel) && neighbours(x, y, width, height).any(|(nx, ny)| filled[index(nx, ny)]) {
+            frontier.push((x, y));
+            queued[index(x, y)] = true;
+        }
+    }
+
+    while !frontier.is_empty() {
+        let colors = frontier
+            .iter()
+            .map(|&(x, y)| {
+                let (mut sum, mut count) = ([0u32; 3], 0);
+                for (nx, ny) in neighbours(x, y, width, height).filter(|&(nx, ny)| filled[index(nx, ny)]) {
+                    let pixel = atlas.get_pixel(nx, ny);
+                    for (total, value) in sum.iter_mut().zip(pixel.channels()) {
+                        *total += (*value).into();
+                    }
+                    count += 1;
+                }
+                // The average of values that fit in the channel type fits in it too
+                let average = |channel: usize| Px::Subpixel::try_from(sum[channel] / count).unwrap_or(zero);
+                *Px::from_slice(&[average(0), average(1), average(2), zero])
+            })
+            .collect::<Vec<Px>>();
+        for (&(x, y), color) in frontier.iter().zip(colors) {
+            atlas.put_pixel(x, y, color);
+            filled[index(x, y)] = true;
+        }
+
+        let mut next = Vec::new();
+        for &(x, y) in &frontier {
+            for (nx, ny) in neighbours(x, y, width, height) {
+                if !queued[index(nx, ny)] {
+                    queued[index(nx, ny)] = true;
+                    next.push((nx, ny));
+                }
+            }
+        }
+        frontier = next;
+    }
+}
+
+/// Whether an RGBA pixel is not fully transparent
+fn is_painted<Px: Pixel>(pixel: &Px) -> bool {
+    pixel.channels()[3] > Px::Subpixel::DEFAULT_MIN_VALUE
+}
+
+/// Get the rect of each sprite ID in an atlas, skipping empty rects
+fn sprite_rects(sprites: &[Sprite], atlas_width: u32, atlas_height: u32) -> Vec<(Sprite, AtlasRect)> {
+    let mut rects: Vec<(Sprite, AtlasRect)> = Vec::new();
+    for sprite in sprites {
+        if rects.iter().any(|(existing, _)| existing.id == sprite.id) {
+            continue;
+        }
+
+        let (rect_width, rect_height) = sprite.atlas_size();
+        let clip = |value: i32, max: u32| value.clamp(0, max as i32) as u32;
+        let rect = AtlasRect {
+            left: clip(sprite.x, atlas_width),
+            top: clip(atlas_height as i32 - (sprite.y + rect_height), atlas_height),
+            right: clip(sprite.x + rect_width, atlas_width),
+            bottom: clip(atlas_height as i32 - sprite.y, atlas_height),
+        };
+        if rect.left < rect.right && rect.top < rect.bottom {
+            rects.push((sprite.clone(), rect));
+        }
+    }
+
+    rects
+}
+
+/// Get whether each pixel of an atlas, row by row from the top, is inside any sprite's rect
+fn covered_pixels(rects: &[(Sprite, AtlasRect)], width: u32, height: u32) -> Vec<bool> {
+    let mut covered = vec![false; (width * height) as usize];
+    for (_, rect) in rects {
+        for y in rect.top..rect.bottom {
+            for x in rect.left..rect.right {
+                covered[(y * width + x) as usize] = true;
+            }
+        }
+    }
+
+    covered
+}
+
+/// Get how many pixels away from a rect a pixel is, counting diagonal steps as one
+fn distance(rect: &AtlasRect, x: u32, y: u32) -> u32 {
+    let dx = rect.left.saturating_sub(x).max(x.saturating_sub(rect.right - 1));
+    let dy = rect.top.saturating_sub(y).max(y.saturating_sub(rect.bottom - 1));
+    dx.max(dy)
+}
+
+/// Get the pixels of an atlas within some distance outside a rect
+fn ring(rect: &AtlasRect, distance: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+    let (left, top) = (rect.left.saturating_sub(distance), rect.top.saturating_sub(distance));
+    let (right, bottom) = ((rect.right + distance).min(width), (rect.bottom + distance).min(height));
+    (top..bottom)
+        .flat_map(move |y| (left..right).map(move |x| (x, y)))
+        .filter(move |&(x, y)| x < rect.left || x >= rect.right || y < rect.top || y >= rect.bottom)
+}
+
+/// Get the pixels next to a pixel, including diagonally
+fn neighbours(x: u32, y: u32, width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    (-1i32..=1)
+        .flat_map(move |dy| (-1i32..=1).map(move |dx| (x as i32 + dx, y as i32 + dy)))
+        .filter(move |&(nx, ny)| {
+            (nx, ny) != (x as i32, y as i32) && nx >= 0 && ny >= 0 && nx < width as i32 && ny < height as i32
+        })
+        .map(|(nx, ny)| (nx as u32, ny as u32))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use image::{ColorType, Rgba, RgbaImage};
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    /// A sprite with a 2x2 rect, placed at (x, 1) in an atlas
+    fn sprite(id: u32, x: i32) -> Sprite {
+        Sprite { id, x, y: 1, xr: 0, yr: 0, width: 2, ..test_support::sprite(&format!("Idle-{}-{}.png", id, id)) }
+    }
+
+    /// A transparent 12x4 atlas with a red sprite in columns 1 and 2 and a blue one right next to it in columns 3 and 4,
+    /// both in rows 1 and 2
+    fn atlas() -> RgbaImage {
+        RgbaImage::from_fn(12, 4, |x, y| match (x, y) {
+            (1..=2, 1..=2) => RED,
+            (3..=4, 1..=2) => BLUE,
+            _ => Rgba([0, 0, 0, 0]),
+        })
+    }
+
+    fn options(padding: u32, extrude: u32, bleed: bool) -> EdgeOptions {
+        EdgeOptions { padding, extrude, bleed }
+    }
+
+    #[test]
+    fn counts_painted_pixels_in_the_padding() {
+        let sprites = [sprite(1, 1), sprite(2, 3), sprite(3, 8)];
+        let mut stray = atlas();
+        stray.put_pixel(5, 0, Rgba([0, 0, 0, 1]));
+
+        let pixels = |options: &EdgeOptions| {
+            let mut atlas = DynamicImage::ImageRgba8(stray.clone());
+            apply_edges(&mut atlas, &sprites, options)
+                .into_iter()
+                .map(|violation| (violation.sprite.id, violation.pixels))
+                .collect::<Vec<(u32, u32)>>()
+        };
+        // Each sprite's neighbour is in its padding, and the stray pixel only in the blue sprite's
+        assert_eq!(pixels(&options(2, 0, false)), vec![(1, 4), (2, 5)]);
+        // Extruding overwrites the stray pixel, but never the neighbour
+        assert_eq!(pixels(&options(2, 1, false)), vec![(1, 4), (2, 4)]);
+        assert!(pixels(&options(0, 1, true)).is_empty());
+    }
+
+    #[test]
+    fn extrudes_without_overwriting_neighbouring_rects() {
+        let sprites = [sprite(1, 1), sprite(2, 3)];
+        let mut extruded = DynamicImage::ImageRgba8(atlas());
+        apply_edges(&mut extruded, &sprites, &options(0, 2, false));
+        let extruded = extruded.to_rgba8();
+
+        for y in 1..=2 {
+            assert_eq!([0, 1, 2, 3, 4, 5, 6].map(|x| *extruded.get_pixel(x, y)), [RED, RED, RED, BLUE, BLUE, BLUE, BLUE]);
+        }
+        // The corners repeat the corner pixels, and pixels further away are untouched
+        assert_eq!(*extruded.get_pixel(0, 0), RED);
+        assert_eq!(*extruded.get_pixel(6, 3), BLUE);
+        assert_eq!(extruded.get_pixel(7, 1)[3], 0);
+    }
+
+    #[test]
+    fn bleeds_colors_into_transparent_pixels_without_painting_them() {
+        let mut atlas = RgbaImage::from_pixel(5, 3, Rgba([0, 255, 0, 0]));
+        atlas.put_pixel(0, 1, RED);
+        atlas.put_pixel(4, 1, BLUE);
+        let mut bled = DynamicImage::ImageRgba8(atlas);
+        assert!(apply_edges(&mut bled, &[], &options(0, 0, true)).is_empty());
+        let bled = bled.to_rgba8();
+
+        assert_eq!(*bled.get_pixel(0, 1), RED);
+        assert_eq!(*bled.get_pixel(4, 1), BLUE);
+        for (x, color) in [(1, [255, 0, 0, 0]), (2, [127, 0, 127, 0]), (3, [0, 0, 255, 0])] {
+            for y in 0..3 {
+                assert_eq!(bled.get_pixel(x, y).0, color, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_the_color_type_of_the_atlas() {
+        let mut wide = DynamicImage::ImageRgba16(DynamicImage::ImageRgba8(atlas()).to_rgba16());
+        wide.as_mut_rgba16().unwrap().put_pixel(0, 1, Rgba([1, 2, 3, 0]));
+        apply_edges(&mut wide, &[], &options(0, 0, true));
+        assert_eq!(wide.color(), ColorType::Rgba16);
+        // Averaged in 16 bits, and still transparent
+        assert_eq!(wide.as_rgba16().unwrap().get_pixel(0, 1).0, [65535, 0, 0, 0]);
+        assert_eq!(wide.as_rgba16().unwrap().get_pixel(5, 1).0, [0, 0, 65535, 0]);
+
+        let mut unchanged = DynamicImage::ImageRgba8(atlas());
+        apply_edges(&mut unchanged, &[sprite(1, 1)], &EdgeOptions::default());
+        assert_eq!(unchanged.to_rgba8(), atlas());
+    }
+}
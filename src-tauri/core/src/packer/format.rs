@@ -0,0 +1,315 @@
+use crate::error::{PackerError, Result};
+use crate::packer::open_sprite;
+use crate::tk2d::sprite::Sprite;
+use image::{ColorType, DynamicImage};
+use log::info;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// The pixel format packed atlases are written in
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AtlasFormat {
+    /// The color type of the original atlas, except that indexed atlases are written as RGB or RGBA
+    Original,
+    /// 8 bits per channel with alpha
+    Rgba8,
+    /// 16 bits per channel with alpha, which keeps the precision of 16-bit frames
+    Rgba16,
+    /// 8 bits per channel without alpha, for atlases with no transparency
+    Rgb8,
+}
+
+impl Default for AtlasFormat {
+    fn default() -> Self {
+        AtlasFormat::Original
+    }
+}
+
+impl AtlasFormat {
+    /// Get the color type an atlas is packed and written in
+    /// # Arguments
+    /// * `original` - The color type the original atlas decodes to
+    pub fn color_type(&self, original: ColorType) -> ColorType {
+        match self {
+            AtlasFormat::Original => original,
+            AtlasFormat::Rgba8 => ColorType::Rgba8,
+            AtlasFormat::Rgba16 => ColorType::Rgba16,
+            AtlasFormat::Rgb8 => ColorType::Rgb8,
+        }
+    }
+}
+
+/// The color type of a PNG file as stored, before decoding expands it
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PngColorType {
+    Grayscale,
+    GrayscaleAlpha,
+    Rgb,
+    Rgba,
+    /// Colors from a palette, which decode to RGB, or RGBA if the palette has transparency
+    Indexed,
+}
+
+/// How a PNG file stores its pixels
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct PngFormat {
+    #[serde(rename = "colorType")]
+    pub color_type: PngColorType,
+    /// The number of bits per channel, or per palette index if indexed
+    #[serde(rename = "bitDepth")]
+    pub bit_depth: u8,
+    /// Whether the file gives some colors transparency without an alpha channel
+    pub transparency: bool,
+}
+
+/// Information a frame's pixels would lose when written into an atlas
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColorLoss {
+    /// The atlas has no alpha channel, but the frame has transparent pixels
+    Alpha,
+    /// The atlas is grayscale, but the frame has colored pixels
+    Color,
+    /// The atlas has 8 bits per channel, but the frame has 16-bit values that don't fit in 8 bits
+    Precision,
+}
+
+/// A frame whose pixels would lose information when written into its atlas
+#[derive(Clone, Debug, Serialize)]
+pub struct FormatWarning {
+    pub sprite: Sprite,
+    pub losses: Vec<ColorLoss>,
+}
+
+/// Read how a PNG file stores its pixels without decoding it
+/// # Arguments
+/// * `path` - The path to the PNG file
+/// # Returns
+/// * `PngFormat` - The file's color type and bit depth
+pub fn png_format(path: &Path) -> Result<PngFormat> {
+    let file = File::open(path).map_err(|e| PackerError::io(path, e))?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info().map_err(|e| {
+        PackerError::image(path, image::ImageError::Decoding(image::error::DecodingError::new(
+            image::ImageFormat::Png.into(),
+            e,
+        )))
+    })?;
+    let info = reader.info();
+    let color_type = match info.color_type {
+        png::ColorType::Grayscale => PngColorType::Grayscale,
+        png::ColorType::GrayscaleAlpha => PngColorType::GrayscaleAlpha,
+        png::ColorType::Rgb => PngColorType::Rgb,
+        png::ColorType::Rgba => PngColorType::Rgba,
+        png::ColorType::Indexed => PngColorType::Indexed,
+    };
+
+    Ok(PngFormat {
+        color_type,
+        bit_depth: info.bit_depth as u8,
+        transparency: info.trns.is_some(),
+    })
+}
+
+/// Open a collection's atlas and convert it to the format it is packed in
+/// # Arguments
+/// * `path` - The path to the atlas
+/// * `format` - The format to pack the atlas in
+/// # Returns
+/// * `DynamicImage` - The atlas in the color type it is packed and written in
+pub fn open_atlas(path: &Path, format: AtlasFormat) -> Result<DynamicImage> {
+    let atlas = image::open(path).map_err(|e| PackerError::image(path, e))?;
+    if format == AtlasFormat::Original {
+        if let Ok(PngFormat { color_type: PngColorType::Indexed, .. }) = png_format(path) {
+            info!("Atlas {:?} is indexed and will be written as {:?}, since its palette is not kept", path.display(), atlas.color());
+        }
+    }
+
+    let color_type = format.color_type(atlas.color());
+    Ok(convert_color(atlas, color_type))
+}
+
+/// Convert an image to a color type
+/// # Arguments
+/// * `image` - The image to convert
+/// * `color_type` - The color type to convert to
+/// # Returns
+/// * `DynamicImage` - The converted image, or the image itself if it already has the color type
+pub fn convert_color(image: DynamicImage, color_type: ColorType) -> DynamicImage {
+    if image.color() == color_type {
+        return image;
+    }
+
+    match color_type {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.into_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.into_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.into_rgb8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(image.into_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.into_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.into_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.into_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.into_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.into_rgb32f()),
+        ColorType::Rgba32F => DynamicImage::ImageRgba32F(image.into_rgba32f()),
+        _ => image,
+    }
+}
+
+/// Whether a color type has more than 8 bits per channel
+pub fn is_wide(color_type: ColorType) -> bool {
+    color_type.bytes_per_pixel() / color_type.channel_count() > 1
+}
+
+/// Find the frames whose painted pixels would lose information when written into an atlas
+/// # Arguments
+/// * `sprites` - The sprites to check the frames of
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `atlas_color` - The color type the atlas is written in
+/// # Returns
+/// * `Vec<FormatWarning>` - The frames that would lose information and what they would lose, once per frame file
+pub fn format_warnings(sprites: &[Sprite], sprites_path: &Path, atlas_color: ColorType) -> Result<Vec<FormatWarning>> {
+    let mut unique_sprites: Vec<&Sprite> = Vec::new();
+    for sprite in sprites {
+        if !unique_sprites.iter().any(|existing| existing.path == sprite.path) {
+            unique_sprites.push(sprite);
+        }
+    }
+
+    let warnings = unique_sprites
+        .par_iter()
+        .map(|sprite| {
+            let (_, frame) = open_sprite(sprite, sprites_path)?;
+            let losses = color_losses(sprite, &frame, atlas_color);
+            Ok((!losses.is_empty()).then(|| FormatWarning { sprite: (*sprite).clone(), losses }))
+        })
+        .collect::<Result<Vec<Option<FormatWarning>>>>()?;
+
+    Ok(warnings.into_iter().flatten().collect())
+}
+
+/// Get the information a sprite's painted pixels would lose when written into an atlas
+/// # Arguments
+/// * `sprite` - The sprite whose trimmed rect is checked
+/// * `frame` - The sprite's frame
+/// * `atlas_color` - The color type the atlas is written in
+/// # Returns
+/// * `Vec<ColorLoss>` - What the pixels would lose
+pub fn color_losses(sprite: &Sprite, frame: &DynamicImage, atlas_color: ColorType) -> Vec<ColorLoss> {
+    let check_alpha = !atlas_color.has_alpha() && frame.color().has_alpha();
+    let check_color = !atlas_color.has_color() && frame.color().has_color();
+    let check_precision = !is_wide(atlas_color) && is_wide(frame.color());
+    if !check_alpha && !check_color && !check_precision {
+        return Vec::new();
+    }
+
+    let frame = frame.to_rgba16();
+    let (width, height) = frame.dimensions();
+    let left = sprite.xr.clamp(0, width as i32) as u32;
+    let right = (sprite.xr + sprite.width).clamp(0, width as i32) as u32;
+    let top = (height as i32 - (sprite.yr + sprite.height)).clamp(0, height as i32) as u32;
+    let bottom = (height as i32 - sprite.yr).clamp(0, height as i32) as u32;
+
+    let (mut alpha, mut color, mut precision) = (false, false, false);
+    for y in top..bottom {
+        for x in left..right {
+            let [r, g, b, a] = frame.get_pixel(x, y).0;
+            alpha |= check_alpha && a < u16::MAX;
+            // The color of fully transparent pixels is never seen, so it is not lost
+            color |= check_color && a > 0 && (r != g || g != b);
+            precision |= check_precision && a > 0 && [r, g, b, a].iter().any(|channel| channel % 257 != 0);
+        }
+    }
+
+    [(alpha, ColorLoss::Alpha), (color, ColorLoss::Color), (precision, ColorLoss::Precision)]
+        .into_iter()
+        .filter(|(lost, _)| *lost)
+        .map(|(_, loss)| loss)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use image::{GrayImage, Rgba, RgbImage, RgbaImage};
+
+    /// A sprite with a 2x2 rect at (1, 1) from the bottom left of a 4x4 frame, i.e. rows 1 and 2 of columns 1 and 2
+    fn sprite() -> Sprite {
+        Sprite { width: 2, ..test_support::sprite("Idle-0-1.png") }
+    }
+
+    /// An opaque gray 4x4 frame with a different pixel at (x, y)
+    fn frame(x: u32, y: u32, pixel: [u16; 4]) -> DynamicImage {
+        let mut frame = image::ImageBuffer::from_pixel(4, 4, Rgba([32896u16, 32896, 32896, 65535]));
+        frame.put_pixel(x, y, Rgba(pixel));
+        DynamicImage::ImageRgba16(frame)
+    }
+
+    /// Get the losses of a frame narrowed to 8 bits per channel, so that only alpha and color can be lost
+    fn narrow_losses(frame: DynamicImage, atlas_color: ColorType) -> Vec<ColorLoss> {
+        color_losses(&sprite(), &DynamicImage::ImageRgba8(frame.to_rgba8()), atlas_color)
+    }
+
+    #[test]
+    fn finds_lost_alpha_inside_the_trimmed_rect() {
+        let faded = frame(1, 1, [0, 0, 0, 32896]);
+        assert_eq!(narrow_losses(faded.clone(), ColorType::Rgb8), vec![ColorLoss::Alpha]);
+        assert_eq!(narrow_losses(faded.clone(), ColorType::Rgba8), vec![]);
+        assert_eq!(narrow_losses(faded, ColorType::La8), vec![]);
+        // Transparent pixels outside the rect are never packed
+        assert_eq!(narrow_losses(frame(0, 3, [0, 0, 0, 0]), ColorType::Rgb8), vec![]);
+        assert_eq!(narrow_losses(frame(2, 2, [0, 0, 0, 0]), ColorType::Rgb8), vec![ColorLoss::Alpha]);
+    }
+
+    #[test]
+    fn finds_lost_color_of_painted_pixels() {
+        let red = frame(2, 1, [65535, 0, 0, 65535]);
+        assert_eq!(narrow_losses(red.clone(), ColorType::L8), vec![ColorLoss::Color]);
+        assert_eq!(narrow_losses(red, ColorType::Rgb8), vec![]);
+        assert_eq!(narrow_losses(frame(2, 1, [0, 0, 0, 65535]), ColorType::L8), vec![]);
+        // The color of a fully transparent pixel is not seen, but its transparency is
+        assert_eq!(narrow_losses(frame(2, 1, [65535, 0, 0, 0]), ColorType::La8), vec![]);
+        assert_eq!(narrow_losses(frame(2, 1, [65535, 0, 0, 0]), ColorType::L8), vec![ColorLoss::Alpha]);
+    }
+
+    #[test]
+    fn finds_lost_precision_of_16_bit_values() {
+        assert_eq!(color_losses(&sprite(), &frame(1, 2, [1000, 1000, 1000, 65535]), ColorType::Rgba8), vec![ColorLoss::Precision]);
+        assert_eq!(color_losses(&sprite(), &frame(1, 2, [1000, 1000, 1000, 65535]), ColorType::Rgba16), vec![]);
+        // Values that are multiples of 257 fit in 8 bits exactly
+        assert_eq!(color_losses(&sprite(), &frame(1, 2, [257, 514, 771, 65535]), ColorType::Rgba8), vec![]);
+        assert_eq!(color_losses(&sprite(), &frame(1, 2, [1000, 1000, 1000, 0]), ColorType::Rgba8), vec![]);
+        assert_eq!(color_losses(&sprite(), &frame(3, 0, [1000, 1000, 1000, 65535]), ColorType::Rgba8), vec![]);
+
+        let everything = frame(1, 2, [1000, 0, 0, 1000]);
+        assert_eq!(color_losses(&sprite(), &everything, ColorType::L8), vec![ColorLoss::Alpha, ColorLoss::Color, ColorLoss::Precision]);
+    }
+
+    #[test]
+    fn converting_colors_round_trips_without_loss() {
+        let rgba = RgbaImage::from_fn(4, 3, |x, y| Rgba([x as u8 * 60, y as u8 * 90, 7, 255 - x as u8 * 50]));
+        let wide = convert_color(DynamicImage::ImageRgba8(rgba.clone()), ColorType::Rgba16);
+        assert_eq!(wide.color(), ColorType::Rgba16);
+        assert_eq!(convert_color(wide, ColorType::Rgba8).as_rgba8(), Some(&rgba));
+
+        let rgb = RgbImage::from_fn(4, 3, |x, y| image::Rgb([x as u8 * 60, y as u8 * 90, 7]));
+        let with_alpha = convert_color(DynamicImage::ImageRgb8(rgb.clone()), ColorType::Rgba8);
+        assert!(with_alpha.as_rgba8().unwrap().pixels().all(|pixel| pixel[3] == 255));
+        assert_eq!(convert_color(with_alpha, ColorType::Rgb8).as_rgb8(), Some(&rgb));
+
+        let gray = GrayImage::from_fn(4, 3, |x, y| image::Luma([(x * 60 + y) as u8]));
+        let as_color = convert_color(DynamicImage::ImageLuma8(gray.clone()), ColorType::Rgba16);
+        assert_eq!(convert_color(as_color, ColorType::L8).as_luma8(), Some(&gray));
+    }
+
+    #[test]
+    fn original_format_keeps_the_color_type() {
+        assert_eq!(AtlasFormat::Original.color_type(ColorType::La16), ColorType::La16);
+        assert_eq!(AtlasFormat::Rgb8.color_type(ColorType::Rgba16), ColorType::Rgb8);
+        assert!(is_wide(ColorType::La16) && is_wide(ColorType::Rgba32F) && !is_wide(ColorType::Rgba8) && !is_wide(ColorType::L8));
+    }
+}
@@ -0,0 +1,331 @@
+use crate::error::{PackerError, Result};
+use crate::packer::format::{convert_color, open_atlas, AtlasFormat};
+use crate::packer::pack::pack_sprites;
+use crate::packer::sprite_path;
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::Sprite;
+use image::{DynamicImage, GenericImageView};
+use log::info;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The name of the hash stored in frame stamps, recorded in each manifest so that stamps hashed another way are
+/// never compared with it
+const HASH_NAME: &str = "fnv1a-64";
+
+/// The state of a frame file when it was last packed
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FrameStamp {
+    /// The file's modification time in nanoseconds since the Unix epoch
+    pub modified: u64,
+    /// The file's size in bytes
+    pub len: u64,
+    /// A hash of the file's contents, hashed with the manifest's hash
+    pub hash: u64,
+}
+
+/// The frame files an atlas was packed from, stored next to the atlas
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PackManifest {
+    /// The name of the hash the frame files were hashed with, empty in manifests written before it was recorded
+    #[serde(default)]
+    pub hash: String,
+    /// The stamp of each frame file, keyed by sprite path
+    pub frames: HashMap<String, FrameStamp>,
+}
+
+impl PackManifest {
+    /// Get the path of the manifest belonging to an atlas, e.g. `Knight Cln.pack.json` for `Knight Cln.png`
+    pub fn path_for(atlas_path: &Path) -> PathBuf {
+        atlas_path.with_extension("pack.json")
+    }
+
+    /// Read the manifest belonging to an atlas
+    /// # Arguments
+    /// * `atlas_path` - The path to the atlas
+    /// # Returns
+    /// * `Option<PackManifest>` - The manifest, or `None` if the atlas has no manifest
+    pub fn load(atlas_path: &Path) -> Result<Option<Self>> {
+        let manifest_path = Self::path_for(atlas_path);
+        if !manifest_path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&manifest_path).map_err(|e| PackerError::io(&manifest_path, e))?;
+        serde_json::from_str(&text).map(Some).map_err(|e| PackerError::json(&manifest_path, e))
+    }
+
+    /// Write the manifest next to an atlas
+    /// # Arguments
+    /// * `atlas_path` - The path to the atlas
+    pub fn save(&self, atlas_path: &Path) -> Result<()> {
+        let manifest_path = Self::path_for(atlas_path);
+        let text = serde_json::to_string_pretty(self).map_err(|e| PackerError::json(&manifest_path, e))?;
+        fs::write(&manifest_path, text).map_err(|e| PackerError::io(&manifest_path, e))
+    }
+}
+
+/// The result of an incremental pack
+pub struct IncrementalPack {
+    /// The generated atlas
+    pub atlas: DynamicImage,
+    /// The manifest to save alongside the atlas
+    pub manifest: PackManifest,
+    /// The sprites that were drawn onto the atlas
+    pub repacked: Vec<Sprite>,
+}
+
+impl IncrementalPack {
+    /// Write the atlas and its manifest
+    /// # Arguments
+    /// * `atlas_path` - The path to write the atlas to
+    pub fn save(&self, atlas_path: &Path) -> Result<()> {
+        self.atlas.save(atlas_path).map_err(|e| PackerError::image(atlas_path, e))?;
+        self.manifest.save(atlas_path)
+    }
+}
+
+/// Stamp a frame file, reusing the previous stamp without hashing if the file's size and modification time are unchanged
+/// # Arguments
+/// * `path` - The path to the frame file
+/// * `previous` - The stamp from when the frame was last packed
+/// # Returns
+/// * `FrameStamp` - The current stamp of the frame file
+pub fn frame_stamp(path: &Path, previous: Option<&FrameStamp>) -> Result<FrameStamp> {
+    let metadata = fs::metadata(path).map_err(|e| PackerError::io(path, e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as u64);
+    let len = metadata.len();
+    if let Some(previous) = previous {
+        if modified != 0 && previous.modified == modified && previous.len == len {
+            return Ok(previous.clone());
+        }
+    }
+
+    let bytes = fs::read(path).map_err(|e| PackerError::io(path, e))?;
+    Ok(FrameStamp {
+        modified,
+        len,
+        hash: fnv1a(&bytes),
+    })
+}
+
+/// Hash bytes with 64-bit FNV-1a, which unlike the standard library's hasher gives the same hash in every build
+/// # Arguments
+/// * `bytes` - The bytes to hash
+/// # Returns
+/// * `u64` - The hash
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+/// Check whether a file was modified after another
+/// # Arguments
+/// * `path` - The file that may be newer
+/// * `other_path` - The file to compare with
+/// # Returns
+/// * `bool` - Whether `path` was modified after `other_path`
+pub(crate) fn modified_after(path: &Path, other_path: &Path) -> Result<bool> {
+    let modified = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| PackerError::io(path, e))
+    };
+    Ok(modified(path)? > modified(other_path)?)
+}
+
+/// Packs only the sprites of a collection whose frame files changed since a previous atlas was generated
+///
+/// Falls back to packing every sprite onto the collection's original atlas if there is no previous atlas,
+/// its manifest is missing or was hashed another way, it has a different size than the original, or the original
+/// was changed since, e.g. by a repack that moved the sprites.
+/// # Arguments
+/// * `collection` - The collection to pack
+/// * `sprites_path` - The path to sprite files
+/// * `previous_atlas_path` - The path to the atlas generated by the last pack, if any
+/// * `format` - The pixel format to pack the atlas in
+/// * `on_progress` - Called with the pack progress as a percentage after each sprite is packed
+/// * `is_cancelled` - Polled while packing; the pack stops once it returns true
+/// # Returns
+/// * `Option<IncrementalPack>` - The generated atlas and its manifest, or `None` if the pack was cancelled
+pub fn pack_collection_incremental<P, C>(
+    collection: &Collection,
+    sprites_path: &Path,
+    previous_atlas_path: Option<&Path>,
+    format: AtlasFormat,
+    on_progress: P,
+    is_cancelled: C,
+) -> Result<Option<IncrementalPack>>
+where
+    P: Fn(usize) + Sync,
+    C: Fn() -> bool + Sync,
+{
+    let original_atlas = open_atlas(&collection.path, format)?;
+    let mut previous = None;
+    if let Some(previous_atlas_path) = previous_atlas_path.filter(|path| path.exists()) {
+        if let Some(manifest) = PackManifest::load(previous_atlas_path)?.filter(|manifest| manifest.hash == HASH_NAME) {
+            let atlas = image::open(previous_atlas_path).map_err(|e| PackerError::image(previous_atlas_path, e))?;
+            if atlas.dimensions() == original_atlas.dimensions()
+                && !modified_after(&collection.path, &PackManifest::path_for(previous_atlas_path))?
+            {
+                previous = Some((convert_color(atlas, original_atlas.color()), manifest));
+            }
+        }
+    }
+
+    let previous_frames = previous.as_ref().map(|(_, manifest)| &manifest.frames);
+    let stamps = collection
+        .sprites
+        .par_iter()
+        .map(|sprite| {
+            let path = sprite_path(sprite, sprites_path)?;
+            let previous_stamp = previous_frames.and_then(|frames| frames.get(&sprite.path));
+            frame_stamp(&path, previous_stamp)
+        })
+        .collect::<Result<Vec<FrameStamp>>>()?;
+
+    let (atlas, changed) = match previous {
+        Some((atlas, manifest)) => {
+            let changed = collection
+                .sprites
+                .iter()
+                .zip(&stamps)
+                .filter(|(sprite, stamp)| manifest.frames.get(&sprite.path) != Some(stamp))
+                .map(|(sprite, _)| sprite.clone())
+                .collect::<Vec<Sprite>>();
+            (atlas, changed)
+        }
+        None => (original_atlas, collection.sprites.clone()),
+    };
+
+    info!("Repacking {} of {} sprites in collection {:?}", changed.len(), collection.sprites.len(), collection.name);
+
+    let atlas = if changed.is_empty() {
+        on_progress(100);
+        atlas
+    } else {
+        match pack_sprites(atlas, &changed, sprites_path, on_progress, is_cancelled)? {
+            Some(atlas) => atlas,
+            None => return Ok(None),
+        }
+    };
+
+    let manifest = PackManifest {
+        hash: HASH_NAME.to_string(),
+        frames: collection
+            .sprites
+            .iter()
+            .zip(stamps)
+            .map(|(sprite, stamp)| (sprite.path.clone(), stamp))
+            .collect(),
+    };
+
+    Ok(Some(IncrementalPack {
+        atlas,
+        manifest,
+        repacked: changed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+    use image::{Rgba, RgbaImage};
+
+    /// A sprite with a 2x2 rect filling its frame, placed at (x, 0) in the atlas
+    fn sprite(name: &str, x: i32) -> Sprite {
+        Sprite { x, xr: 0, yr: 0, width: 2, ..test_support::sprite(name) }
+    }
+
+    /// A sprites folder with two frames and a blank 4x2 original atlas
+    fn sprites_folder(name: &str) -> (TempDir, Collection) {
+        let sprites_path = TempDir::new(&format!("incremental-{}", name));
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+        let collection = test_support::collection(&sprites_path, vec![sprite("Idle-0-1.png", 0), sprite("Idle-1-1.png", 2)]);
+        RgbaImage::new(4, 2).save(&collection.path).unwrap();
+        for (sprite, red) in collection.sprites.iter().zip([10, 20]) {
+            RgbaImage::from_pixel(2, 2, Rgba([red, 0, 0, 255])).save(sprites_path.join(&sprite.path)).unwrap();
+        }
+        (sprites_path, collection)
+    }
+
+    fn pack(collection: &Collection, sprites_path: &Path, previous_atlas_path: &Path) -> IncrementalPack {
+        pack_collection_incremental(collection, sprites_path, Some(previous_atlas_path), AtlasFormat::Original, |_| {}, || false)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn repacked_names(pack: &IncrementalPack) -> Vec<&str> {
+        pack.repacked.iter().map(|sprite| sprite.name.as_str()).collect()
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_hashes() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn unchanged_frames_are_not_repacked() {
+        let (sprites_path, collection) = sprites_folder("unchanged");
+        let atlas_path = sprites_path.join("Knight Cln.png");
+        let first = pack(&collection, &sprites_path, &atlas_path);
+        first.save(&atlas_path).unwrap();
+        let second = pack(&collection, &sprites_path, &atlas_path);
+        let manifest = PackManifest::load(&atlas_path).unwrap().unwrap();
+
+        assert_eq!(repacked_names(&first), vec!["Idle-0-1.png", "Idle-1-1.png"]);
+        assert_eq!(manifest.hash, HASH_NAME);
+        assert!(second.repacked.is_empty());
+        assert_eq!(second.atlas.to_rgba8(), first.atlas.to_rgba8());
+    }
+
+    #[test]
+    fn changed_frames_are_repacked_onto_the_previous_atlas() {
+        let (sprites_path, collection) = sprites_folder("changed");
+        let atlas_path = sprites_path.join("Knight Cln.png");
+        pack(&collection, &sprites_path, &atlas_path).save(&atlas_path).unwrap();
+        // A frame of a different size in bytes, so that it counts as changed even if its modification time doesn't
+        let frame = RgbaImage::from_fn(2, 2, |x, y| Rgba([x as u8 * 90, y as u8 * 70, 30, 255]));
+        frame.save(sprites_path.join(&collection.sprites[1].path)).unwrap();
+        let second = pack(&collection, &sprites_path, &atlas_path);
+
+        assert_eq!(repacked_names(&second), vec!["Idle-1-1.png"]);
+        let atlas = second.atlas.to_rgba8();
+        assert_eq!(*atlas.get_pixel(0, 0), Rgba([10, 0, 0, 255]));
+        assert_eq!(*atlas.get_pixel(3, 0), Rgba([90, 0, 30, 255]));
+    }
+
+    #[test]
+    fn missing_manifests_and_atlases_fall_back_to_a_full_pack() {
+        let (sprites_path, collection) = sprites_folder("missing");
+        let atlas_path = sprites_path.join("Knight Cln.png");
+        let missing_atlas = pack(&collection, &sprites_path, &atlas_path);
+        missing_atlas.save(&atlas_path).unwrap();
+
+        fs::remove_file(PackManifest::path_for(&atlas_path)).unwrap();
+        let missing_manifest = pack(&collection, &sprites_path, &atlas_path);
+        missing_manifest.save(&atlas_path).unwrap();
+
+        // Manifests written before the hash was recorded hashed frames in a way that may differ between builds
+        let mut manifest = PackManifest::load(&atlas_path).unwrap().unwrap();
+        manifest.hash = String::new();
+        manifest.save(&atlas_path).unwrap();
+        let unknown_hash = pack(&collection, &sprites_path, &atlas_path);
+
+        for pack in [missing_atlas, missing_manifest, unknown_hash] {
+            assert_eq!(repacked_names(&pack), vec!["Idle-0-1.png", "Idle-1-1.png"]);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::Result;
+use crate::packer::diff::DiffRect;
+use crate::packer::open_sprite;
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::Sprite;
+use image::GenericImageView;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The most pixel coordinates listed in a bleed report
+pub const MAX_LISTED_PIXELS: usize = 100;
+
+/// Non-transparent pixels of a frame that lie outside its sprite's trimmed rect and so are never packed
+#[derive(Clone, Debug, Serialize)]
+pub struct BleedReport {
+    pub sprite: Sprite,
+    /// The number of non-transparent pixels outside the trimmed rect
+    pub pixels: u32,
+    /// The smallest rect in the frame containing every such pixel
+    #[serde(rename = "boundingBox")]
+    pub bounding_box: DiffRect,
+    /// The first of the pixels in row order, as `(x, y)` from the frame's top left corner like in an image editor
+    pub coordinates: Vec<(u32, u32)>,
+}
+
+/// Find painted pixels outside the trimmed rect of a sprite's frame
+/// # Arguments
+/// * `sprite` - The sprite to lint
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `Option<BleedReport>` - The pixels that will not be packed, or `None` if there are none
+pub fn lint_sprite(sprite: &Sprite, sprites_path: &Path) -> Result<Option<BleedReport>> {
+    let (_, frame) = open_sprite(sprite, sprites_path)?;
+    let height = frame.height();
+    // The trimmed rect from the top left corner, with yr measured from the bottom
+    let x_min = sprite.xr as i64;
+    let x_max = x_min + sprite.width as i64;
+    let y_max = height as i64 - sprite.yr as i64;
+    let y_min = y_max - sprite.height as i64;
+
+    let mut pixels = 0;
+    let mut coordinates = Vec::new();
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in frame.pixels() {
+        let inside = (x_min..x_max).contains(&(x as i64)) && (y_min..y_max).contains(&(y as i64));
+        if inside || pixel[3] == 0 {
+            continue;
+        }
+
+        pixels += 1;
+        if coordinates.len() < MAX_LISTED_PIXELS {
+            coordinates.push((x, y));
+        }
+        left = left.min(x);
+        top = top.min(y);
+        right = right.max(x);
+        bottom = bottom.max(y);
+    }
+
+    if pixels == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(BleedReport {
+        sprite: sprite.clone(),
+        pixels,
+        bounding_box: DiffRect {
+            x: left,
+            y: top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        },
+        coordinates,
+    }))
+}
+
+/// Find painted pixels outside the trimmed rects of every frame in some collections
+/// # Arguments
+/// * `collections` - The collections to lint
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `Vec<BleedReport>` - A report for each frame with pixels that will not be packed, ordered by collection
+pub fn lint_collections(collections: &[Collection], sprites_path: &Path) -> Result<Vec<BleedReport>> {
+    let mut sprites: Vec<&Sprite> = Vec::new();
+    let mut seen: HashSet<(&str, &str)> = HashSet::new();
+    for sprite in collections.iter().flat_map(|collection| collection.sprites.iter()) {
+        if seen.insert((&sprite.collection_name, &sprite.path)) {
+            sprites.push(sprite);
+        }
+    }
+
+    let reports = sprites
+        .par_iter()
+        .map(|sprite| lint_sprite(sprite, sprites_path))
+        .collect::<Result<Vec<Option<BleedReport>>>>()?;
+
+    Ok(reports.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+    use image::{Rgba, RgbaImage};
+    use std::fs;
+
+    /// A sprite with a 3x2 rect at (1, 1) from the bottom left of its frame
+    fn sprite(name: &str, flipped: bool) -> Sprite {
+        Sprite { flipped, ..test_support::sprite(name) }
+    }
+
+    /// A transparent 6x5 frame with the trimmed rect of `sprite` painted, i.e. rows 2 and 3 of columns 1 to 3
+    fn frame() -> RgbaImage {
+        RgbaImage::from_fn(6, 5, |x, y| {
+            if (1..4).contains(&x) && (2..4).contains(&y) {
+                Rgba([200, 0, 0, 255])
+            } else {
+                Rgba([200, 0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn finds_painted_pixels_outside_the_trimmed_rect() {
+        let sprites_path = TempDir::new("lint");
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+
+        // Only colors under zero alpha outside the rect
+        frame().save(sprites_path.join("Knight/Idle/Idle-0-1.png")).unwrap();
+        // Faint pixels just below and right of the rect, which flipping does not change in frame space
+        let mut bleeding = frame();
+        bleeding.put_pixel(2, 4, Rgba([0, 0, 0, 1]));
+        bleeding.put_pixel(4, 3, Rgba([0, 0, 0, 1]));
+        bleeding.save(sprites_path.join("Knight/Idle/Idle-1-1.png")).unwrap();
+        // Every pixel painted
+        RgbaImage::from_pixel(20, 20, Rgba([0, 0, 0, 255])).save(sprites_path.join("Knight/Idle/Idle-2-1.png")).unwrap();
+
+        let clean = lint_sprite(&sprite("Idle-0-1.png", false), &sprites_path).unwrap();
+        let reports = [false, true].map(|flipped| lint_sprite(&sprite("Idle-1-1.png", flipped), &sprites_path).unwrap().unwrap());
+        let full = lint_sprite(&sprite("Idle-2-1.png", false), &sprites_path).unwrap().unwrap();
+
+        assert!(clean.is_none());
+        for report in &reports {
+            assert_eq!(report.pixels, 2);
+            assert_eq!(report.bounding_box, DiffRect { x: 2, y: 3, width: 3, height: 2 });
+            assert_eq!(report.coordinates, vec![(4, 3), (2, 4)]);
+        }
+
+        // The rect is now at rows 17 and 18, and only the first pixels are listed
+        assert_eq!(full.pixels, 20 * 20 - 3 * 2);
+        assert_eq!(full.bounding_box, DiffRect { x: 0, y: 0, width: 20, height: 20 });
+        assert_eq!(full.coordinates.len(), MAX_LISTED_PIXELS);
+        assert_eq!(full.coordinates[..3], [(0, 0), (1, 0), (2, 0)]);
+        assert!(!full.coordinates.contains(&(1, 17)));
+    }
+
+    #[test]
+    fn lints_frames_shared_within_a_collection_once() {
+        let sprites_path = TempDir::new("lint-collections");
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+        let mut bleeding = frame();
+        bleeding.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        bleeding.save(sprites_path.join("Knight/Idle/Idle-0-1.png")).unwrap();
+        frame().save(sprites_path.join("Knight/Idle/Idle-1-1.png")).unwrap();
+
+        let collection = |name: &str| Collection {
+            name: name.to_string(),
+            path: sprites_path.join(format!("Knight/0.Atlases/{}.png", name)),
+            sprites: ["Idle-0-1.png", "Idle-0-1.png", "Idle-1-1.png"]
+                .iter()
+                .map(|frame_name| Sprite { collection_name: name.to_string(), ..sprite(frame_name, false) })
+                .collect(),
+        };
+        let reports = lint_collections(&[collection("Knight Cln"), collection("Knight 2 Cln")], &sprites_path).unwrap();
+
+        let collection_names = reports.iter().map(|report| report.sprite.collection_name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(collection_names, vec!["Knight Cln", "Knight 2 Cln"]);
+        assert!(reports.iter().all(|report| report.sprite.name == "Idle-0-1.png" && report.coordinates == vec![(0, 0)]));
+    }
+}
@@ -0,0 +1,329 @@
+use crate::error::{PackerError, Result};
+use crate::tk2d::anim::Animation;
+use crate::tk2d::clip::Clip;
+use crate::tk2d::cln::Collection;
+use crate::tk2d::info::{AnimInfo, SpriteInfo};
+use crate::tk2d::sprite::Sprite;
+use log::warn;
+use rayon::prelude::*;
+use serde::de::Error;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A summary of the problems found while loading a sprites folder
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LoadReport {
+    pub animations: Vec<AnimationReport>,
+}
+
+impl LoadReport {
+    /// Whether every animation folder loaded without any problems
+    pub fn is_clean(&self) -> bool {
+        self.animations.iter().all(AnimationReport::is_clean)
+    }
+
+    /// Get the reports of animation folders that were skipped
+    pub fn skipped(&self) -> impl Iterator<Item = &AnimationReport> {
+        self.animations.iter().filter(|report| report.skipped_reason.is_some())
+    }
+}
+
+/// The problems found while loading a single animation folder
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AnimationReport {
+    pub name: String,
+    /// Why the animation was not loaded, if it was skipped
+    #[serde(rename = "skippedReason")]
+    pub skipped_reason: Option<String>,
+    /// Frame files that are not listed in the animation's sprite info
+    #[serde(rename = "orphanFrames")]
+    pub orphan_frames: Vec<String>,
+    /// Sprite info entries whose frame file does not exist
+    #[serde(rename = "missingFrames")]
+    pub missing_frames: Vec<String>,
+}
+
+impl AnimationReport {
+    /// Whether the animation folder loaded without any problems
+    pub fn is_clean(&self) -> bool {
+        self.skipped_reason.is_none() && self.orphan_frames.is_empty() && self.missing_frames.is_empty()
+    }
+}
+
+/// Load collections and animations from sprite files on disk, failing on the first broken animation folder
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `(Vec<Collection>, Vec<Animation>)` - The loaded collections and animations
+pub fn load_collections_and_animations(sprites_path: &Path) -> Result<(Vec<Collection>, Vec<Animation>)> {
+    load(sprites_path, true).map(|(collections, animations, _)| (collections, animations))
+}
+
+/// Load collections and animations from sprite files on disk, skipping broken animation folders
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `(Vec<Collection>, Vec<Animation>, LoadReport)` - The loaded collections and animations, and the problems found
+pub fn load_collections_and_animations_with_report(sprites_path: &Path) -> Result<(Vec<Collection>, Vec<Animation>, LoadReport)> {
+    load(sprites_path, false)
+}
+
+/// Load every animation folder in a sprites folder
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `strict` - Whether to fail on the first problem instead of recording it in the report
+fn load(sprites_path: &Path, strict: bool) -> Result<(Vec<Collection>, Vec<Animation>, LoadReport)> {
+    let mut loaded_collections: Vec<Collection> = Vec::new();
+    let mut loaded_animations: Vec<Animation> = Vec::new();
+    let mut report = LoadReport::default();
+    let anim_paths = fs::read_dir(sprites_path).map_err(|e| PackerError::io(sprites_path, e))?;
+    for anim_path in anim_paths {
+        let anim_path = anim_path.map_err(|e| PackerError::io(sprites_path, e))?.path();
+        if !anim_path.is_dir() {
+            continue;
+        }
+
+        let mut anim_report = AnimationReport {
+            name: file_name(&anim_path),
+            ..Default::default()
+        };
+        match load_animation_folder(&anim_path, sprites_path, strict, &mut anim_report) {
+            Ok((sprites, animation)) => {
+                add_sprites_to_collections(&anim_path, sprites, &mut loaded_collections);
+                loaded_animations.push(animation);
+            }
+            Err(e) if !strict => {
+                warn!("Skipped animation folder {:?}: {}", anim_path.display(), e);
+                anim_report.skipped_reason = Some(e.to_string());
+            }
+            Err(e) => return Err(e),
+        }
+        report.animations.push(anim_report);
+    }
+
+    loaded_collections.par_sort();
+    loaded_animations.par_sort();
+    report.animations.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok((loaded_collections, loaded_animations, report))
+}
+
+/// Load a single animation folder
+/// # Arguments
+/// * `anim_path` - The path to the animation folder
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `strict` - Whether frame files missing from the sprite info are an error
+/// * `report` - The report to record missing and orphan frames in
+/// # Returns
+/// * `(Vec<Sprite>, Animation)` - The sprites whose frame files exist, and the loaded animation
+fn load_animation_folder(
+    anim_path: &Path,
+    sprites_path: &Path,
+    strict: bool,
+    report: &mut AnimationReport,
+) -> Result<(Vec<Sprite>, Animation)> {
+    let sprite_info = load_sprite_info(anim_path)?;
+    let mut sprites = Vec::new();
+    for i in 0..sprite_info.id.len() {
+        let sprite = sprite_info_entry(&sprite_info, i, anim_path)?;
+        if sprites_path.join(&sprite.path).exists() || Path::new(&sprite.path).exists() {
+            sprites.push(sprite);
+        } else {
+            report.missing_frames.push(sprite.path);
+        }
+    }
+
+    let (animation, orphan_frames) = load_animation(anim_path, &sprite_info, strict)?;
+    report.orphan_frames = orphan_frames
+        .iter()
+        .map(|path| path.strip_prefix(sprites_path).unwrap_or(path).display().to_string())
+        .collect();
+    report.orphan_frames.sort();
+
+    Ok((sprites, animation))
+}
+
+/// Read the sprite info of an animation folder
+/// # Arguments
+/// * `anim_path` - The path to the animation folder
+/// # Returns
+/// * `SpriteInfo` - The parsed contents of `0.Atlases/SpriteInfo.json`
+pub fn load_sprite_info(anim_path: &Path) -> Result<SpriteInfo> {
+    let sprite_info_path = anim_path.join("0.Atlases").join("SpriteInfo.json");
+    let text = fs::read_to_string(&sprite_info_path).map_err(|e| PackerError::io(&sprite_info_path, e))?;
+    serde_json::from_str(&text).map_err(|e| PackerError::json(&sprite_info_path, e))
+}
+
+/// Get an entry of an animation folder's sprite info as a sprite
+/// # Arguments
+/// * `sprite_info` - The sprite info
+/// * `index` - The index of the entry
+/// * `anim_path` - The path to the animation folder the sprite info was read from
+/// # Returns
+/// * `Sprite` - The sprite, or a JSON error if the sprite info is missing any of the entry's fields
+pub fn sprite_info_entry(sprite_info: &SpriteInfo, index: usize, anim_path: &Path) -> Result<Sprite> {
+    sprite_info.at(index).ok_or_else(|| {
+        let sprite_info_path = anim_path.join("0.Atlases").join("SpriteInfo.json");
+        PackerError::json(sprite_info_path, serde_json::Error::custom(format!("entry {} is incomplete", index)))
+    })
+}
+
+/// Add sprites to the collections they belong to
+/// # Arguments
+/// * `anim_path` - The path to the animation folder the sprites were loaded from
+/// * `sprites` - The sprites to add
+/// * `collections` - The collections loaded so far
+fn add_sprites_to_collections(anim_path: &Path, sprites: Vec<Sprite>, collections: &mut Vec<Collection>) {
+    for sprite in sprites {
+        match collections.iter_mut().find(|cln| cln.name == sprite.collection_name) {
+            Some(collection) => collection.sprites.push(sprite),
+            None => {
+                let collection_name = sprite.collection_name.clone();
+                collections.push(Collection {
+                    path: anim_path.join("0.Atlases").join(format!("{}.png", collection_name)),
+                    name: collection_name,
+                    sprites: vec![sprite],
+                });
+            }
+        }
+    }
+}
+
+/// Load the clips of an animation folder
+/// # Arguments
+/// * `anim_path` - The path to the animation folder
+/// * `sprite_info` - The animation's sprite info
+/// * `strict` - Whether frame files missing from the sprite info are an error
+/// # Returns
+/// * `(Animation, Vec<PathBuf>)` - The loaded animation, and the frame files missing from the sprite info
+fn load_animation(anim_path: &Path, sprite_info: &SpriteInfo, strict: bool) -> Result<(Animation, Vec<PathBuf>)> {
+    let clip_paths = fs::read_dir(anim_path).map_err(|e| PackerError::io(anim_path, e))?;
+    let clips = clip_paths
+        .par_bridge()
+        .map(|clip_path| {
+            let clip_path = clip_path.map_err(|e| PackerError::io(anim_path, e))?.path();
+            if !clip_path.is_dir() || clip_path.ends_with("0.Atlases") {
+                return Ok(None);
+            }
+            load_clip_frames(&clip_path, sprite_info, strict).map(Some)
+        })
+        .collect::<Result<Vec<Option<(Clip, Vec<PathBuf>)>>>>()?;
+
+    let (mut clips, orphan_frames): (Vec<Clip>, Vec<Vec<PathBuf>>) = clips.into_iter().flatten().unzip();
+    clips.par_sort();
+
+    Ok((
+        Animation {
+            name: file_name(anim_path),
+            clips,
+        },
+        orphan_frames.into_iter().flatten().collect(),
+    ))
+}
+
+/// Load the frames and animation info of a clip folder
+/// # Arguments
+/// * `clip_path` - The path to the clip folder
+/// * `sprite_info` - The sprite info of the animation containing the clip
+/// # Returns
+/// * `Clip` - The loaded clip
+pub fn load_clip(clip_path: &Path, sprite_info: &SpriteInfo) -> Result<Clip> {
+    load_clip_frames(clip_path, sprite_info, true).map(|(clip, _)| clip)
+}
+
+/// Load the frames and animation info of a clip folder
+/// # Arguments
+/// * `clip_path` - The path to the clip folder
+/// * `sprite_info` - The sprite info of the animation containing the clip
+/// * `strict` - Whether frame files missing from the sprite info are an error
+/// # Returns
+/// * `(Clip, Vec<PathBuf>)` - The loaded clip, and the frame files missing from the sprite info
+fn load_clip_frames(clip_path: &Path, sprite_info: &SpriteInfo, strict: bool) -> Result<(Clip, Vec<PathBuf>)> {
+    let mut fps = 12.0;
+    let mut loop_start = 0;
+    let anim_info_path = clip_path.join("AnimInfo.json");
+    if anim_info_path.exists() {
+        let text = fs::read_to_string(&anim_info_path).map_err(|e| PackerError::io(&anim_info_path, e))?;
+        if let Ok(anim_info) = serde_json::from_str::<AnimInfo>(&text) {
+            fps = anim_info.fps;
+            loop_start = anim_info.loop_start;
+        }
+    }
+
+    let frame_paths = fs::read_dir(clip_path).map_err(|e| PackerError::io(clip_path, e))?;
+    let entries = frame_paths
+        .par_bridge()
+        .map(|frame_path| {
+            let frame_path = frame_path.map_err(|e| PackerError::io(clip_path, e))?.path();
+            if frame_path.extension().map_or(true, |extension| extension != "png") {
+                return Ok(None);
+            }
+
+            let index = match sprite_info.path.par_iter().position_first(|path| frame_path.ends_with(path)) {
+                Some(index) => index,
+                None if strict => return Err(PackerError::MissingSprite(frame_path.display().to_string())),
+                None => return Ok(Some(Err(frame_path))),
+            };
+            let anim_path = clip_path.parent().unwrap_or(clip_path);
+            sprite_info_entry(sprite_info, index, anim_path).map(|sprite| Some(Ok(sprite)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut frames = Vec::new();
+    let mut orphan_frames = Vec::new();
+    for frame in entries.into_iter().flatten() {
+        match frame {
+            Ok(sprite) => frames.push(sprite),
+            Err(frame_path) => orphan_frames.push(frame_path),
+        }
+    }
+    // The frames are read in whatever order the file system lists them, so put them in the order they play
+    frames.sort_by_key(|sprite| (frame_number(&sprite.name).unwrap_or(u32::MAX), sprite.name.clone()));
+
+    Ok((Clip::new(file_name(clip_path), frames, fps, loop_start), orphan_frames))
+}
+
+/// Get a frame's number in its clip from its file name, e.g. `Idle-3-12.png` is frame 3
+/// # Arguments
+/// * `sprite_name` - The file name of the frame, `<clip>-<frame>-<id>.png`
+/// # Returns
+/// * `Option<u32>` - The frame number, or `None` if the file name doesn't have one
+fn frame_number(sprite_name: &str) -> Option<u32> {
+    let mut sprite_data = sprite_name.trim_end_matches(".png").rsplit('-');
+    sprite_data.next()?;
+    sprite_data.next()?.parse().ok()
+}
+
+/// Get the last component of a path as a string
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn incomplete_sprite_info_entries_are_json_errors() {
+        let sprites_path = TempDir::new("load");
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+        // The second entry has no width
+        let sprite_info = r#"{"sid":[1,2],"sx":[0,0],"sy":[0,0],"sxr":[0,0],"syr":[0,0],"swidth":[2],"sheight":[2,2],
+            "scollectionname":["Knight Cln","Knight Cln"],"spath":["Knight/Idle/Idle-0-1.png","Knight/Idle/Idle-1-2.png"],
+            "sfilpped":[false,false]}"#;
+        fs::write(sprites_path.join("Knight/0.Atlases/SpriteInfo.json"), sprite_info).unwrap();
+
+        let strict = load_collections_and_animations(&sprites_path);
+        let (collections, _, report) = load_collections_and_animations_with_report(&sprites_path).unwrap();
+
+        let error = strict.err().unwrap();
+        assert_eq!(error.kind(), "Json");
+        assert!(error.to_string().ends_with("entry 1 is incomplete"), "{}", error);
+        assert!(collections.is_empty());
+        assert_eq!(report.skipped().map(|animation| animation.name.as_str()).collect::<Vec<&str>>(), vec!["Knight"]);
+    }
+}
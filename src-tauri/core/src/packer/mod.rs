@@ -0,0 +1,108 @@
+pub mod backup;
+pub mod batch;
+pub mod check;
+pub mod definitions;
+pub mod diff;
+pub mod edges;
+pub mod format;
+pub mod incremental;
+pub mod lint;
+pub mod load;
+pub mod output;
+pub mod pack;
+pub mod preview;
+pub mod repack;
+pub mod replace;
+pub mod resolve;
+pub mod sheet;
+pub mod texture;
+pub mod twins;
+pub mod unpack;
+pub mod verify;
+pub mod webp;
+
+use crate::error::{PackerError, Result};
+use crate::tk2d::anim::Animation;
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::Sprite;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{DynamicImage, ImageOutputFormat};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Get a collection by its name
+/// # Arguments
+/// * `collection_name` - The name of the collection
+/// * `collections` - A list of collections to search through
+/// # Returns
+/// * `&Collection` - The collection with the given name
+pub fn get_collection<'a>(collection_name: &str, collections: &'a [Collection]) -> Result<&'a Collection> {
+    collections
+        .iter()
+        .find(|cln| cln.name == collection_name)
+        .ok_or_else(|| PackerError::MissingCollection(collection_name.to_string()))
+}
+
+/// Get the collections used by an animation's frames
+/// # Arguments
+/// * `animation` - The animation
+/// * `collections` - A list of collections to search through
+/// # Returns
+/// * `Vec<Collection>` - The collections used by the animation, sorted by name
+pub fn get_animation_collections(animation: &Animation, collections: &[Collection]) -> Result<Vec<Collection>> {
+    let mut collection_names = animation
+        .clips
+        .iter()
+        .flat_map(|clip| clip.frames.iter().map(|frame| frame.collection_name.as_str()))
+        .collect::<Vec<&str>>();
+    collection_names.sort_unstable();
+    collection_names.dedup();
+    collection_names
+        .into_iter()
+        .map(|collection_name| get_collection(collection_name, collections).cloned())
+        .collect()
+}
+
+/// Get the path to a sprite's frame file on disk
+/// # Arguments
+/// * `sprite` - The sprite to find the file of
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `PathBuf` - The frame's path relative to `sprites_path`, or its own path if it is absolute
+pub fn sprite_path(sprite: &Sprite, sprites_path: &Path) -> Result<PathBuf> {
+    let relative_path = sprites_path.join(&sprite.path);
+    if relative_path.exists() {
+        Ok(relative_path)
+    } else if Path::new(&sprite.path).exists() {
+        Ok(PathBuf::from(&sprite.path))
+    } else {
+        Err(PackerError::MissingSprite(sprite.path.clone()))
+    }
+}
+
+/// Open a sprite's frame file
+/// # Arguments
+/// * `sprite` - The sprite to open the frame of
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `(PathBuf, DynamicImage)` - The path to the frame and its decoded image
+pub fn open_sprite(sprite: &Sprite, sprites_path: &Path) -> Result<(PathBuf, DynamicImage)> {
+    let path = sprite_path(sprite, sprites_path)?;
+    let image = image::open(&path).map_err(|e| PackerError::image(&path, e))?;
+    Ok((path, image))
+}
+
+/// Encode an image as a base64 PNG data URL for display in the frontend
+/// # Arguments
+/// * `image` - The image to encode
+/// * `path` - The path the image was loaded from, used in errors
+/// # Returns
+/// * `String` - The `data:image/png;base64,...` URL
+pub fn png_data_url(image: &DynamicImage, path: &Path) -> Result<String> {
+    let mut bytes = Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, ImageOutputFormat::Png)
+        .map_err(|e| PackerError::image(path, e))?;
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(bytes.into_inner())))
+}
@@ -0,0 +1,297 @@
+use crate::error::{PackerError, Result};
+use crate::packer::format::AtlasFormat;
+use crate::packer::incremental::PackManifest;
+use crate::packer::texture::TextureOptions;
+use crate::tk2d::cln::Collection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The atlas name template used when none is configured
+pub const DEFAULT_TEMPLATE: &str = "{collection}.png";
+/// The placeholder replaced by the time an atlas is written
+const TIMESTAMP_PLACEHOLDER: &str = "{timestamp}";
+/// The length of a timestamp, `YYYYMMDD-HHMMSS`
+const TIMESTAMP_LEN: usize = 15;
+
+/// Where and how generated atlases are written
+#[derive(Clone, Debug)]
+pub struct AtlasOutput {
+    /// The folder atlases are written to
+    pub directory: PathBuf,
+    /// The path of each atlas relative to `directory`, which may contain the
+    /// `{animation}`, `{collection}` and `{timestamp}` placeholders
+    pub template: String,
+    /// Whether to back up an existing atlas before overwriting it
+    pub backup_existing: bool,
+    /// Whether to overwrite the collection's original atlas in `0.Atlases` instead, after backing it up
+    pub overwrite_original: bool,
+    /// The pixel format to write atlases in
+    pub format: AtlasFormat,
+    /// The compressed textures to write next to each atlas
+    pub textures: TextureOptions,
+}
+
+impl AtlasOutput {
+    /// Write atlases to `<directory>/<collection>.png` in the format of the original atlases without backups or textures
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            template: DEFAULT_TEMPLATE.to_string(),
+            backup_existing: false,
+            overwrite_original: false,
+            format: AtlasFormat::default(),
+            textures: TextureOptions::default(),
+        }
+    }
+
+    /// Get the path a collection's atlas is written to
+    /// # Arguments
+    /// * `collection` - The collection whose atlas is written
+    /// # Returns
+    /// * `PathBuf` - The path to write the atlas to
+    pub fn atlas_path(&self, collection: &Collection) -> PathBuf {
+        if self.overwrite_original {
+            return collection.path.clone();
+        }
+
+        self.directory.join(self.expand_template(collection).replace(TIMESTAMP_PLACEHOLDER, &timestamp()))
+    }
+
+    /// Get the path of the atlas a collection was last written to, for an incremental pack to start from
+    ///
+    /// Without a `{timestamp}` placeholder this is the path the atlas is written to. With one, every pack writes
+    /// to a new path, so the newest atlas whose path only differs by its timestamps and that has a pack manifest
+    /// is used instead.
+    /// # Arguments
+    /// * `collection` - The collection whose atlas is written
+    /// # Returns
+    /// * `Option<PathBuf>` - The path of the previous atlas, or `None` if no timestamped atlas has a manifest
+    pub fn previous_atlas_path(&self, collection: &Collection) -> Option<PathBuf> {
+        let relative_path = self.expand_template(collection);
+        if self.overwrite_original || !relative_path.contains(TIMESTAMP_PLACEHOLDER) {
+            return Some(self.atlas_path(collection));
+        }
+
+        let mut candidates = vec![self.directory.clone()];
+        for component in Path::new(&relative_path).components() {
+            let pattern = component.as_os_str().to_string_lossy();
+            candidates = if pattern.contains(TIMESTAMP_PLACEHOLDER) {
+                candidates
+                    .iter()
+                    .filter_map(|path| fs::read_dir(path).ok())
+                    .flatten()
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.file_name().map_or(false, |name| matches_timestamps(&name.to_string_lossy(), &pattern)))
+                    .collect()
+            } else {
+                candidates.into_iter().map(|path| path.join(&*pattern)).collect()
+            };
+        }
+        // Timestamps have a fixed width, so the newest atlas has the greatest path
+        candidates.into_iter().filter(|path| PackManifest::path_for(path).exists()).max()
+    }
+
+    /// Replace the `{animation}` and `{collection}` placeholders of the template, leaving `{timestamp}`
+    fn expand_template(&self, collection: &Collection) -> String {
+        let template = if self.template.trim().is_empty() {
+            DEFAULT_TEMPLATE
+        } else {
+            &self.template
+        };
+        template
+            .replace("{animation}", &animation_name(collection))
+            .replace("{collection}", &collection.name)
+    }
+
+    /// Get the path a collection's atlas is written to, creating its folder and backing up any existing atlas there
+    /// # Arguments
+    /// * `collection` - The collection whose atlas is written
+    /// # Returns
+    /// * `(PathBuf, Option<PathBuf>)` - The path to write the atlas to, and the path of the backup if one was made
+    pub fn prepare(&self, collection: &Collection) -> Result<(PathBuf, Option<PathBuf>)> {
+        let atlas_path = self.atlas_path(collection);
+        if let Some(parent) = atlas_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| PackerError::io(parent, e))?;
+        }
+
+        let backup_path = if atlas_path.exists() && (self.backup_existing || self.overwrite_original) {
+            Some(backup_file(&atlas_path)?)
+        } else {
+            None
+        };
+
+        Ok((atlas_path, backup_path))
+    }
+}
+
+/// Get the name of the animation folder a collection was loaded from
+/// # Arguments
+/// * `collection` - The collection, whose atlas is at `<animation>/0.Atlases/<collection>.png`
+/// # Returns
+/// * `String` - The name of the animation folder
+pub fn animation_name(collection: &Collection) -> String {
+    collection
+        .path
+        .parent()
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Copy a file next to itself with a timestamped backup suffix, e.g. `Knight Cln.20230102-150405.bak.png`
+/// # Arguments
+/// * `path` - The path to the file to back up
+/// # Returns
+/// * `PathBuf` - The path to the backup
+pub fn backup_file(path: &Path) -> Result<PathBuf> {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}.{}.bak.{}", stem, timestamp(), extension.to_string_lossy()),
+        None => format!("{}.{}.bak", stem, timestamp()),
+    };
+    let backup_path = path.with_file_name(file_name);
+    fs::copy(path, &backup_path).map_err(|e| PackerError::io(&backup_path, e))?;
+    Ok(backup_path)
+}
+
+/// Check whether a file name is a pattern with each `{timestamp}` placeholder replaced by a timestamp
+/// # Arguments
+/// * `name` - The file name
+/// * `pattern` - The file name with `{timestamp}` placeholders
+/// # Returns
+/// * `bool` - Whether the file name matches the pattern
+fn matches_timestamps(name: &str, pattern: &str) -> bool {
+    let is_timestamp = |text: &str| {
+        text.char_indices().all(|(index, c)| if index == 8 { c == '-' } else { c.is_ascii_digit() })
+    };
+
+    let mut rest = name;
+    for (index, part) in pattern.split(TIMESTAMP_PLACEHOLDER).enumerate() {
+        if index > 0 {
+            match rest.get(..TIMESTAMP_LEN) {
+                Some(timestamp) if is_timestamp(timestamp) => rest = &rest[TIMESTAMP_LEN..],
+                _ => return false,
+            }
+        }
+        match rest.strip_prefix(part) {
+            Some(after) => rest = after,
+            None => return false,
+        }
+    }
+
+    rest.is_empty()
+}
+
+/// Get the current UTC time formatted as `YYYYMMDD-HHMMSS`
+pub fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs()) as i64;
+    let (days, secs_of_day) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Convert days since the Unix epoch to a civil date
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+
+    fn output(directory: &Path, template: &str) -> AtlasOutput {
+        AtlasOutput { template: template.to_string(), ..AtlasOutput::new(directory) }
+    }
+
+    #[test]
+    fn templates_expand_their_placeholders() {
+        let collection = test_support::collection(Path::new("sprites"), Vec::new());
+        assert_eq!(output(Path::new("out"), "").atlas_path(&collection), Path::new("out/Knight Cln.png"));
+        assert_eq!(
+            output(Path::new("out"), "{animation}/{collection}.png").atlas_path(&collection),
+            Path::new("out/Knight/Knight Cln.png"),
+        );
+
+        let atlas_path = output(Path::new("out"), "{collection}-{timestamp}.png").atlas_path(&collection);
+        let file_name = atlas_path.file_name().unwrap().to_string_lossy();
+        assert!(matches_timestamps(&file_name, "Knight Cln-{timestamp}.png"), "{}", file_name);
+
+        let overwrite = AtlasOutput { overwrite_original: true, ..output(Path::new("out"), "{collection}-{timestamp}.png") };
+        assert_eq!(overwrite.atlas_path(&collection), collection.path);
+    }
+
+    #[test]
+    fn timestamps_match_only_their_own_format() {
+        assert!(matches_timestamps("Knight Cln.20230102-150405.png", "Knight Cln.{timestamp}.png"));
+        assert!(matches_timestamps("20230102-150405-20230102-150406", "{timestamp}-{timestamp}"));
+        assert!(!matches_timestamps("Knight Cln.2023012-150405.png", "Knight Cln.{timestamp}.png"));
+        assert!(!matches_timestamps("Knight Cln.20230102_150405.png", "Knight Cln.{timestamp}.png"));
+        assert!(!matches_timestamps("Knight Cln.20230102-150405.png.bak", "Knight Cln.{timestamp}.png"));
+        assert!(!matches_timestamps("Hornet Cln.20230102-150405.png", "Knight Cln.{timestamp}.png"));
+    }
+
+    #[test]
+    fn backups_are_named_after_the_file_with_a_timestamp() {
+        let directory = TempDir::new("output-backup");
+        fs::write(directory.join("Knight Cln.png"), b"atlas").unwrap();
+        fs::write(directory.join("notes"), b"notes").unwrap();
+
+        let backup_path = backup_file(&directory.join("Knight Cln.png")).unwrap();
+        let bare_backup_path = backup_file(&directory.join("notes")).unwrap();
+        let contents = (fs::read(&backup_path).unwrap(), fs::read(&bare_backup_path).unwrap());
+
+        let file_name = backup_path.file_name().unwrap().to_string_lossy();
+        assert!(matches_timestamps(&file_name, "Knight Cln.{timestamp}.bak.png"), "{}", file_name);
+        let bare_file_name = bare_backup_path.file_name().unwrap().to_string_lossy();
+        assert!(matches_timestamps(&bare_file_name, "notes.{timestamp}.bak"), "{}", bare_file_name);
+        assert_eq!(contents, (b"atlas".to_vec(), b"notes".to_vec()));
+    }
+
+    #[test]
+    fn previous_timestamped_atlas_is_the_newest_with_a_manifest() {
+        let directory = TempDir::new("output-previous");
+        let collection = test_support::collection(Path::new("sprites"), Vec::new());
+        let output = output(&directory, "{timestamp}/{collection}.{timestamp}.png");
+        assert_eq!(output.previous_atlas_path(&collection), None);
+
+        let atlases = [
+            ("20230102-150405", true),
+            ("20230102-150406", true),
+            // Newer, but not packed incrementally
+            ("20230102-150407", false),
+        ];
+        for (timestamp, has_manifest) in atlases {
+            let atlas_path = directory.join(timestamp).join(format!("Knight Cln.{}.png", timestamp));
+            fs::create_dir_all(atlas_path.parent().unwrap()).unwrap();
+            fs::write(&atlas_path, b"atlas").unwrap();
+            if has_manifest {
+                PackManifest::default().save(&atlas_path).unwrap();
+            }
+        }
+        let previous_atlas_path = output.previous_atlas_path(&collection);
+
+        assert_eq!(previous_atlas_path, Some(directory.join("20230102-150406/Knight Cln.20230102-150406.png")));
+        // Without a timestamp the previous atlas is where the next one is written, whether or not it exists
+        let untimestamped = AtlasOutput::new(directory.to_path_buf());
+        assert_eq!(untimestamped.previous_atlas_path(&collection), Some(untimestamped.atlas_path(&collection)));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::error::{PackerError, Result};
+use crate::packer::format::{convert_color, is_wide, open_atlas, AtlasFormat};
+use crate::packer::open_sprite;
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::Sprite;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, RgbaImage};
+use rayon::prelude::*;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Packs a collection of sprites into a copy of its atlas
+/// # Arguments
+/// * `collection` - The collection to pack
+/// * `sprites_path` - The path to sprite files
+/// * `format` - The pixel format to pack the atlas in
+/// * `on_progress` - Called with the pack progress as a percentage after each sprite is packed
+/// * `is_cancelled` - Polled while packing; the pack stops once it returns true
+/// # Returns
+/// * `Option<DynamicImage>` - The generated atlas, or `None` if the pack was cancelled
+pub fn pack_collection<P, C>(
+    collection: &Collection,
+    sprites_path: &Path,
+    format: AtlasFormat,
+    on_progress: P,
+    is_cancelled: C,
+) -> Result<Option<DynamicImage>>
+where
+    P: Fn(usize) + Sync,
+    C: Fn() -> bool + Sync,
+{
+    let atlas = open_atlas(&collection.path, format)?;
+    pack_sprites(atlas, &collection.sprites, sprites_path, on_progress, is_cancelled)
+}
+
+/// Packs sprites onto an atlas
+///
+/// Frames are copied at 16 bits per channel into atlases with more than 8 bits per channel, so that 16-bit
+/// frames keep their precision, and at 8 bits per channel otherwise. The atlas keeps its color type.
+/// # Arguments
+/// * `atlas` - The atlas to draw the sprites onto
+/// * `sprites` - The sprites to pack
+/// * `sprites_path` - The path to sprite files
+/// * `on_progress` - Called with the pack progress as a percentage after each sprite is packed
+/// * `is_cancelled` - Polled while packing; the pack stops once it returns true
+/// # Returns
+/// * `Option<DynamicImage>` - The generated atlas, or `None` if the pack was cancelled
+pub fn pack_sprites<P, C>(
+    atlas: DynamicImage,
+    sprites: &[Sprite],
+    sprites_path: &Path,
+    on_progress: P,
+    is_cancelled: C,
+) -> Result<Option<DynamicImage>>
+where
+    P: Fn(usize) + Sync,
+    C: Fn() -> bool + Sync,
+{
+    let color_type = atlas.color();
+    let packed = if is_wide(color_type) {
+        pack_pixels(atlas.into_rgba16(), sprites, sprites_path, DynamicImage::into_rgba16, on_progress, is_cancelled)?
+            .map(DynamicImage::ImageRgba16)
+    } else {
+        pack_pixels(atlas.into_rgba8(), sprites, sprites_path, DynamicImage::into_rgba8, on_progress, is_cancelled)?
+            .map(DynamicImage::ImageRgba8)
+    };
+
+    Ok(packed.map(|packed| convert_color(packed, color_type)))
+}
+
+/// Packs sprites onto an atlas with a single pixel type
+/// # Arguments
+/// * `atlas` - The atlas to draw the sprites onto
+/// * `sprites` - The sprites to pack
+/// * `sprites_path` - The path to sprite files
+/// * `convert` - Converts a frame to the atlas's pixel type
+/// * `on_progress` - Called with the pack progress as a percentage after each sprite is packed
+/// * `is_cancelled` - Polled while packing; the pack stops once it returns true
+/// # Returns
+/// * `Option<ImageBuffer>` - The generated atlas, or `None` if the pack was cancelled
+fn pack_pixels<Px, P, C>(
+    atlas: ImageBuffer<Px, Vec<Px::Subpixel>>,
+    sprites: &[Sprite],
+    sprites_path: &Path,
+    convert: fn(DynamicImage) -> ImageBuffer<Px, Vec<Px::Subpixel>>,
+    on_progress: P,
+    is_cancelled: C,
+) -> Result<Option<ImageBuffer<Px, Vec<Px::Subpixel>>>>
+where
+    Px: Pixel + Send + Sync,
+    Px::Subpixel: Send + Sync,
+    P: Fn(usize) + Sync,
+    C: Fn() -> bool + Sync,
+{
+    let running_task = AtomicBool::new(true);
+    let sprite_num = AtomicUsize::new(0);
+    let atlas_width = atlas.width() as i32;
+    let atlas_height = atlas.height() as i32;
+    let gen_atlas = Mutex::new(atlas);
+    sprites.par_iter().try_for_each(|sprite| {
+        if !running_task.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let (_, frame_image) = open_sprite(sprite, sprites_path)?;
+        let frame_image = convert(frame_image);
+
+        let _ = (0..frame_image.width()).into_par_iter().try_for_each(|i| {
+            (0..frame_image.height()).into_par_iter().try_for_each(|j| {
+                let i = i as i32;
+                let j = j as i32;
+                let (x, y) = atlas_position(sprite, i - sprite.xr, j - sprite.yr, atlas_height);
+                if i >= sprite.xr && i < (sprite.xr + sprite.width)
+                    && j >= sprite.yr && j < (sprite.yr + sprite.height)
+                    && x >= 0 && x < atlas_width && y >= 0 && y < atlas_height
+                {
+                    gen_atlas.lock().expect("Failed to lock generated atlas").put_pixel(
+                        x as u32,
+                        y as u32,
+                        *frame_image.get_pixel(i as u32, (frame_image.height() as i32 - j - 1) as u32),
+                    );
+                }
+
+                if is_cancelled() {
+                    running_task.store(false, Ordering::Relaxed);
+                    return ControlFlow::Break(());
+                }
+
+                ControlFlow::Continue(())
+            })
+        });
+
+        let num = sprite_num.fetch_add(1, Ordering::Relaxed) + 1;
+        on_progress(num * 100 / sprites.len());
+
+        Ok(())
+    })?;
+
+    if !running_task.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+
+    Ok(Some(gen_atlas.into_inner().expect("Failed to lock generated atlas")))
+}
+
+/// Get where a pixel of a sprite's trimmed rect is packed in its atlas
+/// # Arguments
+/// * `sprite` - The sprite the pixel belongs to
+/// * `di` - The pixel's offset from the left of the trimmed rect
+/// * `dj` - The pixel's offset from the bottom of the trimmed rect
+/// * `atlas_height` - The height of the atlas
+/// # Returns
+/// * `(i32, i32)` - The pixel's position in the atlas from its top left corner, which may be outside the atlas
+pub fn atlas_position(sprite: &Sprite, di: i32, dj: i32, atlas_height: i32) -> (i32, i32) {
+    if sprite.flipped {
+        (sprite.x + dj, atlas_height - (sprite.y + di) - 1)
+    } else {
+        (sprite.x + di, atlas_height - (sprite.y + dj) - 1)
+    }
+}
+
+/// Read the trimmed rect of a sprite back out of an atlas
+/// # Arguments
+/// * `sprite` - The sprite to read
+/// * `atlas` - The atlas the sprite was packed into
+/// # Returns
+/// * `RgbaImage` - The sprite's trimmed rect, the same way up as in its frame file
+pub fn read_sprite_rect(sprite: &Sprite, atlas: &DynamicImage) -> Result<RgbaImage> {
+    if sprite.width < 0 || sprite.height < 0 {
+        return Err(PackerError::Geometry(format!(
+            "Rect {}x{} of sprite {} has a negative size", sprite.width, sprite.height, sprite.path,
+        )));
+    }
+
+    let atlas_width = atlas.width() as i32;
+    let atlas_height = atlas.height() as i32;
+    let mut rect = RgbaImage::new(sprite.width as u32, sprite.height as u32);
+    for (tx, ty, pixel) in rect.enumerate_pixels_mut() {
+        // Offsets into the trimmed rect, with y measured from the bottom like in the pack
+        let (x, y) = atlas_position(sprite, tx as i32, sprite.height - 1 - ty as i32, atlas_height);
+        if x < 0 || x >= atlas_width || y < 0 || y >= atlas_height {
+            return Err(PackerError::Geometry(format!(
+                "Rect {}x{} of sprite {} at ({}, {}) does not fit inside its {}x{} atlas",
+                sprite.width, sprite.height, sprite.path, sprite.x, sprite.y, atlas_width, atlas_height,
+            )));
+        }
+        *pixel = atlas.get_pixel(x as u32, y as u32);
+    }
+
+    Ok(rect)
+}
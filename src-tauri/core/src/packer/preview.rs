@@ -0,0 +1,277 @@
+use crate::error::{PackerError, Result};
+use crate::packer::open_sprite;
+use crate::packer::webp::{self, WebpFrame};
+use crate::tk2d::clip::Clip;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, Frame, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+/// The largest width or height of a GIF
+const MAX_GIF_DIMENSION: u32 = u16::MAX as u32;
+
+/// An animated image format to export clips to
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PreviewFormat {
+    /// A GIF, whose pixels are either opaque or fully transparent and whose delays are in hundredths of a second
+    Gif,
+    /// An animated PNG, which keeps every color and level of transparency
+    Apng,
+    /// A lossless animated WebP, which keeps every color and level of transparency
+    Webp,
+}
+
+impl PreviewFormat {
+    /// Get the file extension of the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PreviewFormat::Gif => "gif",
+            PreviewFormat::Apng => "png",
+            PreviewFormat::Webp => "webp",
+        }
+    }
+}
+
+/// How to export a clip to an animated image
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewOptions {
+    pub format: PreviewFormat,
+    /// The number of times to enlarge each pixel by, without smoothing
+    pub scale: u32,
+    /// The number of times the frames from the clip's loop start play before the preview starts over,
+    /// since animated images can only loop from their first frame
+    #[serde(rename = "loopRepeats")]
+    pub loop_repeats: u32,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            format: PreviewFormat::Gif,
+            scale: 1,
+            loop_repeats: 3,
+        }
+    }
+}
+
+/// Export a clip to an animated image that loops forever
+/// # Arguments
+/// * `clip` - The clip to export
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `options` - The format and scale to export to
+/// * `path` - The path to write the animated image to
+pub fn export_clip(clip: &Clip, sprites_path: &Path, options: &PreviewOptions, path: &Path) -> Result<()> {
+    let canvases = render_clip(clip, sprites_path, options.scale)?;
+    let (width, height) = canvases[0].dimensions();
+    let max_dimension = match options.format {
+        PreviewFormat::Gif => MAX_GIF_DIMENSION,
+        PreviewFormat::Apng => u32::MAX,
+        PreviewFormat::Webp => webp::MAX_DIMENSION,
+    };
+    if width > max_dimension || height > max_dimension {
+        return Err(PackerError::Geometry(format!(
+            "Clip {} is {}x{} pixels at {}x scale, larger than {:?} allows",
+            clip.name, width, height, options.scale, options.format,
+        )));
+    }
+
+    let order = frame_order(canvases.len(), clip.loop_start as usize, options.loop_repeats);
+    match options.format {
+        PreviewFormat::Gif => {
+            let file = File::create(path).map_err(|e| PackerError::io(path, e))?;
+            let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+            encoder.set_repeat(Repeat::Infinite).map_err(|e| PackerError::image(path, e))?;
+            let delays = frame_delays(order.len(), clip.fps, 100);
+            let frames = order.iter().zip(delays).map(|(&index, delay)| {
+                Frame::from_parts(canvases[index].clone(), 0, 0, Delay::from_numer_denom_ms(delay * 10, 1))
+            });
+            encoder.encode_frames(frames).map_err(|e| PackerError::image(path, e))
+        }
+        PreviewFormat::Apng => {
+            let file = File::create(path).map_err(|e| PackerError::io(path, e))?;
+            write_apng(BufWriter::new(file), &canvases, &order, clip.fps)
+                .map_err(|e| PackerError::image(path, png_error(e)))
+        }
+        PreviewFormat::Webp => {
+            let delays = frame_delays(order.len(), clip.fps, 1000);
+            let frames = order
+                .iter()
+                .zip(delays)
+                .map(|(&index, duration)| WebpFrame { image: &canvases[index], duration })
+                .collect::<Vec<WebpFrame>>();
+            fs::write(path, webp::encode_animation(&frames)).map_err(|e| PackerError::io(path, e))
+        }
+    }
+}
+
+/// Draw each frame of a clip on a canvas that fits every frame
+///
+/// Only the pixels inside each sprite's rect are drawn, since those are all that is packed and shown in-game, and
+/// frames are aligned by their centres, which is where tk2d places them. The clip's frames are expected in the order
+/// they play, which is how `load_clip` sorts them.
+/// # Arguments
+/// * `clip` - The clip to draw
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `scale` - The number of times to enlarge each pixel by
+/// # Returns
+/// * `Vec<RgbaImage>` - A canvas for each frame of the clip, in the order they play
+pub fn render_clip(clip: &Clip, sprites_path: &Path, scale: u32) -> Result<Vec<RgbaImage>> {
+    if clip.frames.is_empty() {
+        return Err(PackerError::Geometry(format!("Clip {} has no frames", clip.name)));
+    }
+
+    // Each frame's trimmed pixels and where they are relative to the frame's centre
+    let mut trimmed = Vec::new();
+    for sprite in &clip.frames {
+        let (_, frame) = open_sprite(sprite, sprites_path)?;
+        let frame = frame.to_rgba8();
+        let (width, height) = (frame.width() as i32, frame.height() as i32);
+        let left = sprite.xr.clamp(0, width);
+        let right = (sprite.xr + sprite.width).clamp(0, width);
+        let top = (height - (sprite.yr + sprite.height)).clamp(0, height);
+        let bottom = (height - sprite.yr).clamp(0, height);
+        let pixels = imageops::crop_imm(&frame, left as u32, top as u32, (right - left) as u32, (bottom - top) as u32).to_image();
+        trimmed.push((pixels, left - width / 2, top - height / 2));
+    }
+
+    let min_x = trimmed.iter().map(|(_, x, _)| *x).min().unwrap_or(0);
+    let min_y = trimmed.iter().map(|(_, _, y)| *y).min().unwrap_or(0);
+    let max_x = trimmed.iter().map(|(pixels, x, _)| x + pixels.width() as i32).max().unwrap_or(0);
+    let max_y = trimmed.iter().map(|(pixels, _, y)| y + pixels.height() as i32).max().unwrap_or(0);
+    let (width, height) = ((max_x - min_x).max(1) as u32, (max_y - min_y).max(1) as u32);
+    let scale = scale.max(1);
+
+    Ok(trimmed
+        .iter()
+        .map(|(pixels, x, y)| {
+            let mut canvas = RgbaImage::new(width, height);
+            imageops::replace(&mut canvas, pixels, (x - min_x) as i64, (y - min_y) as i64);
+            if scale > 1 {
+                canvas = imageops::resize(&canvas, width * scale, height * scale, FilterType::Nearest);
+            }
+            canvas
+        })
+        .collect())
+}
+
+/// Get the order to show a clip's frames in, playing the frames before the loop start once and the rest several times
+/// # Arguments
+/// * `num_frames` - The number of frames in the clip
+/// * `loop_start` - The index of the first frame that loops
+/// * `loop_repeats` - The number of times to play the looping frames
+/// # Returns
+/// * `Vec<usize>` - The index of each frame to show
+pub fn frame_order(num_frames: usize, loop_start: usize, loop_repeats: u32) -> Vec<usize> {
+    if loop_start == 0 || loop_start >= num_frames {
+        return (0..num_frames).collect();
+    }
+
+    let mut order = (0..loop_start).collect::<Vec<usize>>();
+    for _ in 0..loop_repeats.max(1) {
+        order.extend(loop_start..num_frames);
+    }
+    order
+}
+
+/// Get how long to show each frame for in whole units, rounding the time each frame starts at so that the clip
+/// doesn't drift from its frame rate
+/// # Arguments
+/// * `num_frames` - The number of frames to show
+/// * `fps` - The frame rate of the clip
+/// * `units_per_second` - The number of units in a second, e.g. 100 for a GIF's hundredths of a second
+/// # Returns
+/// * `Vec<u32>` - The delay of each frame
+fn frame_delays(num_frames: usize, fps: f32, units_per_second: u32) -> Vec<u32> {
+    let fps = if fps > 0.0 { fps as f64 } else { 1.0 };
+    let start = |frame: usize| (frame as f64 * units_per_second as f64 / fps).round() as u32;
+    (0..num_frames).map(|frame| (start(frame + 1) - start(frame)).max(1)).collect()
+}
+
+/// Write canvases as an animated PNG in the given order
+fn write_apng<W: std::io::Write>(writer: W, canvases: &[RgbaImage], order: &[usize], fps: f32) -> std::result::Result<(), png::EncodingError> {
+    let (width, height) = canvases[0].dimensions();
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(order.len() as u32, 0)?;
+    encoder.set_dispose_op(png::DisposeOp::Background)?;
+    encoder.set_blend_op(png::BlendOp::Source)?;
+
+    let mut writer = encoder.write_header()?;
+    for (&index, delay) in order.iter().zip(frame_delays(order.len(), fps, 1000)) {
+        writer.set_frame_delay(delay.min(u16::MAX as u32) as u16, 1000)?;
+        writer.write_image_data(canvases[index].as_raw())?;
+    }
+    writer.finish()
+}
+
+/// Wrap an error from encoding a PNG as an image error
+fn png_error(e: png::EncodingError) -> image::ImageError {
+    image::ImageError::Encoding(image::error::EncodingError::new(ImageFormat::Png.into(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use crate::packer::load::load_clip;
+    use crate::tk2d::info::SpriteInfo;
+    use image::codecs::png::PngDecoder;
+    use image::codecs::webp::WebPDecoder;
+    use image::{AnimationDecoder, Rgba};
+
+    #[test]
+    fn frame_order_repeats_frames_from_the_loop_start() {
+        assert_eq!(frame_order(4, 0, 3), vec![0, 1, 2, 3]);
+        assert_eq!(frame_order(4, 2, 2), vec![0, 1, 2, 3, 2, 3]);
+        assert_eq!(frame_order(4, 4, 2), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn exported_frames_follow_frame_numbers_in_file_names() {
+        let sprites_path = TempDir::new("preview");
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+
+        // Eleven 3x3 frames, each filled with its frame number, listed backwards in the sprite info so that
+        // neither the sprite info nor sorting the names as text gives the order they play in
+        let paths = (0..11).rev().map(|frame| format!("Knight/Idle/Idle-{}-{}.png", frame, frame + 1)).collect::<Vec<String>>();
+        for (path, frame) in paths.iter().zip((0..11).rev()) {
+            RgbaImage::from_pixel(3, 3, Rgba([frame * 20, 0, 0, 255])).save(sprites_path.join(path)).unwrap();
+        }
+        let anim_info = r#"{"fps": 10, "loopStart": 8, "numFrames": 11, "collectionName": "Knight Cln"}"#;
+        fs::write(sprites_path.join("Knight/Idle/AnimInfo.json"), anim_info).unwrap();
+        let sprite_info = SpriteInfo {
+            id: (1..=11).rev().collect(),
+            x: vec![0; 11],
+            y: vec![0; 11],
+            xr: vec![0; 11],
+            yr: vec![0; 11],
+            width: vec![3; 11],
+            height: vec![3; 11],
+            collection_name: vec!["Knight Cln".to_string(); 11],
+            path: paths,
+            flipped: vec![false; 11],
+        };
+        let clip = load_clip(&sprites_path.join("Knight/Idle"), &sprite_info).unwrap();
+
+        let options = PreviewOptions { format: PreviewFormat::Apng, scale: 1, loop_repeats: 2 };
+        let apng_path = sprites_path.join("Idle.png");
+        export_clip(&clip, &sprites_path, &options, &apng_path).unwrap();
+        let webp_path = sprites_path.join("Idle.webp");
+        export_clip(&clip, &sprites_path, &PreviewOptions { format: PreviewFormat::Webp, ..options }, &webp_path).unwrap();
+
+        let apng = PngDecoder::new(File::open(&apng_path).unwrap()).unwrap().apng().into_frames();
+        let webp = WebPDecoder::new(File::open(&webp_path).unwrap()).unwrap().into_frames();
+
+        let expected = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 8, 9, 10];
+        for frames in [apng.collect_frames().unwrap(), webp.collect_frames().unwrap()] {
+            let shown = frames.iter().map(|frame| frame.buffer().get_pixel(1, 1)[0] / 20).collect::<Vec<u8>>();
+            assert_eq!(shown, expected);
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use crate::error::{PackerError, Result};
+use crate::packer::definitions::{build_definitions, DefinitionsFile};
+use crate::packer::format::{convert_color, open_atlas, AtlasFormat};
+use crate::packer::load::load_sprite_info;
+use crate::packer::open_sprite;
+use crate::packer::output::animation_name;
+use crate::packer::pack::pack_sprites;
+use crate::tk2d::cln::Collection;
+use crate::tk2d::compare::CompareMode;
+use crate::tk2d::info::SpriteInfo;
+use crate::tk2d::sprite::Sprite;
+use image::{ColorType, DynamicImage, GenericImageView, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How to lay out a collection's sprites in a new atlas
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepackOptions {
+    /// The number of transparent pixels between sprites and around the edge of the atlas
+    pub padding: u32,
+    /// Whether sprites may be rotated into the atlas, which sets their `flipped` flag
+    #[serde(rename = "allowFlip")]
+    pub allow_flip: bool,
+    /// Whether the atlas's width and height must be powers of two
+    #[serde(rename = "powerOfTwo")]
+    pub power_of_two: bool,
+    /// The largest width and height the atlas may have
+    #[serde(rename = "maxSize")]
+    pub max_size: u32,
+}
+
+impl Default for RepackOptions {
+    fn default() -> Self {
+        Self {
+            padding: 2,
+            allow_flip: false,
+            power_of_two: true,
+            max_size: 4096,
+        }
+    }
+}
+
+/// A collection laid out in a new atlas
+#[derive(Clone, Debug)]
+pub struct Repack {
+    pub atlas: DynamicImage,
+    /// Every sprite in the collection with its new rect and placement
+    pub sprites: Vec<Sprite>,
+    /// The sprite definitions of the new layout, for patching the collection at runtime
+    pub definitions: DefinitionsFile,
+}
+
+/// The files a repacked collection was written to
+#[derive(Clone, Debug)]
+pub struct RepackFiles {
+    pub atlas: PathBuf,
+    pub sprite_info: PathBuf,
+    pub definitions: PathBuf,
+}
+
+/// A sprite's trimmed rect in its frame, with `yr` measured from the bottom like in the sprite info
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Trim {
+    xr: i32,
+    yr: i32,
+    width: i32,
+    height: i32,
+}
+
+/// A rect in an atlas, with `y` measured from the bottom like in the sprite info
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Where a sprite goes in an atlas
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    /// The rect the sprite takes up in the atlas, which is its trimmed rect turned sideways if it is flipped
+    rect: Rect,
+    flipped: bool,
+}
+
+/// Where every sprite goes in an atlas, and the atlas's size
+#[derive(Clone, Debug)]
+struct Layout {
+    placements: Vec<Placement>,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    fn right(&self) -> u32 {
+        self.x + self.width
+    }
+
+    fn top(&self) -> u32 {
+        self.y + self.height
+    }
+
+    fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x && other.y >= self.y && other.right() <= self.right() && other.top() <= self.top()
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        other.x < self.right() && other.right() > self.x && other.y < self.top() && other.top() > self.y
+    }
+}
+
+/// Lay out every sprite of a collection in a new atlas, trimming each frame to its painted pixels
+///
+/// Sprites with the same ID share a rect, so their frames must have the same painted pixels. Groups whose frames
+/// differ have to be resolved first, since only one frame can be drawn into the shared rect.
+/// # Arguments
+/// * `collection` - The collection to repack
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `options` - How to lay out the sprites
+/// * `format` - The pixel format to write the atlas in
+/// # Returns
+/// * `Repack` - The new atlas, the collection's sprites with their new rects and their sprite definitions
+pub fn repack_collection(
+    collection: &Collection,
+    sprites_path: &Path,
+    options: &RepackOptions,
+    format: AtlasFormat,
+) -> Result<Repack> {
+    let mut groups: BTreeMap<u32, Vec<&Sprite>> = BTreeMap::new();
+    for sprite in &collection.sprites {
+        let sprites = groups.entry(sprite.id).or_default();
+        if !sprites.iter().any(|existing| existing.path == sprite.path) {
+            sprites.push(sprite);
+        }
+    }
+    let groups = groups.into_iter().collect::<Vec<(u32, Vec<&Sprite>)>>();
+
+    let trims = groups
+        .par_iter()
+        .map(|(id, sprites)| {
+            let (_, first_frame) = open_sprite(sprites[0], sprites_path)?;
+            let first_pixels = first_frame.to_rgba8();
+            for sprite in &sprites[1..] {
+                let (_, frame) = open_sprite(sprite, sprites_path)?;
+                if !CompareMode::IgnoreTransparentRgb.images_match(&first_pixels, &frame.to_rgba8()) {
+                    return Err(PackerError::Unresolved(format!(
+                        "Sprite {} in collection {}: frames {} and {} differ, resolve them before repacking",
+                        id, collection.name, sprites[0].path, sprite.path,
+                    )));
+                }
+            }
+            // A frame with nothing painted still needs a rect for the game to load
+            Ok(painted_trim(&first_frame).unwrap_or(Trim { xr: 0, yr: 0, width: 1, height: 1 }))
+        })
+        .collect::<Result<Vec<Trim>>>()?;
+
+    let sizes = trims.iter().map(|trim| (trim.width as u32, trim.height as u32)).collect::<Vec<(u32, u32)>>();
+    let layout = layout(&sizes, options).ok_or_else(|| {
+        PackerError::Geometry(format!(
+            "The sprites of collection {} do not fit in a {}x{} atlas",
+            collection.name, options.max_size, options.max_size,
+        ))
+    })?;
+
+    let mut placed: BTreeMap<u32, Sprite> = BTreeMap::new();
+    for (((id, sprites), trim), placement) in groups.iter().zip(&trims).zip(&layout.placements) {
+        placed.insert(*id, Sprite {
+            x: placement.rect.x as i32,
+            y: placement.rect.y as i32,
+            xr: trim.xr,
+            yr: trim.yr,
+            width: trim.width,
+            height: trim.height,
+            flipped: placement.flipped,
+            ..sprites[0].clone()
+        });
+    }
+
+    let first_sprites = placed.values().cloned().collect::<Vec<Sprite>>();
+    // Start from a blank atlas in the pixel format a pack of the collection would write
+    let color_type = match format {
+        AtlasFormat::Original => open_atlas(&collection.path, format)?.color(),
+        _ => format.color_type(ColorType::Rgba8),
+    };
+    let blank = convert_color(DynamicImage::ImageRgba8(RgbaImage::new(layout.width, layout.height)), color_type);
+    let atlas = pack_sprites(blank, &first_sprites, sprites_path, |_| {}, || false)?
+        .ok_or_else(|| PackerError::Cancelled(collection.name.clone()))?;
+
+    let sprites = collection
+        .sprites
+        .iter()
+        .map(|sprite| {
+            let new_sprite = &placed[&sprite.id];
+            Sprite {
+                x: new_sprite.x,
+                y: new_sprite.y,
+                xr: new_sprite.xr,
+                yr: new_sprite.yr,
+                width: new_sprite.width,
+                height: new_sprite.height,
+                flipped: new_sprite.flipped,
+                ..sprite.clone()
+            }
+        })
+        .collect::<Vec<Sprite>>();
+
+    let definitions = build_definitions(
+        &collection.name,
+        &sprites,
+        &format!("{}.png", collection.name),
+        (layout.width, layout.height),
+        sprites_path,
+    )?;
+
+    Ok(Repack { atlas, sprites, definitions })
+}
+
+/// Write a repacked atlas, its updated sprite info and its sprite definitions into a sprites folder in the GODump layout
+/// # Arguments
+/// * `repack` - The repacked collection
+/// * `collection` - The collection that was repacked
+/// * `output_path` - The sprites folder to write to, which may be the one the collection was loaded from
+/// # Returns
+/// * `RepackFiles` - The paths the atlas, sprite info and sprite definitions were written to
+pub fn write_repack(repack: &Repack, collection: &Collection, output_path: &Path) -> Result<RepackFiles> {
+    let output_anim_path = output_path.join(animation_name(collection));
+    let atlases_path = output_anim_path.join("0.Atlases");
+    fs::create_dir_all(&atlases_path).map_err(|e| PackerError::io(&atlases_path, e))?;
+
+    // Build on sprite info already written to the output, so repacking several collections keeps every update
+    let sprite_info_path = atlases_path.join("SpriteInfo.json");
+    let mut sprite_info = if sprite_info_path.exists() {
+        load_sprite_info(&output_anim_path)?
+    } else {
+        let anim_path = collection.path.parent().and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+        load_sprite_info(anim_path)?
+    };
+    update_sprite_info(&mut sprite_info, &collection.name, &repack.sprites);
+    let text = serde_json::to_string(&sprite_info).map_err(|e| PackerError::json(&sprite_info_path, e))?;
+    fs::write(&sprite_info_path, text).map_err(|e| PackerError::io(&sprite_info_path, e))?;
+
+    let atlas_path = atlases_path.join(format!("{}.png", collection.name));
+    repack.atlas.save(&atlas_path).map_err(|e| PackerError::image(&atlas_path, e))?;
+    let definitions_path = repack.definitions.save(&atlas_path)?;
+
+    Ok(RepackFiles {
+        atlas: atlas_path,
+        sprite_info: sprite_info_path,
+        definitions: definitions_path,
+    })
+}
+
+/// Update the rects and placements of a collection's entries in a sprite info
+/// # Arguments
+/// * `sprite_info` - The sprite info to update
+/// * `collection_name` - The name of the collection whose entries are updated
+/// * `sprites` - The collection's sprites with their new rects, matched to entries by ID
+pub fn update_sprite_info(sprite_info: &mut SpriteInfo, collection_name: &str, sprites: &[Sprite]) {
+    for i in 0..sprite_info.id.len() {
+        if sprite_info.collection_name.get(i).map(String::as_str) != Some(collection_name) {
+            continue;
+        }
+        let sprite = match sprites.iter().find(|sprite| sprite.id == sprite_info.id[i]) {
+            Some(sprite) => sprite,
+            None => continue,
+        };
+
+        let fields = [
+            (&mut sprite_info.x, sprite.x),
+            (&mut sprite_info.y, sprite.y),
+            (&mut sprite_info.xr, sprite.xr),
+            (&mut sprite_info.yr, sprite.yr),
+            (&mut sprite_info.width, sprite.width),
+            (&mut sprite_info.height, sprite.height),
+        ];
+        for (field, value) in fields {
+            if let Some(existing) = field.get_mut(i) {
+                *existing = value;
+            }
+        }
+        if let Some(flipped) = sprite_info.flipped.get_mut(i) {
+            *flipped = sprite.flipped;
+        }
+    }
+}
+
+/// Get the smallest rect of a frame containing every non-transparent pixel
+/// # Arguments
+/// * `frame` - The frame to trim
+/// # Returns
+/// * `Option<Trim>` - The painted rect, or `None` if the frame is fully transparent
+fn painted_trim(frame: &DynamicImage) -> Option<Trim> {
+    let height = frame.height() as i32;
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for (x, y, pixel) in frame.pixels() {
+        if pixel[3] == 0 {
+            continue;
+        }
+        let (x, y) = (x as i32, height - y as i32 - 1);
+        bounds = Some(match bounds {
+            Some((left, bottom, right, top)) => (left.min(x), bottom.min(y), right.max(x), top.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+
+    bounds.map(|(left, bottom, right, top)| Trim {
+        xr: left,
+        yr: bottom,
+        width: right - left + 1,
+        height: top - bottom + 1,
+    })
+}
+
+/// Find the smallest atlas the sprites fit in and where each sprite goes in it
+/// # Arguments
+/// * `sizes` - The width and height of each sprite's trimmed rect
+/// * `options` - How to lay out the sprites
+/// # Returns
+/// * `Option<Layout>` - Where each sprite goes, or `None` if the sprites don't fit in the largest allowed atlas
+fn layout(sizes: &[(u32, u32)], options: &RepackOptions) -> Option<Layout> {
+    let padding = options.padding;
+    let area = sizes
+        .iter()
+        .map(|(width, height)| (width + padding) as u64 * (height + padding) as u64)
+        .sum::<u64>();
+    let longest = sizes.iter().map(|(width, height)| *width.max(height) + 2 * padding).max().unwrap_or(1);
+
+    let mut width = longest.next_power_of_two();
+    let mut height = longest.next_power_of_two();
+    while (width as u64) * (height as u64) < area {
+        if width <= height {
+            width *= 2;
+        } else {
+            height *= 2;
+        }
+    }
+
+    while width <= options.max_size && height <= options.max_size {
+        if let Some(placements) = place_all(sizes, width, height, options) {
+            if options.power_of_two {
+                return Some(Layout { placements, width, height });
+            }
+            let used_width = placements.iter().map(|placement| placement.rect.right()).max().unwrap_or(0) + padding;
+            let used_height = placements.iter().map(|placement| placement.rect.top()).max().unwrap_or(0) + padding;
+            return Some(Layout {
+                placements,
+                width: used_width.max(1),
+                height: used_height.max(1),
+            });
+        }
+
+        if width <= height {
+            width *= 2;
+        } else {
+            height *= 2;
+        }
+    }
+
+    None
+}
+
+/// Place every sprite in an atlas of a fixed size with MaxRects, largest sprites first
+/// # Arguments
+/// * `sizes` - The width and height of each sprite's trimmed rect
+/// * `width` - The width of the atlas
+/// * `height` - The height of the atlas
+/// * `options` - How to lay out the sprites
+/// # Returns
+/// * `Option<Vec<Placement>>` - Where each sprite goes, or `None` if the sprites don't fit
+fn place_all(sizes: &[(u32, u32)], width: u32, height: u32, options: &RepackOptions) -> Option<Vec<Placement>> {
+    let padding = options.padding;
+    if width <= padding || height <= padding {
+        return None;
+    }
+
+    let mut order = (0..sizes.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|&index| {
+        let (w, h) = sizes[index];
+        (std::cmp::Reverse(w.max(h)), std::cmp::Reverse(w * h), index)
+    });
+
+    // Each sprite takes up its size plus the padding on one side, in a bin inset by the padding on the other
+    let mut free = vec![Rect {
+        x: padding,
+        y: padding,
+        width: width - padding,
+        height: height - padding,
+    }];
+    let mut placements = vec![None; sizes.len()];
+    for index in order {
+        let (w, h) = sizes[index];
+        let mut orientations = vec![(w + padding, h + padding, false)];
+        if options.allow_flip && w != h {
+            orientations.push((h + padding, w + padding, true));
+        }
+
+        let mut best: Option<(Rect, bool, (u32, u32))> = None;
+        for free_rect in &free {
+            for &(w, h, flipped) in &orientations {
+                if w > free_rect.width || h > free_rect.height {
+                    continue;
+                }
+                let leftover_x = free_rect.width - w;
+                let leftover_y = free_rect.height - h;
+                let score = (leftover_x.min(leftover_y), leftover_x.max(leftover_y));
+                if best.map_or(true, |(_, _, best_score)| score < best_score) {
+                    best = Some((Rect { x: free_rect.x, y: free_rect.y, width: w, height: h }, flipped, score));
+                }
+            }
+        }
+
+        let (used, flipped, _) = best?;
+        split_free_rects(&mut free, &used);
+        placements[index] = Some(Placement {
+            rect: Rect {
+                x: used.x,
+                y: used.y,
+                width: used.width - padding,
+                height: used.height - padding,
+            },
+            flipped,
+        });
+    }
+
+    placements.into_iter().collect()
+}
+
+/// Split the free rects around a rect that was just used, then drop free rects contained in others
+fn split_free_rects(free: &mut Vec<Rect>, used: &Rect) {
+    let mut split = Vec::new();
+    for rect in free.drain(..) {
+        if !rect.intersects(used) {
+            split.push(rect);
+            continue;
+        }
+        if used.x > rect.x {
+            split.push(Rect { width: used.x - rect.x, ..rect });
+        }
+        if used.right() < rect.right() {
+            split.push(Rect { x: used.right(), width: rect.right() - used.right(), ..rect });
+        }
+        if used.y > rect.y {
+            split.push(Rect { height: used.y - rect.y, ..rect });
+        }
+        if used.top() < rect.top() {
+            split.push(Rect { y: used.top(), height: rect.top() - used.top(), ..rect });
+        }
+    }
+
+    let mut pruned: Vec<Rect> = Vec::new();
+    for (index, rect) in split.iter().enumerate() {
+        let contained = split
+            .iter()
+            .enumerate()
+            .any(|(other_index, other)| other_index != index && other.contains(rect) && (other != rect || other_index < index));
+        if !contained {
+            pruned.push(*rect);
+        }
+    }
+    *free = pruned;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+
+    fn options(padding: u32, allow_flip: bool, power_of_two: bool, max_size: u32) -> RepackOptions {
+        RepackOptions { padding, allow_flip, power_of_two, max_size }
+    }
+
+    /// Grow a rect by the padding on its right and top, so that two grown rects that don't intersect are at least
+    /// the padding apart
+    fn padded(rect: &Rect, padding: u32) -> Rect {
+        Rect { width: rect.width + padding, height: rect.height + padding, ..*rect }
+    }
+
+    #[test]
+    fn placements_do_not_overlap_and_keep_their_padding() {
+        let sizes = [(7, 3), (3, 7), (5, 5), (1, 1), (9, 2), (2, 2), (4, 6), (6, 1), (3, 3), (8, 8)];
+        for padding in [0, 1, 3] {
+            for allow_flip in [false, true] {
+                let layout = layout(&sizes, &options(padding, allow_flip, true, 256)).unwrap();
+                for (index, (placement, (width, height))) in layout.placements.iter().zip(sizes).enumerate() {
+                    let rect = placement.rect;
+                    let expected = if placement.flipped { (height, width) } else { (width, height) };
+                    assert_eq!((rect.width, rect.height), expected);
+                    assert!(rect.x >= padding && rect.y >= padding, "{:?} with padding {}", rect, padding);
+                    assert!(rect.right() + padding <= layout.width && rect.top() + padding <= layout.height);
+                    for other in &layout.placements[index + 1..] {
+                        assert!(!padded(&rect, padding).intersects(&padded(&other.rect, padding)), "{:?} and {:?}", rect, other.rect);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flipping_turns_rects_sideways() {
+        // In an 8x8 atlas the 8x2 rect only fits next to the 2x8 one if it is turned sideways
+        let sizes = [(2, 8), (8, 2)];
+        assert!(layout(&sizes, &options(0, false, true, 8)).is_none());
+
+        let layout = layout(&sizes, &options(0, true, true, 8)).unwrap();
+        assert_eq!((layout.width, layout.height), (8, 8));
+        assert!(!layout.placements[0].flipped);
+        assert!(layout.placements[1].flipped);
+        assert_eq!((layout.placements[1].rect.width, layout.placements[1].rect.height), (2, 8));
+    }
+
+    #[test]
+    fn atlas_size_respects_power_of_two_and_max_size() {
+        let sizes = [(5, 3), (3, 3), (6, 2)];
+        let power_of_two = layout(&sizes, &options(1, false, true, 64)).unwrap();
+        assert!(power_of_two.width.is_power_of_two() && power_of_two.height.is_power_of_two());
+
+        let tight = layout(&sizes, &options(1, false, false, 64)).unwrap();
+        let used_width = tight.placements.iter().map(|placement| placement.rect.right()).max().unwrap();
+        let used_height = tight.placements.iter().map(|placement| placement.rect.top()).max().unwrap();
+        assert_eq!((tight.width, tight.height), (used_width + 1, used_height + 1));
+        assert!(tight.width <= power_of_two.width && tight.height <= power_of_two.height);
+
+        // The padding around the edge counts towards the largest size
+        assert!(layout(&[(8, 8)], &options(0, false, true, 8)).is_some());
+        assert!(layout(&[(8, 8)], &options(1, false, true, 8)).is_none());
+        assert!(layout(&[(8, 8)], &options(1, false, false, 16)).is_some());
+    }
+
+    #[test]
+    fn sprite_info_updates_only_touch_the_collection() {
+        let collection_names = ["Knight Cln", "Hornet Cln", "Knight Cln"];
+        let mut sprite_info = SpriteInfo {
+            id: vec![1, 1, 2],
+            x: vec![0; 3],
+            y: vec![0; 3],
+            xr: vec![0; 3],
+            yr: vec![0; 3],
+            width: vec![4; 3],
+            height: vec![4; 3],
+            collection_name: collection_names.iter().map(|name| name.to_string()).collect(),
+            path: vec!["Knight/Idle/Idle-0-1.png".to_string(), "Hornet/Idle/Idle-0-1.png".to_string(), "Knight/Idle/Idle-1-2.png".to_string()],
+            flipped: vec![false; 3],
+        };
+        let sprite = Sprite { x: 5, y: 6, yr: 2, flipped: true, ..test_support::sprite("Idle-0-1.png") };
+        update_sprite_info(&mut sprite_info, "Knight Cln", &[sprite]);
+
+        // The other collection's sprite with the same ID, and the collection's sprite with another ID, are untouched
+        assert_eq!((sprite_info.x.clone(), sprite_info.y.clone()), (vec![5, 0, 0], vec![6, 0, 0]));
+        assert_eq!((sprite_info.xr.clone(), sprite_info.yr.clone()), (vec![1, 0, 0], vec![2, 0, 0]));
+        assert_eq!((sprite_info.width.clone(), sprite_info.height.clone()), (vec![3, 4, 4], vec![2, 4, 4]));
+        assert_eq!(sprite_info.flipped, vec![true, false, false]);
+    }
+
+    #[test]
+    fn repacks_in_the_output_format_and_refuses_differing_duplicates() {
+        let sprites_path = TempDir::new("repack");
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+
+        // Two duplicates with the same 2x1 painted rect, and a sprite with another ID
+        let sprites = [("Idle-0-1.png", 1), ("Idle-1-1.png", 1), ("Idle-2-2.png", 2)].map(|(name, id)| Sprite {
+            id,
+            xr: 0,
+            yr: 0,
+            width: 4,
+            height: 4,
+            ..test_support::sprite(name)
+        });
+        let frame = RgbaImage::from_fn(4, 4, |x, y| image::Rgba([200, 100, 50, if x < 2 && y == 1 { 255 } else { 0 }]));
+        for sprite in &sprites {
+            frame.save(sprites_path.join(&sprite.path)).unwrap();
+        }
+        let collection = test_support::collection(&sprites_path, sprites.to_vec());
+        image::RgbImage::new(8, 8).save(&collection.path).unwrap();
+
+        let options = RepackOptions::default();
+        let original = repack_collection(&collection, &sprites_path, &options, AtlasFormat::Original).unwrap();
+        let wide = repack_collection(&collection, &sprites_path, &options, AtlasFormat::Rgba16).unwrap();
+        // Editing a duplicate other than the first can't be drawn into the shared rect
+        let mut edited = frame.clone();
+        edited.put_pixel(0, 1, image::Rgba([0, 0, 0, 255]));
+        edited.save(sprites_path.join(&sprites[1].path)).unwrap();
+        let differing = repack_collection(&collection, &sprites_path, &options, AtlasFormat::Rgba8);
+
+        assert_eq!(original.atlas.color(), ColorType::Rgb8);
+        assert_eq!(wide.atlas.color(), ColorType::Rgba16);
+        assert!(wide.sprites.iter().all(|sprite| (sprite.xr, sprite.yr, sprite.width, sprite.height) == (0, 2, 2, 1)));
+        assert!(matches!(differing, Err(PackerError::Unresolved(_))));
+    }
+}
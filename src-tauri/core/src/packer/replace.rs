@@ -0,0 +1,251 @@
+use crate::error::{PackerError, Result};
+use crate::packer::{open_sprite, png_data_url, sprite_path};
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::{Sprite, SpriteImage};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImage, GenericImageView};
+use log::info;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// What replacing a single duplicate frame would change
+#[derive(Clone, Debug, Serialize)]
+pub struct ReplacementPreview {
+    /// The path to the frame file that would be overwritten
+    pub path: PathBuf,
+    /// The number of pixels whose color would change
+    #[serde(rename = "changedPixels")]
+    pub changed_pixels: u32,
+    /// The number of pixels in the frame
+    #[serde(rename = "totalPixels")]
+    pub total_pixels: u32,
+    /// A PNG data URL of the frame as it is now
+    pub before: String,
+    /// A PNG data URL of the frame as it would be after replacement
+    pub after: String,
+}
+
+/// Replace all duplicate sprites in a collection
+/// # Arguments
+/// * `source_sprite` - The sprite to replace duplicates with
+/// * `collection` - The collection containing the source sprite
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `Vec<PathBuf>` - The paths of the frame files that were overwritten
+pub fn replace_duplicate_sprites(
+    source_sprite: &Sprite,
+    collection: &Collection,
+    sprites_path: &Path,
+) -> Result<Vec<PathBuf>> {
+    let (source_path, source_image) = open_sprite(source_sprite, sprites_path)?;
+    let source_image = SpriteImage {
+        sprite: source_sprite.clone(),
+        image: source_image,
+    };
+
+    let mut replaced_paths = Vec::new();
+    for sprite in &collection.sprites {
+        if sprite.id != source_sprite.id {
+            continue;
+        }
+
+        let (sprite_path, sprite_image) = open_sprite(sprite, sprites_path)?;
+        let mut sprite_image = SpriteImage {
+            sprite: sprite.clone(),
+            image: sprite_image,
+        };
+
+        replace_sprite(&source_image, &mut sprite_image)?;
+
+        sprite_image.image.save(&sprite_path).map_err(|e| PackerError::image(&sprite_path, e))?;
+        info!("Replaced sprite at path {:?} with sprite at path {:?}.", sprite_path.display(), source_path.display());
+        replaced_paths.push(sprite_path);
+    }
+
+    Ok(replaced_paths)
+}
+
+/// Compute the result of replacing all duplicate sprites in a collection without writing any files
+/// # Arguments
+/// * `source_sprite` - The sprite to replace duplicates with
+/// * `collection` - The collection containing the source sprite
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `thumbnail_size` - The size to fit the before and after images in, or `None` to keep them at full size
+/// # Returns
+/// * `Vec<ReplacementPreview>` - The changes to each frame file that would be overwritten
+pub fn preview_duplicate_replacement(
+    source_sprite: &Sprite,
+    collection: &Collection,
+    sprites_path: &Path,
+    thumbnail_size: Option<u32>,
+) -> Result<Vec<ReplacementPreview>> {
+    let (_, source_image) = open_sprite(source_sprite, sprites_path)?;
+    let source_image = SpriteImage {
+        sprite: source_sprite.clone(),
+        image: source_image,
+    };
+
+    let thumbnail = |image: &DynamicImage| match thumbnail_size {
+        Some(size) => image.resize(size, size, FilterType::Nearest),
+        None => image.clone(),
+    };
+
+    let mut previews = Vec::new();
+    for sprite in &collection.sprites {
+        if sprite.id != source_sprite.id {
+            continue;
+        }
+
+        let (sprite_path, sprite_image) = open_sprite(sprite, sprites_path)?;
+        let before = thumbnail(&sprite_image);
+        let mut sprite_image = SpriteImage {
+            sprite: sprite.clone(),
+            image: sprite_image,
+        };
+        let original = sprite_image.image.clone();
+
+        replace_sprite(&source_image, &mut sprite_image)?;
+
+        let changed_pixels = original
+            .pixels()
+            .zip(sprite_image.image.pixels())
+            .filter(|((_, _, before), (_, _, after))| before != after)
+            .count() as u32;
+        previews.push(ReplacementPreview {
+            changed_pixels,
+            total_pixels: original.width() * original.height(),
+            before: png_data_url(&before, &sprite_path)?,
+            after: png_data_url(&thumbnail(&sprite_image.image), &sprite_path)?,
+            path: sprite_path,
+        });
+    }
+
+    Ok(previews)
+}
+
+/// Get the frame files that replacing a sprite's duplicates would overwrite
+/// # Arguments
+/// * `source_sprite` - The sprite to replace duplicates with
+/// * `collection` - The collection containing the source sprite
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `Vec<PathBuf>` - The paths of the frame files of every sprite with the source sprite's ID
+pub fn duplicate_sprite_paths(
+    source_sprite: &Sprite,
+    collection: &Collection,
+    sprites_path: &Path,
+) -> Result<Vec<PathBuf>> {
+    collection
+        .sprites
+        .iter()
+        .filter(|sprite| sprite.id == source_sprite.id)
+        .map(|sprite| sprite_path(sprite, sprites_path))
+        .collect()
+}
+
+/// Replace a sprite with another sprite
+///
+/// Frames are stored the right way up whether or not their sprite is flipped, so the source's trimmed rect is
+/// copied as is to the target's offsets in its frame, and the pack rotates it if the target is flipped.
+/// # Arguments
+/// * `source_image` - The sprite to replace with
+/// * `target_image` - The sprite to replace
+pub fn replace_sprite(source_image: &SpriteImage, target_image: &mut SpriteImage) -> Result<()> {
+    let sub_image = source_image.trim()?;
+    let source = &source_image.sprite;
+    let target = &target_image.sprite;
+    if (sub_image.width() as i32, sub_image.height() as i32) != (target.width, target.height) {
+        return Err(PackerError::Geometry(format!(
+            "{}x{} rect of sprite {}{} does not match the {}x{} rect of sprite {}{}",
+            sub_image.width(), sub_image.height(), source.path, if source.flipped { " (flipped)" } else { "" },
+            target.width, target.height, target.path, if target.flipped { " (flipped)" } else { "" },
+        )));
+    }
+    if target.xr < 0 || target.yr < 0
+        || target.xr as u32 + sub_image.width() > target_image.image.width()
+        || target.yr as u32 + sub_image.height() > target_image.image.height()
+    {
+        return Err(PackerError::Geometry(format!(
+            "{}x{} rect of sprite {} does not fit at ({}, {}) inside the {}x{} frame of sprite {}",
+            sub_image.width(), sub_image.height(), source.path, target.xr, target.yr,
+            target_image.image.width(), target_image.image.height(), target.path,
+        )));
+    }
+
+    for x in 0..sub_image.width() {
+        for y in 0..sub_image.height() {
+            let source_y = sub_image.height() - y - 1;
+            let pixel = sub_image.get_pixel(x, source_y);
+            let target_x = x + target_image.sprite.xr as u32;
+            let target_y = target_image.image.height() - (y + target_image.sprite.yr as u32) - 1;
+            target_image.image.put_pixel(target_x, target_y, pixel);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packer::pack::{pack_sprites, read_sprite_rect};
+    use crate::test_support::{self, frame, TempDir};
+    use image::{Rgba, RgbaImage};
+    use std::fs;
+
+    /// A sprite with a 3x2 rect in a 6x5 frame, placed at (x, y) in an atlas
+    fn sprite(name: &str, xr: i32, yr: i32, x: i32, y: i32, flipped: bool) -> Sprite {
+        Sprite { x, y, xr, yr, flipped, ..test_support::sprite(name) }
+    }
+
+    #[test]
+    fn replaces_flipped_and_unflipped_targets_in_frame_space() {
+        for (source_flipped, target_flipped) in [(false, false), (true, true), (false, true), (true, false)] {
+            let source_image = SpriteImage { sprite: sprite("Idle-0-1.png", 1, 1, 0, 0, source_flipped), image: frame(7) };
+            let mut target_image = SpriteImage { sprite: sprite("Idle-1-1.png", 2, 3, 0, 0, target_flipped), image: frame(9) };
+            replace_sprite(&source_image, &mut target_image).unwrap();
+
+            assert!(target_image.equals(&source_image).unwrap(), "flipped {} to {}", source_flipped, target_flipped);
+            // Pixels outside the target's rect are untouched
+            assert_eq!(target_image.image.get_pixel(0, 0), Rgba([9, 0, 0, 255]));
+            assert_eq!(target_image.image.get_pixel(5, 4), Rgba([9, 200, 200, 255]));
+        }
+    }
+
+    #[test]
+    fn rejects_transposed_rects() {
+        let source_image = SpriteImage { sprite: sprite("Idle-0-1.png", 1, 1, 0, 0, false), image: frame(7) };
+        let mut target_image = SpriteImage {
+            sprite: Sprite { width: 2, height: 3, ..sprite("Idle-1-1.png", 1, 1, 0, 0, true) },
+            image: frame(9),
+        };
+        assert!(matches!(replace_sprite(&source_image, &mut target_image), Err(PackerError::Geometry(_))));
+        assert_eq!(target_image.image, frame(9));
+    }
+
+    #[test]
+    fn replaced_flipped_and_unflipped_targets_pack_to_the_source_rect() {
+        let sprites_path = TempDir::new("replace");
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+
+        let source_image = SpriteImage { sprite: sprite("Idle-0-1.png", 1, 1, 0, 0, false), image: frame(7) };
+        // A 3x2 rect at (1, 1) and a flipped 2x3 rect at (5, 1) in the atlas
+        let targets = [sprite("Idle-1-1.png", 2, 3, 1, 1, false), sprite("Idle-2-1.png", 0, 0, 5, 1, true)];
+        for target in &targets {
+            let mut target_image = SpriteImage { sprite: target.clone(), image: frame(9) };
+            replace_sprite(&source_image, &mut target_image).unwrap();
+            target_image.image.save(sprites_path.join(&target.path)).unwrap();
+        }
+
+        let blank = DynamicImage::ImageRgba8(RgbaImage::new(8, 8));
+        let atlas = pack_sprites(blank, &targets, &sprites_path, |_| {}, || false).unwrap().unwrap();
+
+        let source_rect = source_image.trim().unwrap().to_image();
+        for target in &targets {
+            assert_eq!(read_sprite_rect(target, &atlas).unwrap(), source_rect, "flipped {}", target.flipped);
+        }
+        // The flipped target takes up its rect turned sideways
+        let flipped_pixels = (5..7).flat_map(|x| (4..7).map(move |y| (x, y)));
+        assert!(flipped_pixels.into_iter().all(|(x, y)| atlas.get_pixel(x, y)[3] == 255));
+    }
+}
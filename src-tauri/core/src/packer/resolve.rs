@@ -0,0 +1,460 @@
+use crate::error::{PackerError, Result};
+use crate::packer::check::check;
+use crate::packer::pack::read_sprite_rect;
+use crate::packer::replace::replace_sprite;
+use crate::packer::{open_sprite, png_data_url, sprite_path};
+use crate::tk2d::cln::Collection;
+use crate::tk2d::compare::CompareMode;
+use crate::tk2d::sprite::{Sprite, SpriteImage};
+use image::{DynamicImage, Rgba, RgbaImage};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How to settle which pixels a group of differing sprites with the same ID should end up with
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ResolveStrategy {
+    /// Copy the sprite whose frame file was modified most recently to the others
+    NewestModified,
+    /// Give each pixel the value most of the sprites have
+    MajorityVote,
+    /// Copy the sprite that was marked as changed to the others
+    Preferred,
+    /// Apply every pixel that only one distinct edit was made to, compared with the collection's original atlas
+    MergeNonConflicting,
+}
+
+/// What resolving a group of differing sprites with the same ID would do
+#[derive(Clone, Debug, Serialize)]
+pub struct ResolutionPlan {
+    #[serde(rename = "collectionName")]
+    pub collection_name: String,
+    pub id: u32,
+    pub strategy: ResolveStrategy,
+    /// The sprite that is copied to the others, for strategies that pick one
+    pub source: Option<Sprite>,
+    /// The frame files that would be overwritten
+    pub targets: Vec<PathBuf>,
+    /// The number of pixels the strategy could not decide on, which keep the first sprite's or the original atlas's value
+    pub conflicts: u32,
+    /// Why the group cannot be resolved with this strategy, if it cannot
+    pub unresolved: Option<String>,
+    /// The trimmed rect every sprite would end up with, as a PNG data URL
+    pub preview: Option<String>,
+    /// The trimmed rect every sprite would end up with
+    #[serde(skip)]
+    pub result: Option<RgbaImage>,
+}
+
+/// Plan how to resolve every group of differing sprites with the same ID in some collections
+/// # Arguments
+/// * `collections` - The collections to resolve the sprites of
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `strategy` - How to resolve each group
+/// * `preferred` - The sprites that win under the `Preferred` strategy
+/// * `mode` - How strictly the sprites must match to not need resolving
+/// # Returns
+/// * `Vec<ResolutionPlan>` - A plan for each group of differing sprites, ordered by collection and ID
+pub fn plan_resolutions(
+    collections: &[Collection],
+    sprites_path: &Path,
+    strategy: ResolveStrategy,
+    preferred: &[Sprite],
+    mode: CompareMode,
+) -> Result<Vec<ResolutionPlan>> {
+    let mut groups: BTreeMap<(String, u32), Vec<Sprite>> = BTreeMap::new();
+    for sprite in check(collections, sprites_path, mode)? {
+        groups.entry((sprite.collection_name.clone(), sprite.id)).or_default().push(sprite);
+    }
+
+    groups
+        .into_values()
+        .map(|group| {
+            let collection = collections
+                .iter()
+                .find(|collection| collection.name == group[0].collection_name)
+                .ok_or_else(|| PackerError::MissingCollection(group[0].collection_name.clone()))?;
+            plan_resolution(&group, collection, sprites_path, strategy, preferred)
+        })
+        .collect()
+}
+
+/// Plan how to resolve a group of differing sprites with the same ID
+/// # Arguments
+/// * `group` - The sprites with the same ID
+/// * `collection` - The collection containing the sprites
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `strategy` - How to resolve the group
+/// * `preferred` - The sprites that win under the `Preferred` strategy
+/// # Returns
+/// * `ResolutionPlan` - What resolving the group would do
+pub fn plan_resolution(
+    group: &[Sprite],
+    collection: &Collection,
+    sprites_path: &Path,
+    strategy: ResolveStrategy,
+    preferred: &[Sprite],
+) -> Result<ResolutionPlan> {
+    let mut images = Vec::new();
+    for sprite in group {
+        let (path, image) = open_sprite(sprite, sprites_path)?;
+        if images.iter().all(|(existing_path, _): &(PathBuf, SpriteImage)| *existing_path != path) {
+            images.push((path, SpriteImage { sprite: sprite.clone(), image }));
+        }
+    }
+
+    let mut plan = ResolutionPlan {
+        collection_name: collection.name.clone(),
+        id: group[0].id,
+        strategy,
+        source: None,
+        targets: Vec::new(),
+        conflicts: 0,
+        unresolved: None,
+        preview: None,
+        result: None,
+    };
+
+    // Every strategy copies one rect over all the others, so they must all be the same size
+    let rects = images
+        .iter()
+        .map(|(_, image)| image.trim().map(|rect| rect.to_image()))
+        .collect::<Result<Vec<RgbaImage>>>()?;
+    if rects.iter().any(|rect| rect.dimensions() != rects[0].dimensions()) {
+        plan.unresolved = Some("The sprites' trimmed rects have different sizes".to_string());
+        return Ok(plan);
+    }
+
+    let source_index = match strategy {
+        ResolveStrategy::NewestModified => Some(newest_index(&images)?),
+        ResolveStrategy::Preferred => {
+            let preferred_indices = images
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, image))| {
+                    preferred.iter().any(|sprite| {
+                        sprite.collection_name == image.sprite.collection_name
+                            && Path::new(&sprite.path) == Path::new(&image.sprite.path)
+                    })
+                })
+                .map(|(index, _)| index)
+                .collect::<Vec<usize>>();
+            match preferred_indices.as_slice() {
+                [index] => Some(*index),
+                [] => {
+                    plan.unresolved = Some("None of the sprites were marked as changed".to_string());
+                    return Ok(plan);
+                }
+                _ => {
+                    plan.unresolved = Some(format!("{} of the sprites were marked as changed", preferred_indices.len()));
+                    return Ok(plan);
+                }
+            }
+        }
+        ResolveStrategy::MajorityVote | ResolveStrategy::MergeNonConflicting => None,
+    };
+
+    let result = match source_index {
+        Some(index) => {
+            plan.source = Some(images[index].1.sprite.clone());
+            rects[index].clone()
+        }
+        None => {
+            let base = if strategy == ResolveStrategy::MergeNonConflicting {
+                let atlas = image::open(&collection.path).map_err(|e| PackerError::image(&collection.path, e))?;
+                Some(read_sprite_rect(&images[0].1.sprite, &atlas)?)
+            } else {
+                None
+            };
+            let (result, conflicts) = combine(&rects, base.as_ref());
+            plan.conflicts = conflicts;
+            result
+        }
+    };
+
+    plan.targets = images
+        .iter()
+        .zip(&rects)
+        .filter(|(_, rect)| **rect != result)
+        .map(|((path, _), _)| path.clone())
+        .collect();
+    plan.preview = Some(png_data_url(&DynamicImage::ImageRgba8(result.clone()), &images[0].0)?);
+    plan.result = Some(result);
+
+    Ok(plan)
+}
+
+/// Write the result of a resolution plan to every sprite in its group
+/// # Arguments
+/// * `plan` - The plan to apply
+/// * `collection` - The collection containing the sprites
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `Vec<PathBuf>` - The paths of the frame files that were overwritten
+pub fn apply_resolution(plan: &ResolutionPlan, collection: &Collection, sprites_path: &Path) -> Result<Vec<PathBuf>> {
+    let result = match (&plan.result, &plan.unresolved) {
+        (Some(result), None) => result,
+        (_, reason) => {
+            return Err(PackerError::Unresolved(format!(
+                "Sprite {} in collection {}: {}",
+                plan.id,
+                plan.collection_name,
+                reason.as_deref().unwrap_or("the plan has no result"),
+            )))
+        }
+    };
+
+    let group_sprite = match &plan.source {
+        Some(source) => source.clone(),
+        None => collection
+            .sprites
+            .iter()
+            .find(|sprite| sprite.id == plan.id)
+            .cloned()
+            .ok_or_else(|| PackerError::MissingSprite(format!("{} in collection {}", plan.id, plan.collection_name)))?,
+    };
+    // A sprite whose trimmed rect is the whole result image, so that replace_sprite copies all of it
+    let source_image = SpriteImage {
+        sprite: Sprite {
+            xr: 0,
+            yr: 0,
+            width: result.width() as i32,
+            height: result.height() as i32,
+            flipped: false,
+            ..group_sprite
+        },
+        image: DynamicImage::ImageRgba8(result.clone()),
+    };
+
+    // Replace every target in memory first, so that a target the result does not fit leaves every frame file untouched
+    let mut replaced_images: Vec<(PathBuf, SpriteImage)> = Vec::new();
+    for sprite in collection.sprites.iter().filter(|sprite| sprite.id == plan.id) {
+        let path = sprite_path(sprite, sprites_path)?;
+        if !plan.targets.contains(&path) || replaced_images.iter().any(|(existing_path, _)| *existing_path == path) {
+            continue;
+        }
+
+        let (_, image) = open_sprite(sprite, sprites_path)?;
+        let mut target_image = SpriteImage {
+            sprite: sprite.clone(),
+            image,
+        };
+        replace_sprite(&source_image, &mut target_image)?;
+        replaced_images.push((path, target_image));
+    }
+
+    let mut replaced_paths = Vec::new();
+    for (path, target_image) in replaced_images {
+        target_image.image.save(&path).map_err(|e| PackerError::image(&path, e))?;
+        info!("Resolved sprite at path {:?} with strategy {:?}.", path.display(), plan.strategy);
+        replaced_paths.push(path);
+    }
+
+    Ok(replaced_paths)
+}
+
+/// Get the index of the sprite whose frame file was modified most recently
+fn newest_index(images: &[(PathBuf, SpriteImage)]) -> Result<usize> {
+    let mut newest = (0, SystemTime::UNIX_EPOCH);
+    for (index, (path, _)) in images.iter().enumerate() {
+        let metadata = fs::metadata(path).map_err(|e| PackerError::io(path, e))?;
+        let modified = metadata.modified().map_err(|e| PackerError::io(path, e))?;
+        if modified > newest.1 {
+            newest = (index, modified);
+        }
+    }
+
+    Ok(newest.0)
+}
+
+/// Combine same-sized rects pixel by pixel
+/// # Arguments
+/// * `rects` - The rects to combine
+/// * `base` - The rect the others were edited from, to merge edits onto, or `None` to take a majority vote
+/// # Returns
+/// * `(RgbaImage, u32)` - The combined rect, and the number of pixels that could not be decided
+fn combine(rects: &[RgbaImage], base: Option<&RgbaImage>) -> (RgbaImage, u32) {
+    let (width, height) = rects[0].dimensions();
+    let mut result = RgbaImage::new(width, height);
+    let mut conflicts = 0;
+    for (x, y, pixel) in result.enumerate_pixels_mut() {
+        let values = rects.iter().map(|rect| *rect.get_pixel(x, y)).collect::<Vec<Rgba<u8>>>();
+        let (value, conflict) = match base {
+            Some(base) => merge_pixel(&values, base.get_pixel(x, y)),
+            None => vote_pixel(&values),
+        };
+        *pixel = value;
+        if conflict {
+            conflicts += 1;
+        }
+    }
+
+    (result, conflicts)
+}
+
+/// Take the value most of the pixels have, preferring the earliest on a tie
+/// # Returns
+/// * `(Rgba<u8>, bool)` - The chosen value, and whether there was a tie
+fn vote_pixel(values: &[Rgba<u8>]) -> (Rgba<u8>, bool) {
+    let mut counts: Vec<(Rgba<u8>, usize)> = Vec::new();
+    for value in values {
+        match counts.iter_mut().find(|(existing, _)| existing == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*value, 1)),
+        }
+    }
+
+    let max_count = counts.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let mut winners = counts.iter().filter(|(_, count)| *count == max_count);
+    let winner = winners.next().map_or(values[0], |(value, _)| *value);
+    (winner, winners.next().is_some())
+}
+
+/// Apply the edits made to a base pixel if they all agree, otherwise keep the base pixel
+/// # Returns
+/// * `(Rgba<u8>, bool)` - The merged value, and whether the edits conflicted
+fn merge_pixel(values: &[Rgba<u8>], base: &Rgba<u8>) -> (Rgba<u8>, bool) {
+    let mut edits = values.iter().filter(|value| *value != base);
+    match edits.next() {
+        None => (*base, false),
+        Some(edit) if edits.all(|other| other == edit) => (*edit, false),
+        Some(_) => (*base, true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    #[test]
+    fn vote_takes_the_most_common_value_and_reports_ties() {
+        assert_eq!(vote_pixel(&[RED]), (RED, false));
+        assert_eq!(vote_pixel(&[GREEN, RED, GREEN]), (GREEN, false));
+        // On a tie the value seen first wins
+        assert_eq!(vote_pixel(&[BLUE, RED, RED, BLUE]), (BLUE, true));
+        assert_eq!(vote_pixel(&[RED, GREEN, BLUE]), (RED, true));
+    }
+
+    #[test]
+    fn merge_applies_agreeing_edits_and_keeps_the_base_on_conflicts() {
+        assert_eq!(merge_pixel(&[RED, RED], &RED), (RED, false));
+        assert_eq!(merge_pixel(&[RED, GREEN, RED], &RED), (GREEN, false));
+        assert_eq!(merge_pixel(&[GREEN, GREEN, RED], &RED), (GREEN, false));
+        assert_eq!(merge_pixel(&[GREEN, BLUE, RED], &RED), (RED, true));
+    }
+
+    #[test]
+    fn combine_counts_undecided_pixels() {
+        let rects = [
+            RgbaImage::from_raw(3, 1, [RED, GREEN, BLUE].iter().flat_map(|pixel| pixel.0).collect()).unwrap(),
+            RgbaImage::from_raw(3, 1, [RED, BLUE, GREEN].iter().flat_map(|pixel| pixel.0).collect()).unwrap(),
+        ];
+        let (voted, ties) = combine(&rects, None);
+        assert_eq!((voted.pixels().copied().collect::<Vec<Rgba<u8>>>(), ties), (vec![RED, GREEN, BLUE], 2));
+
+        let base = RgbaImage::from_raw(3, 1, [GREEN, RED, GREEN].iter().flat_map(|pixel| pixel.0).collect()).unwrap();
+        let (merged, conflicts) = combine(&rects, Some(&base));
+        assert_eq!((merged.pixels().copied().collect::<Vec<Rgba<u8>>>(), conflicts), (vec![RED, RED, BLUE], 1));
+    }
+
+    /// A sprite whose rect is the whole of its `width`x2 frame
+    fn sprite(name: &str, width: i32) -> Sprite {
+        Sprite { xr: 0, yr: 0, width, ..test_support::sprite(name) }
+    }
+
+    /// A collection of sprites with the same ID, whose frames are written in a single color each
+    fn collection(sprites_path: &Path, frames: &[(&str, i32, Rgba<u8>)]) -> Collection {
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+        let sprites = frames
+            .iter()
+            .map(|&(name, width, color)| {
+                let sprite = sprite(name, width);
+                RgbaImage::from_pixel(width as u32, 2, color).save(sprites_path.join(&sprite.path)).unwrap();
+                sprite
+            })
+            .collect();
+        test_support::collection(sprites_path, sprites)
+    }
+
+    #[test]
+    fn preferred_needs_exactly_one_marked_sprite() {
+        let sprites_path = TempDir::new("resolve");
+        let collection = collection(&sprites_path, &[("Idle-0-1.png", 2, RED), ("Idle-1-1.png", 2, GREEN)]);
+        let sprites = &collection.sprites;
+
+        let plan = |preferred: &[Sprite]| plan_resolution(sprites, &collection, &sprites_path, ResolveStrategy::Preferred, preferred).unwrap();
+        let (none, both, second) = (plan(&[]), plan(sprites), plan(&sprites[1..]));
+
+        assert_eq!(none.unresolved.as_deref(), Some("None of the sprites were marked as changed"));
+        assert_eq!(both.unresolved.as_deref(), Some("2 of the sprites were marked as changed"));
+        for plan in [&none, &both] {
+            assert!(plan.source.is_none() && plan.targets.is_empty() && plan.result.is_none());
+        }
+
+        assert!(second.unresolved.is_none());
+        assert_eq!(second.source.as_ref(), Some(&sprites[1]));
+        assert_eq!(second.targets, vec![sprites_path.join(&sprites[0].path)]);
+        assert_eq!(second.result, Some(RgbaImage::from_pixel(2, 2, GREEN)));
+    }
+
+    #[test]
+    fn every_strategy_needs_rects_of_the_same_size() {
+        let sprites_path = TempDir::new("resolve-sizes");
+        let collection = collection(&sprites_path, &[("Idle-0-1.png", 2, RED), ("Idle-1-1.png", 3, GREEN)]);
+        let sprites = &collection.sprites;
+
+        let strategies = [
+            ResolveStrategy::NewestModified,
+            ResolveStrategy::MajorityVote,
+            ResolveStrategy::Preferred,
+            ResolveStrategy::MergeNonConflicting,
+        ];
+        let plans = strategies
+            .iter()
+            .map(|&strategy| plan_resolution(sprites, &collection, &sprites_path, strategy, &sprites[..1]).unwrap())
+            .collect::<Vec<ResolutionPlan>>();
+
+        for plan in plans {
+            assert_eq!(plan.unresolved.as_deref(), Some("The sprites' trimmed rects have different sizes"), "{:?}", plan.strategy);
+            assert!(plan.targets.is_empty() && plan.result.is_none());
+        }
+    }
+
+    #[test]
+    fn applying_writes_nothing_unless_every_target_fits() {
+        let sprites_path = TempDir::new("resolve-apply");
+        let collection = collection(&sprites_path, &[("Idle-0-1.png", 2, RED), ("Idle-1-1.png", 2, GREEN), ("Idle-2-1.png", 3, GREEN)]);
+        let paths = collection.sprites.iter().map(|sprite| sprites_path.join(&sprite.path)).collect::<Vec<PathBuf>>();
+        let mut plan = ResolutionPlan {
+            collection_name: collection.name.clone(),
+            id: 1,
+            strategy: ResolveStrategy::MajorityVote,
+            source: None,
+            targets: paths[1..].to_vec(),
+            conflicts: 0,
+            unresolved: None,
+            preview: None,
+            result: Some(RgbaImage::from_pixel(2, 2, BLUE)),
+        };
+
+        let misfit = apply_resolution(&plan, &collection, &sprites_path);
+        let untouched = image::open(&paths[1]).unwrap().to_rgba8();
+        plan.targets.pop();
+        let replaced_paths = apply_resolution(&plan, &collection, &sprites_path).unwrap();
+        let frames = paths[..2].iter().map(|path| image::open(path).unwrap().to_rgba8()).collect::<Vec<RgbaImage>>();
+
+        assert!(matches!(misfit, Err(PackerError::Geometry(_))));
+        assert_eq!(untouched, RgbaImage::from_pixel(2, 2, GREEN));
+        assert_eq!(replaced_paths, vec![paths[1].clone()]);
+        assert_eq!(frames, vec![RgbaImage::from_pixel(2, 2, RED), RgbaImage::from_pixel(2, 2, BLUE)]);
+    }
+}
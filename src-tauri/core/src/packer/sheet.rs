@@ -0,0 +1,232 @@
+use crate::error::{PackerError, Result};
+use crate::packer::open_sprite;
+use crate::tk2d::clip::Clip;
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How the cells of a sprite sheet are arranged
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SheetLayout {
+    /// A single row of cells
+    Strip,
+    /// Rows of cells, left to right and then top to bottom
+    Grid {
+        /// The number of cells in each row, or 0 for as close to a square as possible
+        columns: u32,
+    },
+}
+
+impl Default for SheetLayout {
+    fn default() -> Self {
+        SheetLayout::Strip
+    }
+}
+
+impl SheetLayout {
+    /// Get the number of columns and rows of cells for a number of frames
+    pub fn dimensions(&self, num_frames: u32) -> (u32, u32) {
+        let columns = match *self {
+            SheetLayout::Strip => num_frames,
+            SheetLayout::Grid { columns: 0 } => (num_frames as f64).sqrt().ceil() as u32,
+            SheetLayout::Grid { columns } => columns.min(num_frames),
+        }
+        .max(1);
+        (columns, ((num_frames + columns - 1) / columns).max(1))
+    }
+}
+
+/// A rect in a sprite sheet, from the top left corner like in image editors
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SheetRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where a frame of a clip is in a sprite sheet
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SheetFrame {
+    /// The index of the frame in the clip
+    pub index: usize,
+    pub name: String,
+    /// The frame file the cell was drawn from
+    pub path: String,
+    /// The frame's cell
+    pub cell: SheetRect,
+    /// The whole frame, centred in its cell
+    pub frame: SheetRect,
+    /// The frame's sprite rect, i.e. the part of the frame that is packed and shown in-game
+    #[serde(rename = "spriteRect")]
+    pub sprite_rect: SheetRect,
+}
+
+/// The metadata of a clip composed into a sprite sheet
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SheetFile {
+    #[serde(rename = "clipName")]
+    pub clip_name: String,
+    /// The file name of the sprite sheet
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    /// The size of every cell, which fits the largest frame
+    #[serde(rename = "cellWidth")]
+    pub cell_width: u32,
+    #[serde(rename = "cellHeight")]
+    pub cell_height: u32,
+    pub columns: u32,
+    pub rows: u32,
+    pub fps: f32,
+    /// The index of the first frame that plays again when the clip loops
+    #[serde(rename = "loopStart")]
+    pub loop_start: u32,
+    /// The clip's frames in the order they play
+    pub frames: Vec<SheetFrame>,
+}
+
+impl SheetFile {
+    /// Get the path of the metadata belonging to a sprite sheet, e.g. `Idle.json` for `Idle.png`
+    pub fn path_for(image_path: &Path) -> PathBuf {
+        image_path.with_extension("json")
+    }
+}
+
+/// Compose the frames of a clip into a sprite sheet with uniform cells
+/// # Arguments
+/// * `clip` - The clip to compose
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `layout` - How to arrange the cells
+/// * `image_name` - The file name the sprite sheet will be written to, recorded in the metadata
+/// # Returns
+/// * `(RgbaImage, SheetFile)` - The sprite sheet and its metadata
+pub fn compose_sheet(clip: &Clip, sprites_path: &Path, layout: &SheetLayout, image_name: &str) -> Result<(RgbaImage, SheetFile)> {
+    if clip.frames.is_empty() {
+        return Err(PackerError::Geometry(format!("Clip {} has no frames", clip.name)));
+    }
+
+    let frames = clip
+        .frames
+        .iter()
+        .map(|sprite| open_sprite(sprite, sprites_path).map(|(_, frame)| frame.to_rgba8()))
+        .collect::<Result<Vec<RgbaImage>>>()?;
+    let cell_width = frames.iter().map(RgbaImage::width).max().unwrap_or(1).max(1);
+    let cell_height = frames.iter().map(RgbaImage::height).max().unwrap_or(1).max(1);
+    let (columns, rows) = layout.dimensions(frames.len() as u32);
+
+    let mut sheet = RgbaImage::new(columns * cell_width, rows * cell_height);
+    let mut sheet_frames = Vec::new();
+    for (index, (sprite, frame)) in clip.frames.iter().zip(&frames).enumerate() {
+        let cell = SheetRect {
+            x: index as u32 % columns * cell_width,
+            y: index as u32 / columns * cell_height,
+            width: cell_width,
+            height: cell_height,
+        };
+        let (frame_x, frame_y) = (cell.x + (cell_width - frame.width()) / 2, cell.y + (cell_height - frame.height()) / 2);
+        imageops::replace(&mut sheet, frame, frame_x as i64, frame_y as i64);
+
+        let (width, height) = (frame.width() as i32, frame.height() as i32);
+        let left = sprite.xr.clamp(0, width);
+        let right = (sprite.xr + sprite.width).clamp(0, width);
+        let top = (height - (sprite.yr + sprite.height)).clamp(0, height);
+        let bottom = (height - sprite.yr).clamp(0, height);
+        sheet_frames.push(SheetFrame {
+            index,
+            name: sprite.name.clone(),
+            path: sprite.path.clone(),
+            cell,
+            frame: SheetRect { x: frame_x, y: frame_y, width: frame.width(), height: frame.height() },
+            sprite_rect: SheetRect {
+                x: frame_x + left as u32,
+                y: frame_y + top as u32,
+                width: (right - left) as u32,
+                height: (bottom - top) as u32,
+            },
+        });
+    }
+
+    let metadata = SheetFile {
+        clip_name: clip.name.clone(),
+        image: image_name.to_string(),
+        width: sheet.width(),
+        height: sheet.height(),
+        cell_width,
+        cell_height,
+        columns,
+        rows,
+        fps: clip.fps,
+        loop_start: clip.loop_start,
+        frames: sheet_frames,
+    };
+    Ok((sheet, metadata))
+}
+
+/// Compose the frames of a clip into a sprite sheet and write it with its metadata next to it
+/// # Arguments
+/// * `clip` - The clip to compose
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `layout` - How to arrange the cells
+/// * `image_path` - The path to write the sprite sheet to
+/// # Returns
+/// * `SheetFile` - The metadata, which was written to `SheetFile::path_for(image_path)`
+pub fn write_sheet(clip: &Clip, sprites_path: &Path, layout: &SheetLayout, image_path: &Path) -> Result<SheetFile> {
+    let image_name = image_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let (sheet, metadata) = compose_sheet(clip, sprites_path, layout, &image_name)?;
+    sheet.save(image_path).map_err(|e| PackerError::image(image_path, e))?;
+
+    let metadata_path = SheetFile::path_for(image_path);
+    let text = serde_json::to_string_pretty(&metadata).map_err(|e| PackerError::json(&metadata_path, e))?;
+    fs::write(&metadata_path, text).map_err(|e| PackerError::io(&metadata_path, e))?;
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use crate::packer::load::load_clip;
+    use crate::tk2d::info::SpriteInfo;
+    use image::Rgba;
+
+    #[test]
+    fn cells_follow_frame_numbers_in_file_names() {
+        let sprites_path = TempDir::new("sheet");
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+
+        // Twelve 2x2 frames, each filled with its frame number, listed backwards in the sprite info so that
+        // neither the sprite info nor sorting the names as text gives the order they play in
+        let paths = (0..12).rev().map(|frame| format!("Knight/Idle/Idle-{}-{}.png", frame, frame + 1)).collect::<Vec<String>>();
+        for (path, frame) in paths.iter().zip((0..12).rev()) {
+            RgbaImage::from_pixel(2, 2, Rgba([frame * 20, 0, 0, 255])).save(sprites_path.join(path)).unwrap();
+        }
+        let sprite_info = SpriteInfo {
+            id: (1..=12).rev().collect(),
+            x: vec![0; 12],
+            y: vec![0; 12],
+            xr: vec![0; 12],
+            yr: vec![0; 12],
+            width: vec![2; 12],
+            height: vec![2; 12],
+            collection_name: vec!["Knight Cln".to_string(); 12],
+            path: paths,
+            flipped: vec![false; 12],
+        };
+
+        let clip = load_clip(&sprites_path.join("Knight/Idle"), &sprite_info).unwrap();
+        let (sheet, metadata) = compose_sheet(&clip, &sprites_path, &SheetLayout::Grid { columns: 4 }, "Idle.png").unwrap();
+
+        assert_eq!((metadata.columns, metadata.rows), (4, 3));
+        for (index, frame) in metadata.frames.iter().enumerate() {
+            assert_eq!(frame.index, index);
+            assert_eq!(frame.name, format!("Idle-{}-{}.png", index, index + 1));
+            assert_eq!(frame.cell, SheetRect { x: index as u32 % 4 * 2, y: index as u32 / 4 * 2, width: 2, height: 2 });
+            assert_eq!(*sheet.get_pixel(frame.cell.x, frame.cell.y), Rgba([index as u8 * 20, 0, 0, 255]));
+        }
+    }
+}
@@ -0,0 +1,729 @@
+use crate::error::{PackerError, Result};
+use image::{DynamicImage, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A compression format of 4x4 pixel blocks that GPUs, and so Unity, can sample directly
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockCompression {
+    /// DXT5, with 5:6:5 colors and separately interpolated alpha, supported by every desktop GPU
+    Bc3,
+    /// Higher quality than BC3 in the same size, supported by DirectX 11 class GPUs
+    Bc7,
+}
+
+/// The compressed textures to write next to each packed atlas
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureOptions {
+    /// The compression of a `.dds` texture to write, or `None` to not write one
+    pub dds: Option<BlockCompression>,
+    /// The compression of a `.ktx2` texture to write, or `None` to not write one
+    pub ktx2: Option<BlockCompression>,
+    /// Whether to generate mipmaps down to 1x1 in the textures
+    pub mipmaps: bool,
+    /// Whether to mark the textures as holding linear colors instead of sRGB colors
+    pub linear: bool,
+}
+
+impl TextureOptions {
+    /// Whether no textures are written
+    pub fn is_empty(&self) -> bool {
+        self.dds.is_none() && self.ktx2.is_none()
+    }
+}
+
+/// An image compressed into 4x4 pixel blocks, with its mipmaps
+#[derive(Clone, Debug)]
+pub struct CompressedTexture {
+    pub compression: BlockCompression,
+    pub width: u32,
+    pub height: u32,
+    /// The blocks of each mip level, row by row from the top left, starting with the full size image
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// Write the compressed textures of a packed atlas next to it, e.g. `Knight Cln.dds` for `Knight Cln.png`
+/// # Arguments
+/// * `atlas` - The packed atlas
+/// * `atlas_path` - The path the atlas was written to
+/// * `options` - Which textures to write
+/// # Returns
+/// * `Vec<PathBuf>` - The paths the textures were written to
+pub fn write_textures(atlas: &DynamicImage, atlas_path: &Path, options: &TextureOptions) -> Result<Vec<PathBuf>> {
+    if options.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let levels = mipmaps(atlas.to_rgba8(), options.mipmaps);
+    let mut textures: Vec<CompressedTexture> = Vec::new();
+    let mut paths = Vec::new();
+    for (compression, extension) in [(options.dds, "dds"), (options.ktx2, "ktx2")] {
+        let compression = match compression {
+            Some(compression) => compression,
+            None => continue,
+        };
+        if !textures.iter().any(|texture| texture.compression == compression) {
+            textures.push(compress_levels(&levels, compression));
+        }
+        let texture = textures
+            .iter()
+            .find(|texture| texture.compression == compression)
+            .expect("Texture was just compressed");
+
+        let srgb = !options.linear;
+        let bytes = if extension == "dds" { dds_bytes(texture, srgb) } else { ktx2_bytes(texture, srgb) };
+        let path = atlas_path.with_extension(extension);
+        fs::write(&path, bytes).map_err(|e| PackerError::io(&path, e))?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Halve an image repeatedly down to 1x1
+///
+/// Each pixel of a level averages 2x2 pixels of the level above, weighting colors by their alpha so that the colors
+/// of transparent pixels don't darken the edges of sprites.
+/// # Arguments
+/// * `image` - The full size image
+/// * `all` - Whether to generate every level, or only return the full size image
+/// # Returns
+/// * `Vec<RgbaImage>` - The levels, starting with the full size image
+pub fn mipmaps(image: RgbaImage, all: bool) -> Vec<RgbaImage> {
+    let mut levels = vec![image];
+    if !all {
+        return levels;
+    }
+
+    loop {
+        let previous = levels.last().expect("There is always a level");
+        let (width, height) = previous.dimensions();
+        if width == 1 && height == 1 {
+            return levels;
+        }
+
+        let level = RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+            let (mut color, mut alpha, mut plain) = ([0u32; 3], 0u32, [0u32; 3]);
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let pixel = previous.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1)).0;
+                for channel in 0..3 {
+                    color[channel] += pixel[channel] as u32 * pixel[3] as u32;
+                    plain[channel] += pixel[channel] as u32;
+                }
+                alpha += pixel[3] as u32;
+            }
+            let channel = |channel: usize| match alpha {
+                0 => ((plain[channel] + 2) / 4) as u8,
+                _ => ((color[channel] + alpha / 2) / alpha) as u8,
+            };
+            image::Rgba([channel(0), channel(1), channel(2), ((alpha + 2) / 4) as u8])
+        });
+        levels.push(level);
+    }
+}
+
+/// Compress every mip level of an image
+/// # Arguments
+/// * `levels` - The mip levels, starting with the full size image
+/// * `compression` - The block compression to use
+/// # Returns
+/// * `CompressedTexture` - The compressed levels
+pub fn compress_levels(levels: &[RgbaImage], compression: BlockCompression) -> CompressedTexture {
+    CompressedTexture {
+        compression,
+        width: levels[0].width(),
+        height: levels[0].height(),
+        levels: levels.iter().map(|level| compress(level, compression)).collect(),
+    }
+}
+
+/// Compress an image into 4x4 pixel blocks, repeating its last row and column to fill partial blocks
+/// # Arguments
+/// * `image` - The image to compress
+/// * `compression` - The block compression to use
+/// # Returns
+/// * `Vec<u8>` - 16 bytes for each block, row by row from the top left
+pub fn compress(image: &RgbaImage, compression: BlockCompression) -> Vec<u8> {
+    let (blocks_x, blocks_y) = ((image.width() + 3) / 4, (image.height() + 3) / 4);
+    (0..blocks_y)
+        .into_par_iter()
+        .flat_map_iter(|block_y| {
+            (0..blocks_x).flat_map(move |block_x| {
+                let block = block_pixels(image, block_x, block_y);
+                match compression {
+                    BlockCompression::Bc3 => encode_bc3(&block),
+                    BlockCompression::Bc7 => encode_bc7(&block),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Get the 16 pixels of a block, row by row, clamping coordinates to the image
+fn block_pixels(image: &RgbaImage, block_x: u32, block_y: u32) -> [[u8; 4]; 16] {
+    let mut block = [[0; 4]; 16];
+    for (i, pixel) in block.iter_mut().enumerate() {
+        let x = (block_x * 4 + i as u32 % 4).min(image.width() - 1);
+        let y = (block_y * 4 + i as u32 / 4).min(image.height() - 1);
+        *pixel = image.get_pixel(x, y).0;
+    }
+
+    block
+}
+
+/// Find the two ends of the line that best fits some colors, along the axis they vary the most on
+/// # Arguments
+/// * `colors` - The colors, with values from 0 to 255
+/// # Returns
+/// * `([f32; N], [f32; N])` - The lower and upper ends, clamped from 0 to 255
+fn principal_endpoints<const N: usize>(colors: &[[f32; N]]) -> ([f32; N], [f32; N]) {
+    let mut mean = [0.0; N];
+    for color in colors {
+        for channel in 0..N {
+            mean[channel] += color[channel] / colors.len() as f32;
+        }
+    }
+    let mut covariance = [[0.0; N]; N];
+    for color in colors {
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value += (color[i] - mean[i]) * (color[j] - mean[j]);
+            }
+        }
+    }
+
+    // Power iteration converges on the axis of greatest variance
+    let mut axis = [1.0; N];
+    for _ in 0..8 {
+        let mut next = [0.0; N];
+        for (value, row) in next.iter_mut().zip(&covariance) {
+            *value = row.iter().zip(&axis).map(|(a, b)| a * b).sum();
+        }
+        let length = next.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if length < f32::EPSILON {
+            return (mean, mean);
+        }
+        axis = next.map(|value| value / length);
+    }
+
+    let (mut low, mut high) = (f32::MAX, f32::MIN);
+    for color in colors {
+        let t = (0..N).map(|channel| (color[channel] - mean[channel]) * axis[channel]).sum::<f32>();
+        low = low.min(t);
+        high = high.max(t);
+    }
+    let point = |t: f32| {
+        let mut point = [0.0; N];
+        for channel in 0..N {
+            point[channel] = (mean[channel] + axis[channel] * t).clamp(0.0, 255.0);
+        }
+        point
+    };
+
+    (point(low), point(high))
+}
+
+/// Get the index of the palette entry closest to a color
+fn nearest<const N: usize>(color: [i32; N], palette: &[[i32; N]]) -> usize {
+    let distance = |entry: &[i32; N]| (0..N).map(|channel| (color[channel] - entry[channel]).pow(2)).sum::<i32>();
+    (0..palette.len()).min_by_key(|&index| distance(&palette[index])).unwrap_or(0)
+}
+
+/// Compress a block into BC3, an 8 byte alpha block followed by an 8 byte color block
+fn encode_bc3(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let mut bytes = [0; 16];
+
+    let (alpha_max, alpha_min) = (
+        block.iter().map(|pixel| pixel[3]).max().unwrap_or(0),
+        block.iter().map(|pixel| pixel[3]).min().unwrap_or(0),
+    );
+    bytes[0] = alpha_max;
+    bytes[1] = alpha_min;
+    if alpha_max > alpha_min {
+        // With the first alpha greater than the second, indices 2 to 7 interpolate between them
+        let mut palette = [[0i32; 1]; 8];
+        palette[0] = [alpha_max as i32];
+        palette[1] = [alpha_min as i32];
+        for i in 1..7 {
+            palette[i + 1] = [((7 - i as i32) * alpha_max as i32 + i as i32 * alpha_min as i32 + 3) / 7];
+        }
+        let mut bits = 0u64;
+        for (i, pixel) in block.iter().enumerate() {
+            bits |= (nearest([pixel[3] as i32], &palette) as u64) << (3 * i);
+        }
+        bytes[2..8].copy_from_slice(&bits.to_le_bytes()[..6]);
+    }
+
+    // The colors of fully transparent pixels are never seen, so they don't need to fit
+    let mut colors = block
+        .iter()
+        .filter(|pixel| pixel[3] > 0)
+        .map(|pixel| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32])
+        .collect::<Vec<[f32; 3]>>();
+    if colors.is_empty() {
+        colors = block.iter().map(|pixel| [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]).collect();
+    }
+    let (low, high) = principal_endpoints(&colors);
+    let (mut color0, mut color1) = (to_rgb565(high), to_rgb565(low));
+    if color0 < color1 {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+    bytes[8..10].copy_from_slice(&color0.to_le_bytes());
+    bytes[10..12].copy_from_slice(&color1.to_le_bytes());
+    if color0 != color1 {
+        let (end0, end1) = (from_rgb565(color0), from_rgb565(color1));
+        let mix = |a: [i32; 3], b: [i32; 3]| [(2 * a[0] + b[0]) / 3, (2 * a[1] + b[1]) / 3, (2 * a[2] + b[2]) / 3];
+        let palette = [end0, end1, mix(end0, end1), mix(end1, end0)];
+        let mut bits = 0u32;
+        for (i, pixel) in block.iter().enumerate() {
+            let color = [pixel[0] as i32, pixel[1] as i32, pixel[2] as i32];
+            bits |= (nearest(color, &palette) as u32) << (2 * i);
+        }
+        bytes[12..16].copy_from_slice(&bits.to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Round a color to 5 bits of red, 6 of green and 5 of blue
+fn to_rgb565(color: [f32; 3]) -> u16 {
+    let scale = |value: f32, max: f32| (value * max / 255.0).round() as u16;
+    (scale(color[0], 31.0) << 11) | (scale(color[1], 63.0) << 5) | scale(color[2], 31.0)
+}
+
+/// Expand a 5:6:5 color to 8 bits per channel the way GPUs do
+fn from_rgb565(color: u16) -> [i32; 3] {
+    let (red, green, blue) = ((color >> 11) as i32 & 31, (color >> 5) as i32 & 63, color as i32 & 31);
+    [(red << 3) | (red >> 2), (green << 2) | (green >> 4), (blue << 3) | (blue >> 2)]
+}
+
+/// The weights BC7 interpolates between endpoints with for 4-bit indices, out of 64
+const BC7_WEIGHTS: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Compress a block into BC7 mode 6, which has one pair of RGBA endpoints and 16 levels between them
+fn encode_bc7(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let colors = block.map(|pixel| pixel.map(|value| value as f32));
+    let (low, high) = principal_endpoints(&colors);
+    let mut endpoints = [quantize_bc7_endpoint(low), quantize_bc7_endpoint(high)];
+
+    let palette = |endpoints: &[([i32; 4], i32); 2]| {
+        let (end0, end1) = (expand_bc7_endpoint(endpoints[0]), expand_bc7_endpoint(endpoints[1]));
+        BC7_WEIGHTS.map(|weight| {
+            let mut color = [0; 4];
+            for channel in 0..4 {
+                color[channel] = ((64 - weight) * end0[channel] + weight * end1[channel] + 32) >> 6;
+            }
+            color
+        })
+    };
+    let entries = palette(&endpoints);
+    let mut indices = block.map(|pixel| nearest(pixel.map(|value| value as i32), &entries));
+
+    // The first pixel's index only has 3 bits, so its top bit must be 0
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut writer = BitWriter::default();
+    writer.write(1 << 6, 7);
+    for channel in 0..4 {
+        writer.write(endpoints[0].0[channel] as u128, 7);
+        writer.write(endpoints[1].0[channel] as u128, 7);
+    }
+    writer.write(endpoints[0].1 as u128, 1);
+    writer.write(endpoints[1].1 as u128, 1);
+    for (i, index) in indices.iter().enumerate() {
+        writer.write(*index as u128, if i == 0 { 3 } else { 4 });
+    }
+
+    writer.bits.to_le_bytes()
+}
+
+/// Round an endpoint to 7 bits per channel and the shared lowest bit that fits it best
+fn quantize_bc7_endpoint(color: [f32; 4]) -> ([i32; 4], i32) {
+    (0..2)
+        .map(|p_bit| {
+            let values = color.map(|value| (((value - p_bit as f32) / 2.0).round() as i32).clamp(0, 127));
+            let error = (0..4)
+                .map(|channel| ((values[channel] << 1 | p_bit) as f32 - color[channel]).powi(2))
+                .sum::<f32>();
+            ((values, p_bit), error)
+        })
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(endpoint, _)| endpoint)
+        .expect("There are two p-bits")
+}
+
+/// Expand a 7-bit endpoint and its p-bit to 8 bits per channel
+fn expand_bc7_endpoint((values, p_bit): ([i32; 4], i32)) -> [i32; 4] {
+    values.map(|value| value << 1 | p_bit)
+}
+
+/// Writes bits into a 128-bit block from the lowest bit up
+#[derive(Default)]
+struct BitWriter {
+    bits: u128,
+    position: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u128, count: u32) {
+        self.bits |= (value & ((1 << count) - 1)) << self.position;
+        self.position += count;
+    }
+}
+
+/// Build a DDS file with the DX10 header, which unlike the legacy `DXT5` header can mark colors as sRGB
+/// # Arguments
+/// * `texture` - The compressed texture
+/// * `srgb` - Whether the colors are sRGB rather than linear
+/// # Returns
+/// * `Vec<u8>` - The contents of the file
+pub fn dds_bytes(texture: &CompressedTexture, srgb: bool) -> Vec<u8> {
+    const CAPS: u32 = 0x1;
+    const HEIGHT: u32 = 0x2;
+    const WIDTH: u32 = 0x4;
+    const PIXEL_FORMAT: u32 = 0x1000;
+    const MIPMAP_COUNT: u32 = 0x20000;
+    const LINEAR_SIZE: u32 = 0x80000;
+    const FOURCC: u32 = 0x4;
+    const CAPS_COMPLEX: u32 = 0x8;
+    const CAPS_TEXTURE: u32 = 0x1000;
+    const CAPS_MIPMAP: u32 = 0x400000;
+    const DXGI_FORMAT_BC3_UNORM: u32 = 77;
+    const DXGI_FORMAT_BC3_UNORM_SRGB: u32 = 78;
+    const DXGI_FORMAT_BC7_UNORM: u32 = 98;
+    const DXGI_FORMAT_BC7_UNORM_SRGB: u32 = 99;
+    const DIMENSION_TEXTURE2D: u32 = 3;
+
+    let has_mipmaps = texture.levels.len() > 1;
+    let mut bytes = b"DDS ".to_vec();
+    let header = [
+        124,
+        CAPS | HEIGHT | WIDTH | PIXEL_FORMAT | LINEAR_SIZE | if has_mipmaps { MIPMAP_COUNT } else { 0 },
+        texture.height,
+        texture.width,
+        texture.levels[0].len() as u32,
+        0,
+        texture.levels.len() as u32,
+    ];
+    header.iter().for_each(|value| bytes.extend(value.to_le_bytes()));
+    bytes.extend([0; 11 * 4]);
+
+    bytes.extend(32u32.to_le_bytes());
+    bytes.extend(FOURCC.to_le_bytes());
+    bytes.extend(b"DX10");
+    bytes.extend([0; 5 * 4]);
+
+    let caps = CAPS_TEXTURE | if has_mipmaps { CAPS_COMPLEX | CAPS_MIPMAP } else { 0 };
+    bytes.extend(caps.to_le_bytes());
+    bytes.extend([0; 4 * 4]);
+
+    let dxgi_format = match (texture.compression, srgb) {
+        (BlockCompression::Bc3, false) => DXGI_FORMAT_BC3_UNORM,
+        (BlockCompression::Bc3, true) => DXGI_FORMAT_BC3_UNORM_SRGB,
+        (BlockCompression::Bc7, false) => DXGI_FORMAT_BC7_UNORM,
+        (BlockCompression::Bc7, true) => DXGI_FORMAT_BC7_UNORM_SRGB,
+    };
+    for value in [dxgi_format, DIMENSION_TEXTURE2D, 0, 1, 0] {
+        bytes.extend(value.to_le_bytes());
+    }
+    for level in &texture.levels {
+        bytes.extend(level);
+    }
+
+    bytes
+}
+
+/// Build a KTX2 file without supercompression, with rows ordered from the top
+/// # Arguments
+/// * `texture` - The compressed texture
+/// * `srgb` - Whether the colors are sRGB rather than linear
+/// # Returns
+/// * `Vec<u8>` - The contents of the file
+pub fn ktx2_bytes(texture: &CompressedTexture, srgb: bool) -> Vec<u8> {
+    const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+    const HEADER_SIZE: usize = 80;
+    const LEVEL_ENTRY_SIZE: usize = 24;
+    const BLOCK_SIZE: usize = 16;
+
+    let (vk_format, color_model, samples): (u32, u8, &[(u32, u8)]) = match texture.compression {
+        // VK_FORMAT_BC3_UNORM_BLOCK with an alpha sample followed by a color sample
+        BlockCompression::Bc3 => (137, 130, &[(0, 15), (64, 0)]),
+        // VK_FORMAT_BC7_UNORM_BLOCK with a single sample covering the block
+        BlockCompression::Bc7 => (145, 134, &[(0, 0)]),
+    };
+    // Each format's _SRGB_BLOCK variant follows its _UNORM_BLOCK variant
+    let (vk_format, transfer) = if srgb { (vk_format + 1, 2) } else { (vk_format, 1) };
+
+    // The data format descriptor, a single basic block
+    let mut descriptor = Vec::new();
+    let block_size = 24 + 16 * samples.len() as u32;
+    descriptor.extend((4 + block_size).to_le_bytes());
+    descriptor.extend(0u32.to_le_bytes());
+    descriptor.extend((2 | block_size << 16).to_le_bytes());
+    // The color model, BT.709 primaries, the linear or sRGB transfer function and straight alpha
+    descriptor.extend([color_model, 1, transfer, 0]);
+    descriptor.extend([3, 3, 0, 0]);
+    descriptor.extend([BLOCK_SIZE as u8, 0, 0, 0, 0, 0, 0, 0]);
+    for (bit_offset, channel) in samples {
+        let bit_length = 64 * 2 / samples.len() as u32 - 1;
+        descriptor.extend((bit_offset | bit_length << 16 | (*channel as u32) << 24).to_le_bytes());
+        descriptor.extend([0; 4]);
+        descriptor.extend(0u32.to_le_bytes());
+        descriptor.extend(u32::MAX.to_le_bytes());
+    }
+
+    // Key/value data, sorted by key
+    let mut key_values = Vec::new();
+    let writer = concat!("sprite-packer ", env!("CARGO_PKG_VERSION"));
+    for (key, value) in [("KTXorientation", "rd"), ("KTXwriter", writer)] {
+        let entry = [key.as_bytes(), &[0], value.as_bytes(), &[0]].concat();
+        key_values.extend((entry.len() as u32).to_le_bytes());
+        key_values.extend(&entry);
+        key_values.resize((key_values.len() + 3) / 4 * 4, 0);
+    }
+
+    let level_count = texture.levels.len();
+    let descriptor_offset = HEADER_SIZE + LEVEL_ENTRY_SIZE * level_count;
+    let key_values_offset = descriptor_offset + descriptor.len();
+    let data_offset = (key_values_offset + key_values.len() + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE;
+
+    // Levels are stored from the smallest to the full size image
+    let mut level_offsets = vec![0; level_count];
+    let mut offset = data_offset;
+    for (index, level) in texture.levels.iter().enumerate().rev() {
+        level_offsets[index] = offset;
+        offset += level.len();
+    }
+
+    let mut bytes = IDENTIFIER.to_vec();
+    for value in [vk_format, 1, texture.width, texture.height, 0, 0, 1, level_count as u32, 0] {
+        bytes.extend(value.to_le_bytes());
+    }
+    for value in [descriptor_offset, descriptor.len(), key_values_offset, key_values.len()] {
+        bytes.extend((value as u32).to_le_bytes());
+    }
+    bytes.extend([0; 16]);
+    for (level, level_offset) in texture.levels.iter().zip(&level_offsets) {
+        for value in [*level_offset, level.len(), level.len()] {
+            bytes.extend((value as u64).to_le_bytes());
+        }
+    }
+    bytes.extend(descriptor);
+    bytes.extend(key_values);
+    bytes.resize(data_offset, 0);
+    for level in texture.levels.iter().rev() {
+        bytes.extend(level);
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> usize {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap()) as usize
+    }
+
+    /// A smooth 12x8 image with a diagonal gradient between two colors with partial alpha, so that the pixels of
+    /// each block lie on a line like the endpoints of a single subset expect
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(12, 8, |x, y| {
+            let t = x + 2 * y;
+            Rgba([(t * 9) as u8, (255 - t * 9) as u8, (40 + t * 4) as u8, (255 - t * 6) as u8])
+        })
+    }
+
+    /// Decode a BC3 block the way GPUs do
+    fn decode_bc3(block: &[u8]) -> [[u8; 4]; 16] {
+        let (alpha0, alpha1) = (block[0] as u32, block[1] as u32);
+        let alphas = if alpha0 > alpha1 {
+            let mut alphas = [alpha0, alpha1, 0, 0, 0, 0, 0, 0];
+            for i in 1..7 {
+                alphas[i + 1] = ((7 - i as u32) * alpha0 + i as u32 * alpha1) / 7;
+            }
+            alphas
+        } else {
+            let mut alphas = [alpha0, alpha1, 0, 0, 0, 0, 0, 255];
+            for i in 1..5 {
+                alphas[i + 1] = ((5 - i as u32) * alpha0 + i as u32 * alpha1) / 5;
+            }
+            alphas
+        };
+        let mut alpha_bits = [0; 8];
+        alpha_bits[..6].copy_from_slice(&block[2..8]);
+        let alpha_bits = u64::from_le_bytes(alpha_bits);
+
+        let (color0, color1) = (u16::from_le_bytes([block[8], block[9]]), u16::from_le_bytes([block[10], block[11]]));
+        let (end0, end1) = (from_rgb565(color0), from_rgb565(color1));
+        let mix = |a: [i32; 3], b: [i32; 3], wa: i32, wb: i32| [0, 1, 2].map(|c| (wa * a[c] + wb * b[c]) / (wa + wb));
+        let colors = if color0 > color1 {
+            [end0, end1, mix(end0, end1, 2, 1), mix(end0, end1, 1, 2)]
+        } else {
+            [end0, end1, mix(end0, end1, 1, 1), [0, 0, 0]]
+        };
+        let color_bits = u32::from_le_bytes([block[12], block[13], block[14], block[15]]);
+
+        let mut pixels = [[0; 4]; 16];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let color = colors[(color_bits >> (2 * i) & 3) as usize];
+            let alpha = alphas[(alpha_bits >> (3 * i) & 7) as usize];
+            *pixel = [color[0] as u8, color[1] as u8, color[2] as u8, alpha as u8];
+        }
+        pixels
+    }
+
+    /// Decode a BC7 mode 6 block the way GPUs do
+    fn decode_bc7(block: &[u8]) -> [[u8; 4]; 16] {
+        let bits = u128::from_le_bytes(block.try_into().unwrap());
+        let mut position = 0;
+        let mut read = |count: u32| {
+            let value = (bits >> position) as u32 & ((1 << count) - 1);
+            position += count;
+            value as i32
+        };
+        assert_eq!(read(7), 1 << 6, "Only mode 6 is written");
+
+        // Both endpoints of red, then of green, blue and alpha, followed by a p-bit for each endpoint
+        let values = [(); 8].map(|_| read(7));
+        let p_bits = [read(1), read(1)];
+        let endpoints = [0, 1].map(|end| [0, 1, 2, 3].map(|channel| values[2 * channel + end] << 1 | p_bits[end]));
+        let mut pixels = [[0; 4]; 16];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let weight = BC7_WEIGHTS[read(if i == 0 { 3 } else { 4 }) as usize];
+            *pixel = [0, 1, 2, 3].map(|c| (((64 - weight) * endpoints[0][c] + weight * endpoints[1][c] + 32) >> 6) as u8);
+        }
+        pixels
+    }
+
+    /// Get the largest difference of any channel between an image and its decoded blocks
+    fn max_error(image: &RgbaImage, blocks: &[u8], decode: fn(&[u8]) -> [[u8; 4]; 16]) -> u8 {
+        let blocks_x = (image.width() + 3) / 4;
+        let mut error = 0;
+        for (index, block) in blocks.chunks(16).enumerate() {
+            let (block_x, block_y) = (index as u32 % blocks_x, index as u32 / blocks_x);
+            for (i, decoded) in decode(block).iter().enumerate() {
+                let (x, y) = (block_x * 4 + i as u32 % 4, block_y * 4 + i as u32 / 4);
+                if x < image.width() && y < image.height() {
+                    let pixel = image.get_pixel(x, y).0;
+                    error = (0..4).map(|c| pixel[c].abs_diff(decoded[c])).fold(error, u8::max);
+                }
+            }
+        }
+        error
+    }
+
+    #[test]
+    fn bc3_blocks_decode_within_tolerance() {
+        // Each block's red spans 81 values with only 4 colors to pick from, so pixels can be up to half of the 27
+        // between colors away plus the rounding of 5:6:5 endpoints
+        let image = gradient();
+        let blocks = compress(&image, BlockCompression::Bc3);
+        assert_eq!(blocks.len(), 3 * 2 * 16);
+        assert!(max_error(&image, &blocks, decode_bc3) <= 16);
+
+        let solid = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 255, 255]));
+        assert_eq!(max_error(&solid, &compress(&solid, BlockCompression::Bc3), decode_bc3), 0);
+    }
+
+    #[test]
+    fn bc7_blocks_decode_within_tolerance() {
+        // 16 levels between the endpoints are at most 6 apart across each block's range of 81
+        let image = gradient();
+        let blocks = compress(&image, BlockCompression::Bc7);
+        assert_eq!(blocks.len(), 3 * 2 * 16);
+        assert!(max_error(&image, &blocks, decode_bc7) <= 4);
+
+        let solid = RgbaImage::from_pixel(4, 4, Rgba([254, 0, 254, 255]));
+        assert!(max_error(&solid, &compress(&solid, BlockCompression::Bc7), decode_bc7) <= 1);
+    }
+
+    #[test]
+    fn mipmaps_halve_down_to_one_pixel() {
+        let levels = mipmaps(gradient(), true);
+        let sizes = levels.iter().map(RgbaImage::dimensions).collect::<Vec<(u32, u32)>>();
+        assert_eq!(sizes, vec![(12, 8), (6, 4), (3, 2), (1, 1)]);
+        assert_eq!(mipmaps(gradient(), false).len(), 1);
+    }
+
+    #[test]
+    fn dds_has_a_dx10_header_and_every_level() {
+        for (compression, srgb, dxgi_format) in [
+            (BlockCompression::Bc3, false, 77),
+            (BlockCompression::Bc3, true, 78),
+            (BlockCompression::Bc7, false, 98),
+            (BlockCompression::Bc7, true, 99),
+        ] {
+            let texture = compress_levels(&mipmaps(gradient(), true), compression);
+            let bytes = dds_bytes(&texture, srgb);
+
+            assert_eq!(&bytes[..4], b"DDS ");
+            assert_eq!(u32_at(&bytes, 4), 124);
+            assert_eq!((u32_at(&bytes, 12), u32_at(&bytes, 16)), (8, 12));
+            assert_eq!(u32_at(&bytes, 20), 3 * 2 * 16);
+            assert_eq!(u32_at(&bytes, 28), 4);
+            assert_eq!(&bytes[84..88], b"DX10");
+            assert_eq!(u32_at(&bytes, 128), dxgi_format);
+            assert_eq!(u32_at(&bytes, 132), 3);
+
+            // Levels follow the headers from the full size image down, 6, 2, 1 and 1 blocks each
+            let mut offset = 148;
+            for (level, blocks) in texture.levels.iter().zip([6, 2, 1, 1]) {
+                assert_eq!(level.len(), blocks * 16);
+                assert_eq!(&bytes[offset..offset + level.len()], &level[..]);
+                offset += level.len();
+            }
+            assert_eq!(bytes.len(), offset);
+        }
+    }
+
+    #[test]
+    fn ktx2_has_a_level_index_matching_its_levels() {
+        for (compression, srgb, vk_format, transfer) in [
+            (BlockCompression::Bc3, false, 137, 1),
+            (BlockCompression::Bc3, true, 138, 2),
+            (BlockCompression::Bc7, false, 145, 1),
+            (BlockCompression::Bc7, true, 146, 2),
+        ] {
+            let texture = compress_levels(&mipmaps(gradient(), true), compression);
+            let bytes = ktx2_bytes(&texture, srgb);
+
+            assert_eq!(&bytes[..12], &[0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A]);
+            assert_eq!(u32_at(&bytes, 12), vk_format);
+            assert_eq!(u32_at(&bytes, 16), 1);
+            assert_eq!((u32_at(&bytes, 20), u32_at(&bytes, 24)), (12, 8));
+            assert_eq!(u32_at(&bytes, 40), 4);
+            assert_eq!(u32_at(&bytes, 44), 0);
+
+            let descriptor_offset = u32_at(&bytes, 48) as usize;
+            assert_eq!(descriptor_offset, 80 + 24 * 4);
+            assert_eq!(bytes[descriptor_offset + 12 + 2], transfer);
+
+            // The level index lists the full size image first, but the smallest level is stored first
+            let mut previous_offset = bytes.len();
+            for (index, (level, blocks)) in texture.levels.iter().zip([6, 2, 1, 1]).enumerate() {
+                let entry = 80 + 24 * index;
+                let (offset, length) = (u64_at(&bytes, entry), u64_at(&bytes, entry + 8));
+                assert_eq!(length, blocks * 16);
+                assert_eq!(u64_at(&bytes, entry + 16), length);
+                assert_eq!(offset % 16, 0);
+                assert_eq!(offset + length, previous_offset);
+                assert_eq!(&bytes[offset..offset + length], &level[..]);
+                previous_offset = offset;
+            }
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use crate::error::{PackerError, Result};
+use crate::packer::incremental::{fnv1a, modified_after};
+use crate::packer::pack::read_sprite_rect;
+use crate::packer::replace::replace_sprite;
+use crate::packer::{get_collection, open_sprite};
+use crate::tk2d::cln::Collection;
+use crate::tk2d::compare::CompareMode;
+use crate::tk2d::sprite::{Sprite, SpriteImage};
+use image::RgbaImage;
+use log::info;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A frame file in a content index
+#[derive(Clone, Debug)]
+pub struct ContentEntry {
+    /// The first sprite found using the frame file
+    pub sprite: Sprite,
+    pub path: PathBuf,
+    /// The frame's trimmed rect
+    pub rect: RgbaImage,
+    /// The hash of the trimmed rect's size and pixels, with the color of fully transparent pixels ignored
+    pub hash: u64,
+}
+
+/// Frames with matching trimmed rects, which may be in different collections and have different IDs
+#[derive(Clone, Debug, Serialize)]
+pub struct TwinGroup {
+    pub width: u32,
+    pub height: u32,
+    pub sprites: Vec<Sprite>,
+}
+
+/// An index of the trimmed rects of every frame file in some collections by their content
+#[derive(Clone, Debug, Default)]
+pub struct ContentIndex {
+    pub entries: Vec<ContentEntry>,
+    by_hash: HashMap<u64, Vec<usize>>,
+}
+
+impl ContentIndex {
+    /// Index the frame files of some collections
+    /// # Arguments
+    /// * `collections` - The collections to index the sprites of
+    /// * `sprites_path` - The path to the folder containing the animation folders
+    /// # Returns
+    /// * `ContentIndex` - An index with an entry for each frame file, in the order the sprites were found
+    pub fn build(collections: &[Collection], sprites_path: &Path) -> Result<Self> {
+        let mut sprites: Vec<&Sprite> = Vec::new();
+        let mut seen_paths: HashSet<&str> = HashSet::new();
+        for sprite in collections.iter().flat_map(|collection| collection.sprites.iter()) {
+            if seen_paths.insert(&sprite.path) {
+                sprites.push(sprite);
+            }
+        }
+
+        let entries = sprites
+            .par_iter()
+            .map(|sprite| {
+                let (path, image) = open_sprite(sprite, sprites_path)?;
+                let sprite_image = SpriteImage {
+                    sprite: (*sprite).clone(),
+                    image,
+                };
+                let rect = sprite_image.trim()?.to_image();
+                Ok(ContentEntry {
+                    sprite: (*sprite).clone(),
+                    path,
+                    hash: content_hash(&rect),
+                    rect,
+                })
+            })
+            .collect::<Result<Vec<ContentEntry>>>()?;
+
+        let mut index = ContentIndex::default();
+        for entry in entries {
+            index.insert(entry);
+        }
+
+        Ok(index)
+    }
+
+    /// Add an entry to the index
+    pub fn insert(&mut self, entry: ContentEntry) {
+        self.by_hash.entry(entry.hash).or_default().push(self.entries.len());
+        self.entries.push(entry);
+    }
+
+    /// Find the frames whose trimmed rects match a rect
+    /// # Arguments
+    /// * `rect` - The trimmed rect to find the twins of
+    /// * `mode` - How strictly the rects must match
+    /// # Returns
+    /// * `Vec<&ContentEntry>` - The matching entries, in the order they were added
+    pub fn find(&self, rect: &RgbaImage, mode: CompareMode) -> Vec<&ContentEntry> {
+        match mode {
+            // Transparent colors are left out of the hash, so these modes only match within a bucket
+            CompareMode::Exact | CompareMode::IgnoreTransparentRgb => self
+                .by_hash
+                .get(&content_hash(rect))
+                .into_iter()
+                .flatten()
+                .map(|index| &self.entries[*index])
+                .filter(|entry| mode.images_match(&entry.rect, rect))
+                .collect(),
+            _ => self.entries.iter().filter(|entry| mode.images_match(&entry.rect, rect)).collect(),
+        }
+    }
+
+    /// Group the frames whose trimmed rects match
+    /// # Arguments
+    /// * `mode` - How strictly the rects must match
+    /// # Returns
+    /// * `Vec<TwinGroup>` - The groups of more than one frame, ordered by size and then by first frame
+    ///
+    /// Groups whose frames all share a collection and ID are left out, since `check` already covers those.
+    pub fn groups(&self, mode: CompareMode) -> Vec<TwinGroup> {
+        let mut buckets = self.by_hash.values().collect::<Vec<&Vec<usize>>>();
+        buckets.sort_unstable_by_key(|bucket| bucket[0]);
+
+        // Each cluster is compared with its first entry, so near-identical clusters don't drift
+        let mut clusters: BTreeMap<(u32, u32), Vec<Vec<usize>>> = BTreeMap::new();
+        for bucket in buckets {
+            for &index in bucket {
+                let entry = &self.entries[index];
+                let size_clusters = clusters.entry(entry.rect.dimensions()).or_default();
+                let cluster = match mode {
+                    CompareMode::Exact | CompareMode::IgnoreTransparentRgb => size_clusters
+                        .iter_mut()
+                        .find(|cluster| self.entries[cluster[0]].hash == entry.hash
+                            && mode.images_match(&self.entries[cluster[0]].rect, &entry.rect)),
+                    _ => size_clusters
+                        .iter_mut()
+                        .find(|cluster| mode.images_match(&self.entries[cluster[0]].rect, &entry.rect)),
+                };
+                match cluster {
+                    Some(cluster) => cluster.push(index),
+                    None => size_clusters.push(vec![index]),
+                }
+            }
+        }
+
+        clusters
+            .into_iter()
+            .flat_map(|((width, height), size_clusters)| {
+                size_clusters.into_iter().filter_map(move |mut cluster| {
+                    cluster.sort_unstable();
+                    let sprites = cluster.iter().map(|index| self.entries[*index].sprite.clone()).collect::<Vec<Sprite>>();
+                    let only_duplicates = sprites.iter().all(|sprite| {
+                        sprite.collection_name == sprites[0].collection_name && sprite.id == sprites[0].id
+                    });
+                    if only_duplicates {
+                        None
+                    } else {
+                        Some(TwinGroup { width, height, sprites })
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// Find the frames with matching trimmed rects across some collections
+/// # Arguments
+/// * `collections` - The collections to search through
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `mode` - How strictly the rects must match
+/// # Returns
+/// * `Vec<TwinGroup>` - The groups of matching frames
+pub fn find_twins(collections: &[Collection], sprites_path: &Path, mode: CompareMode) -> Result<Vec<TwinGroup>> {
+    Ok(ContentIndex::build(collections, sprites_path)?.groups(mode))
+}
+
+/// Find the frames an edit to a sprite should be propagated to
+///
+/// The edited sprite's pixels from before the edit are read from its collection's atlas, so the atlas must not have
+/// been written since the frame was edited.
+/// # Arguments
+/// * `edited_sprite` - The sprite whose frame file was edited
+/// * `collections` - The collections to search through, including the edited sprite's
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `mode` - How strictly the frames must match the edited sprite's pixels from before the edit
+/// # Returns
+/// * `Vec<ContentEntry>` - The other frames, in any collection and with any ID, that match the edited
+///   sprite's rect in its collection's atlas and do not already have the edit
+pub fn edit_twins(
+    edited_sprite: &Sprite,
+    collections: &[Collection],
+    sprites_path: &Path,
+    mode: CompareMode,
+) -> Result<Vec<ContentEntry>> {
+    let collection = get_collection(&edited_sprite.collection_name, collections)?;
+    let (edited_path, edited_image) = open_sprite(edited_sprite, sprites_path)?;
+    // An atlas written since the edit, by packing over it or repacking, may already have the edit or bled edges
+    if modified_after(&collection.path, &edited_path)? {
+        return Err(PackerError::StaleAtlas {
+            collection: collection.name.clone(),
+            sprite: edited_sprite.path.clone(),
+        });
+    }
+
+    // Otherwise the atlas still has the pixels from before the edit, read back the same way as a frame is trimmed
+    let atlas = image::open(&collection.path).map_err(|e| PackerError::image(&collection.path, e))?;
+    let original = read_sprite_rect(edited_sprite, &atlas)?;
+
+    let edited = SpriteImage {
+        sprite: edited_sprite.clone(),
+        image: edited_image,
+    }
+    .trim()?
+    .to_image();
+
+    let index = ContentIndex::build(collections, sprites_path)?;
+    Ok(index
+        .find(&original, mode)
+        .into_iter()
+        .filter(|entry| entry.path != edited_path && entry.rect != edited)
+        .cloned()
+        .collect())
+}
+
+/// Copy an edited sprite's trimmed rect into the frames of its twins
+/// # Arguments
+/// * `edited_sprite` - The sprite whose frame file was edited
+/// * `twins` - The frames to copy the edit to, from `edit_twins`
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `Vec<PathBuf>` - The paths of the frame files that were overwritten
+pub fn propagate_edit(edited_sprite: &Sprite, twins: &[ContentEntry], sprites_path: &Path) -> Result<Vec<PathBuf>> {
+    let (edited_path, edited_image) = open_sprite(edited_sprite, sprites_path)?;
+    let source_image = SpriteImage {
+        sprite: edited_sprite.clone(),
+        image: edited_image,
+    };
+
+    let mut replaced_paths = Vec::new();
+    for twin in twins {
+        let image = image::open(&twin.path).map_err(|e| PackerError::image(&twin.path, e))?;
+        let mut target_image = SpriteImage {
+            sprite: twin.sprite.clone(),
+            image,
+        };
+
+        // Frame files are stored unrotated, so a flipped twin takes the rect as is at its own offsets
+        replace_sprite(&source_image, &mut target_image)?;
+        target_image.image.save(&twin.path).map_err(|e| PackerError::image(&twin.path, e))?;
+        info!("Propagated sprite at path {:?} to twin at path {:?}.", edited_path.display(), twin.path.display());
+        replaced_paths.push(twin.path.clone());
+    }
+
+    Ok(replaced_paths)
+}
+
+/// Hash the size and pixels of a trimmed rect
+/// # Arguments
+/// * `rect` - The trimmed rect
+/// # Returns
+/// * `u64` - The hash, which is the same for rects that only differ in the color of fully transparent pixels, and
+///   the same in every build so that it can be stored
+pub fn content_hash(rect: &RgbaImage) -> u64 {
+    let mut bytes = Vec::with_capacity(8 + rect.as_raw().len());
+    bytes.extend_from_slice(&rect.width().to_le_bytes());
+    bytes.extend_from_slice(&rect.height().to_le_bytes());
+    for pixel in rect.pixels() {
+        bytes.extend_from_slice(if pixel[3] == 0 { &[0; 4] } else { &pixel.0 });
+    }
+    fnv1a(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packer::pack::pack_sprites;
+    use crate::test_support::{self, TempDir};
+    use image::Rgba;
+    use std::fs;
+
+    /// A sprite with a 2x2 rect in a 2x2 frame
+    fn sprite(collection_name: &str, id: u32, name: &str) -> Sprite {
+        Sprite { id, xr: 0, yr: 0, width: 2, collection_name: collection_name.to_string(), ..test_support::sprite(name) }
+    }
+
+    /// A 2x2 rect with a transparent top left pixel of some color and opaque pixels of another
+    fn rect(transparent: [u8; 3], opaque: [u8; 3]) -> RgbaImage {
+        RgbaImage::from_fn(2, 2, |x, y| match (x, y) {
+            (0, 0) => Rgba([transparent[0], transparent[1], transparent[2], 0]),
+            _ => Rgba([opaque[0], opaque[1], opaque[2], 255]),
+        })
+    }
+
+    fn entry(sprite: Sprite, rect: RgbaImage) -> ContentEntry {
+        ContentEntry { path: PathBuf::from(&sprite.path), hash: content_hash(&rect), sprite, rect }
+    }
+
+    #[test]
+    fn hash_ignores_the_color_of_transparent_pixels() {
+        assert_eq!(content_hash(&rect([0, 0, 0], [9, 9, 9])), content_hash(&rect([255, 40, 7], [9, 9, 9])));
+        assert_ne!(content_hash(&rect([0, 0, 0], [9, 9, 9])), content_hash(&rect([0, 0, 0], [9, 9, 8])));
+
+        // Partly transparent pixels keep their color
+        let mut faded = rect([0, 0, 0], [9, 9, 9]);
+        faded.put_pixel(0, 0, Rgba([0, 0, 0, 1]));
+        let mut tinted = faded.clone();
+        tinted.put_pixel(0, 0, Rgba([255, 0, 0, 1]));
+        assert_ne!(content_hash(&faded), content_hash(&tinted));
+
+        // The same pixels in a different shape hash differently
+        let tall = RgbaImage::from_raw(1, 4, rect([0, 0, 0], [9, 9, 9]).into_raw()).unwrap();
+        assert_ne!(content_hash(&tall), content_hash(&rect([0, 0, 0], [9, 9, 9])));
+    }
+
+    #[test]
+    fn groups_leave_out_frames_sharing_a_collection_and_id() {
+        let mut index = ContentIndex::default();
+        // Duplicates in the same collection with the same ID
+        index.insert(entry(sprite("Knight Cln", 1, "Idle-0-1.png"), rect([0, 0, 0], [10, 10, 10])));
+        index.insert(entry(sprite("Knight Cln", 1, "Idle-1-1.png"), rect([0, 0, 0], [10, 10, 10])));
+        // Twins in different collections, and with a different ID in the same collection
+        index.insert(entry(sprite("Knight Cln", 2, "Idle-2-2.png"), rect([0, 0, 0], [20, 20, 20])));
+        index.insert(entry(sprite("Knight 2 Cln", 2, "Idle-3-2.png"), rect([0, 0, 0], [20, 20, 20])));
+        index.insert(entry(sprite("Knight Cln", 3, "Idle-4-3.png"), rect([255, 255, 255], [20, 20, 20])));
+        // A frame without twins
+        index.insert(entry(sprite("Knight Cln", 4, "Idle-5-4.png"), rect([0, 0, 0], [30, 30, 30])));
+
+        let names = |groups: Vec<TwinGroup>| {
+            groups
+                .into_iter()
+                .map(|group| group.sprites.into_iter().map(|sprite| sprite.name).collect::<Vec<String>>())
+                .collect::<Vec<Vec<String>>>()
+        };
+        assert_eq!(names(index.groups(CompareMode::Exact)), vec![vec!["Idle-2-2.png", "Idle-3-2.png"]]);
+        assert_eq!(
+            names(index.groups(CompareMode::IgnoreTransparentRgb)),
+            vec![vec!["Idle-2-2.png", "Idle-3-2.png", "Idle-4-3.png"]],
+        );
+        // Within the tolerance the duplicates join the twins, so their group is kept
+        assert_eq!(
+            names(index.groups(CompareMode::Tolerance { tolerance: 10 })),
+            vec![vec!["Idle-0-1.png", "Idle-1-1.png", "Idle-2-2.png", "Idle-3-2.png", "Idle-4-3.png"]],
+        );
+    }
+
+    #[test]
+    fn edits_propagate_to_twins_in_other_collections_and_flipped_frames() {
+        let sprites_path = TempDir::new("twins");
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+
+        let frame = |seed: u8| RgbaImage::from_fn(2, 2, |x, y| Rgba([seed, x as u8 * 100, y as u8 * 100, 255]));
+        let edited = sprite("Knight Cln", 1, "Idle-0-1.png");
+        let same_collection = Sprite { x: 4, ..sprite("Knight Cln", 3, "Idle-2-3.png") };
+        let unrelated = Sprite { x: 4, y: 4, ..sprite("Knight Cln", 4, "Idle-3-4.png") };
+        let flipped = Sprite { x: 2, y: 2, flipped: true, ..sprite("Knight 2 Cln", 2, "Idle-1-2.png") };
+        let collections = [
+            ("Knight Cln", vec![edited.clone(), same_collection.clone(), unrelated.clone()]),
+            ("Knight 2 Cln", vec![flipped.clone()]),
+        ]
+        .map(|(name, sprites)| Collection {
+            name: name.to_string(),
+            path: sprites_path.join(format!("Knight/0.Atlases/{}.png", name)),
+            sprites,
+        });
+
+        for (sprite, seed) in [(&edited, 1), (&same_collection, 1), (&flipped, 1), (&unrelated, 2)] {
+            frame(seed).save(sprites_path.join(&sprite.path)).unwrap();
+        }
+        for collection in &collections {
+            let blank = image::DynamicImage::ImageRgba8(RgbaImage::new(8, 8));
+            let atlas = pack_sprites(blank, &collection.sprites, &sprites_path, |_| {}, || false).unwrap().unwrap();
+            atlas.save(&collection.path).unwrap();
+        }
+        // Edit the frame after its atlas was packed
+        frame(5).save(sprites_path.join(&edited.path)).unwrap();
+
+        let twins = edit_twins(&edited, &collections, &sprites_path, CompareMode::Exact).unwrap();
+        let twin_paths = twins.iter().map(|twin| twin.path.clone()).collect::<Vec<PathBuf>>();
+        let replaced_paths = propagate_edit(&edited, &twins, &sprites_path).unwrap();
+        let frames = [&same_collection, &flipped, &unrelated].map(|sprite| image::open(sprites_path.join(&sprite.path)).unwrap().to_rgba8());
+        // Propagating again finds nothing left to edit
+        let remaining = edit_twins(&edited, &collections, &sprites_path, CompareMode::Exact).unwrap();
+
+        let expected_paths = [&same_collection, &flipped].map(|sprite| sprites_path.join(&sprite.path)).to_vec();
+        assert_eq!(twin_paths, expected_paths);
+        assert_eq!(replaced_paths, expected_paths);
+        assert_eq!(frames, [frame(5), frame(5), frame(2)]);
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn refuses_to_propagate_from_an_atlas_written_after_the_edit() {
+        let sprites_path = TempDir::new("twins-stale");
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+
+        let edited = sprite("Knight Cln", 1, "Idle-0-1.png");
+        let collection = test_support::collection(&sprites_path, vec![edited.clone()]);
+        let frame_path = sprites_path.join(&edited.path);
+        rect([0, 0, 0], [9, 9, 9]).save(&frame_path).unwrap();
+        // Pack over the atlas after the edit, rewriting it until the file system's clock has moved past the frame's
+        loop {
+            RgbaImage::from_pixel(8, 8, Rgba([9, 9, 9, 255])).save(&collection.path).unwrap();
+            if modified_after(&collection.path, &frame_path).unwrap() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let result = edit_twins(&edited, &[collection], &sprites_path, CompareMode::Exact);
+
+        assert!(matches!(result, Err(PackerError::StaleAtlas { .. })));
+    }
+}
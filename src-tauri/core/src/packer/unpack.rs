@@ -0,0 +1,371 @@
+use crate::error::{PackerError, Result};
+use crate::packer::load::{load_sprite_info, sprite_info_entry};
+use crate::packer::pack::read_sprite_rect;
+use crate::tk2d::info::SpriteInfo;
+use crate::tk2d::sprite::Sprite;
+use image::{DynamicImage, GenericImage, RgbaImage};
+use log::info;
+use rayon::prelude::*;
+use serde::de::Error;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The frame files written and skipped while unpacking an animation
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct UnpackSummary {
+    pub written: Vec<PathBuf>,
+    /// Frame files that already existed and were not overwritten
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Regenerate the frames of an animation from its atlases and sprite info in the GODump layout
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `animation_name` - The name of the animation folder to unpack
+/// * `output_path` - The sprites folder to write the frames to, which may be `sprites_path` itself
+/// * `overwrite` - Whether to overwrite frame files that already exist
+/// # Returns
+/// * `UnpackSummary` - The frame files that were written and skipped
+pub fn unpack_animation(
+    sprites_path: &Path,
+    animation_name: &str,
+    output_path: &Path,
+    overwrite: bool,
+) -> Result<UnpackSummary> {
+    let anim_path = sprites_path.join(animation_name);
+    if !anim_path.join("0.Atlases").join("SpriteInfo.json").exists() {
+        return Err(PackerError::MissingAnimation(animation_name.to_string()));
+    }
+    let sprite_info = load_sprite_info(&anim_path)?;
+    let sprites = unique_sprites(&sprite_info, &anim_path)?;
+
+    let mut atlases: HashMap<&str, DynamicImage> = HashMap::new();
+    for sprite in &sprites {
+        if !atlases.contains_key(sprite.collection_name.as_str()) {
+            let atlas_path = anim_path.join("0.Atlases").join(format!("{}.png", sprite.collection_name));
+            let atlas = image::open(&atlas_path).map_err(|e| PackerError::image(&atlas_path, e))?;
+            atlases.insert(&sprite.collection_name, atlas);
+        }
+    }
+
+    if output_path != sprites_path {
+        copy_animation_info(&anim_path, &output_path.join(animation_name), &sprites)?;
+    }
+
+    let canvas_sizes = canvas_sizes(&sprites, sprites_path);
+    let results = sprites
+        .par_iter()
+        .map(|sprite| {
+            let path = output_path.join(&sprite.path);
+            if path.exists() && !overwrite {
+                return Ok(Err(path));
+            }
+
+            let frame = unpack_sprite(sprite, &atlases[sprite.collection_name.as_str()], canvas_sizes[&sprite.path])?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| PackerError::io(parent, e))?;
+            }
+            frame.save(&path).map_err(|e| PackerError::image(&path, e))?;
+            Ok(Ok(path))
+        })
+        .collect::<Result<Vec<std::result::Result<PathBuf, PathBuf>>>>()?;
+
+    let mut summary = UnpackSummary::default();
+    for result in results {
+        match result {
+            Ok(path) => summary.written.push(path),
+            Err(path) => summary.skipped.push(path),
+        }
+    }
+    info!(
+        "Unpacked {} frames of animation {} to {:?}, skipped {} existing frames.",
+        summary.written.len(), animation_name, output_path.display(), summary.skipped.len(),
+    );
+
+    Ok(summary)
+}
+
+/// Get the frame files of an animation that unpacking it into its own sprites folder would overwrite
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `animation_name` - The name of the animation folder
+/// # Returns
+/// * `Vec<PathBuf>` - The paths of the frame files listed in the animation's sprite info that exist
+pub fn existing_frame_paths(sprites_path: &Path, animation_name: &str) -> Result<Vec<PathBuf>> {
+    let anim_path = sprites_path.join(animation_name);
+    let sprite_info = load_sprite_info(&anim_path)?;
+    Ok(unique_sprites(&sprite_info, &anim_path)?
+        .iter()
+        .map(|sprite| sprites_path.join(&sprite.path))
+        .filter(|path| path.exists())
+        .collect())
+}
+
+/// Regenerate a sprite's frame from the atlas it was packed into
+/// # Arguments
+/// * `sprite` - The sprite to regenerate the frame of
+/// * `atlas` - The atlas the sprite was packed into
+/// * `canvas` - The width and height of the frame
+/// # Returns
+/// * `RgbaImage` - The frame, transparent outside the sprite's trimmed rect
+pub fn unpack_sprite(sprite: &Sprite, atlas: &DynamicImage, canvas: (u32, u32)) -> Result<RgbaImage> {
+    let (width, height) = canvas;
+    if sprite.xr < 0 || sprite.yr < 0
+        || (sprite.xr + sprite.width) as u32 > width
+        || (sprite.yr + sprite.height) as u32 > height
+    {
+        return Err(PackerError::Geometry(format!(
+            "Rect {}x{} at ({}, {}) of sprite {} does not fit inside a {}x{} frame",
+            sprite.width, sprite.height, sprite.xr, sprite.yr, sprite.path, width, height,
+        )));
+    }
+
+    // The same view of the frame that SpriteImage::trim takes, with yr measured from the bottom
+    let rect = read_sprite_rect(sprite, atlas)?;
+    let mut frame = RgbaImage::new(width, height);
+    frame
+        .copy_from(&rect, sprite.xr as u32, height - (sprite.yr + sprite.height) as u32)
+        .map_err(|e| PackerError::image(&sprite.path, e))?;
+
+    Ok(frame)
+}
+
+/// Get each sprite in a sprite info once, by the path of its frame file
+///
+/// The frame and atlas paths are joined onto the output folder, so entries whose paths would lead outside it, being
+/// absolute or containing `..`, are rejected.
+fn unique_sprites(sprite_info: &SpriteInfo, anim_path: &Path) -> Result<Vec<Sprite>> {
+    let stays_inside = |path: &str| Path::new(path).components().all(|component| matches!(component, Component::Normal(_)));
+    let mut sprites: Vec<Sprite> = Vec::new();
+    for i in 0..sprite_info.id.len() {
+        let sprite = sprite_info_entry(sprite_info, i, anim_path)?;
+        if !stays_inside(&sprite.path) || !stays_inside(&sprite.collection_name) {
+            let sprite_info_path = anim_path.join("0.Atlases").join("SpriteInfo.json");
+            let message = format!("entry {} leads outside the sprites folder: {}", i, sprite.path);
+            return Err(PackerError::json(sprite_info_path, serde_json::Error::custom(message)));
+        }
+        if !sprites.iter().any(|existing| existing.path == sprite.path) {
+            sprites.push(sprite);
+        }
+    }
+
+    Ok(sprites)
+}
+
+/// Work out the size of each sprite's frame, which the sprite info does not record
+///
+/// A frame keeps the size of its existing file if there is one. Otherwise it takes the size of the largest
+/// existing frame in its clip, since the frames of a clip share a canvas, then in its collection, or failing
+/// that the smallest canvas that fits every trimmed rect in its clip.
+/// # Arguments
+/// * `sprites` - The sprites to size the frames of
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `HashMap<String, (u32, u32)>` - The width and height of each frame by the sprite's path
+fn canvas_sizes(sprites: &[Sprite], sprites_path: &Path) -> HashMap<String, (u32, u32)> {
+    let clip_path = |sprite: &Sprite| Path::new(&sprite.path).parent().map(Path::to_path_buf).unwrap_or_default();
+    let extent = |sprite: &Sprite| ((sprite.xr + sprite.width).max(0) as u32, (sprite.yr + sprite.height).max(0) as u32);
+    let max_size = |(w1, h1): (u32, u32), (w2, h2): (u32, u32)| (w1.max(w2), h1.max(h2));
+
+    let existing_sizes = sprites
+        .par_iter()
+        .filter_map(|sprite| {
+            image::image_dimensions(sprites_path.join(&sprite.path))
+                .ok()
+                .map(|size| (sprite.path.clone(), size))
+        })
+        .collect::<HashMap<String, (u32, u32)>>();
+
+    let mut clip_existing_sizes: HashMap<PathBuf, (u32, u32)> = HashMap::new();
+    let mut collection_existing_sizes: HashMap<&str, (u32, u32)> = HashMap::new();
+    let mut clip_fitted_sizes: HashMap<PathBuf, (u32, u32)> = HashMap::new();
+    for sprite in sprites {
+        if let Some(size) = existing_sizes.get(&sprite.path) {
+            let existing = clip_existing_sizes.entry(clip_path(sprite)).or_insert(*size);
+            *existing = max_size(*existing, *size);
+            let existing = collection_existing_sizes.entry(&sprite.collection_name).or_insert(*size);
+            *existing = max_size(*existing, *size);
+        }
+        let fitted = clip_fitted_sizes.entry(clip_path(sprite)).or_insert((0, 0));
+        *fitted = max_size(*fitted, extent(sprite));
+    }
+
+    sprites
+        .iter()
+        .map(|sprite| {
+            let size = existing_sizes
+                .get(&sprite.path)
+                .or_else(|| clip_existing_sizes.get(&clip_path(sprite)))
+                .or_else(|| collection_existing_sizes.get(sprite.collection_name.as_str()))
+                .copied()
+                .unwrap_or(clip_fitted_sizes[&clip_path(sprite)]);
+            // A corrupted frame may be too small for its rect, in which case it is grown to fit
+            (sprite.path.clone(), max_size(size, extent(sprite)))
+        })
+        .collect()
+}
+
+/// Copy the sprite info, atlases and clip info of an animation folder to another folder
+/// # Arguments
+/// * `anim_path` - The path to the animation folder
+/// * `output_anim_path` - The path to the animation folder to copy to
+/// * `sprites` - The sprites in the animation's sprite info
+fn copy_animation_info(anim_path: &Path, output_anim_path: &Path, sprites: &[Sprite]) -> Result<()> {
+    let output_atlases_path = output_anim_path.join("0.Atlases");
+    fs::create_dir_all(&output_atlases_path).map_err(|e| PackerError::io(&output_atlases_path, e))?;
+    let mut file_names = vec!["SpriteInfo.json".to_string()];
+    for sprite in sprites {
+        let file_name = format!("{}.png", sprite.collection_name);
+        if !file_names.contains(&file_name) {
+            file_names.push(file_name);
+        }
+    }
+    for file_name in file_names {
+        let path = anim_path.join("0.Atlases").join(&file_name);
+        fs::copy(&path, output_atlases_path.join(&file_name)).map_err(|e| PackerError::io(&path, e))?;
+    }
+
+    for sprite in sprites {
+        let clip_name = match Path::new(&sprite.path).parent().and_then(Path::file_name) {
+            Some(clip_name) => clip_name,
+            None => continue,
+        };
+        let anim_info_path = anim_path.join(clip_name).join("AnimInfo.json");
+        let output_clip_path = output_anim_path.join(clip_name);
+        if anim_info_path.exists() && !output_clip_path.join("AnimInfo.json").exists() {
+            fs::create_dir_all(&output_clip_path).map_err(|e| PackerError::io(&output_clip_path, e))?;
+            fs::copy(&anim_info_path, output_clip_path.join("AnimInfo.json")).map_err(|e| PackerError::io(&anim_info_path, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packer::format::AtlasFormat;
+    use crate::packer::load::load_collections_and_animations;
+    use crate::packer::pack::{atlas_position, pack_collection};
+    use crate::test_support::{self, TempDir};
+    use image::Rgba;
+
+    /// A sprite with a 3x2 rect in its frame, placed at (x, y) in the atlas
+    fn sprite(path: &str, id: u32, xr: i32, yr: i32, x: i32, y: i32, flipped: bool) -> Sprite {
+        let name = Path::new(path).file_name().unwrap().to_string_lossy().to_string();
+        Sprite { id, x, y, xr, yr, path: path.to_string(), flipped, ..test_support::sprite(&name) }
+    }
+
+    fn sprite_info(sprites: &[Sprite]) -> SpriteInfo {
+        SpriteInfo {
+            id: sprites.iter().map(|sprite| sprite.id).collect(),
+            x: sprites.iter().map(|sprite| sprite.x).collect(),
+            y: sprites.iter().map(|sprite| sprite.y).collect(),
+            xr: sprites.iter().map(|sprite| sprite.xr).collect(),
+            yr: sprites.iter().map(|sprite| sprite.yr).collect(),
+            width: sprites.iter().map(|sprite| sprite.width).collect(),
+            height: sprites.iter().map(|sprite| sprite.height).collect(),
+            collection_name: sprites.iter().map(|sprite| sprite.collection_name.clone()).collect(),
+            path: sprites.iter().map(|sprite| sprite.path.clone()).collect(),
+            flipped: sprites.iter().map(|sprite| sprite.flipped).collect(),
+        }
+    }
+
+    #[test]
+    fn unpacked_flipped_and_unflipped_frames_pack_to_the_same_atlas() {
+        let directory = TempDir::new("unpack");
+        let (sprites_path, output_path) = (directory.join("sprites"), directory.join("unpacked"));
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+
+        // A 3x2 rect at (1, 1) and a flipped 2x3 rect at (5, 1) in the atlas, each pixel a distinct color
+        let sprites = [
+            sprite("Knight/Idle/Idle-0-1.png", 1, 1, 2, 1, 1, false),
+            sprite("Knight/Run/Run-0-2.png", 2, 0, 1, 5, 1, true),
+        ];
+        let mut atlas = RgbaImage::new(8, 8);
+        for (index, sprite) in sprites.iter().enumerate() {
+            for (di, dj) in (0..sprite.width).flat_map(|di| (0..sprite.height).map(move |dj| (di, dj))) {
+                let (x, y) = atlas_position(sprite, di, dj, 8);
+                atlas.put_pixel(x as u32, y as u32, Rgba([index as u8 * 100 + 10, di as u8 * 60, dj as u8 * 90, 200 + di as u8]));
+            }
+        }
+        atlas.save(sprites_path.join("Knight/0.Atlases/Knight Cln.png")).unwrap();
+        let text = serde_json::to_string(&sprite_info(&sprites)).unwrap();
+        fs::write(sprites_path.join("Knight/0.Atlases/SpriteInfo.json"), text).unwrap();
+
+        let summary = unpack_animation(&sprites_path, "Knight", &output_path, false).unwrap();
+        // Pack onto a blank atlas, so that only the unpacked frames can put pixels in it
+        RgbaImage::new(8, 8).save(output_path.join("Knight/0.Atlases/Knight Cln.png")).unwrap();
+        let (collections, _) = load_collections_and_animations(&output_path).unwrap();
+        let packed = pack_collection(&collections[0], &output_path, AtlasFormat::Original, |_| {}, || false).unwrap().unwrap();
+        let frame_sizes = sprites
+            .iter()
+            .map(|sprite| image::image_dimensions(output_path.join(&sprite.path)).unwrap())
+            .collect::<Vec<(u32, u32)>>();
+
+        assert_eq!(summary.written.len(), 2);
+        // With no frames to go by, each frame is just big enough for its rect at its offsets
+        assert_eq!(frame_sizes, vec![(4, 4), (3, 3)]);
+        assert_eq!(packed.to_rgba8().as_raw(), atlas.as_raw());
+    }
+
+    #[test]
+    fn canvas_sizes_are_guessed_from_existing_frames() {
+        let sprites_path = TempDir::new("unpack-sizes");
+        let existing = [("Knight/Idle/Idle-0-1.png", (10, 8)), ("Knight/Idle/Idle-1-2.png", (6, 12)), ("Knight/Run/Run-0-3.png", (9, 9))];
+        for (path, (width, height)) in existing {
+            fs::create_dir_all(sprites_path.join(path).parent().unwrap()).unwrap();
+            RgbaImage::new(width, height).save(sprites_path.join(path)).unwrap();
+        }
+        // A corrupted frame too small for its rect
+        fs::create_dir_all(sprites_path.join("Knight/Dash")).unwrap();
+        RgbaImage::new(2, 2).save(sprites_path.join("Knight/Dash/Dash-0-7.png")).unwrap();
+
+        let mut sprites = vec![
+            sprite("Knight/Idle/Idle-0-1.png", 1, 0, 0, 0, 0, false),
+            sprite("Knight/Idle/Idle-1-2.png", 2, 0, 0, 0, 0, false),
+            // Missing frames in a clip with existing frames, and in a clip without any
+            sprite("Knight/Idle/Idle-2-4.png", 4, 0, 0, 0, 0, false),
+            sprite("Knight/Run/Run-0-3.png", 3, 0, 0, 0, 0, false),
+            sprite("Knight/Fall/Fall-0-5.png", 5, 0, 0, 0, 0, false),
+            sprite("Knight/Dash/Dash-0-7.png", 7, 1, 2, 0, 0, false),
+        ];
+        // A collection without any existing frames, whose clip fits its rects
+        sprites.push(Sprite { collection_name: "Hornet Cln".to_string(), ..sprite("Hornet/Idle/Idle-0-6.png", 6, 4, 1, 0, 0, false) });
+        sprites.push(Sprite { collection_name: "Hornet Cln".to_string(), ..sprite("Hornet/Idle/Idle-1-8.png", 8, 1, 5, 0, 0, false) });
+
+        let sizes = canvas_sizes(&sprites, &sprites_path);
+
+        assert_eq!(sizes["Knight/Idle/Idle-0-1.png"], (10, 8));
+        assert_eq!(sizes["Knight/Idle/Idle-1-2.png"], (6, 12));
+        // The largest width and height of the existing frames in the clip
+        assert_eq!(sizes["Knight/Idle/Idle-2-4.png"], (10, 12));
+        // The largest width and height of the existing frames in the collection
+        assert_eq!(sizes["Knight/Fall/Fall-0-5.png"], (10, 12));
+        assert_eq!(sizes["Knight/Dash/Dash-0-7.png"], (4, 4));
+        // The smallest canvas that fits both rects at their offsets
+        assert_eq!(sizes["Hornet/Idle/Idle-0-6.png"], (7, 7));
+        assert_eq!(sizes["Hornet/Idle/Idle-1-8.png"], (7, 7));
+    }
+
+    #[test]
+    fn rejects_sprite_info_paths_leading_outside_the_sprites_folder() {
+        let anim_path = Path::new("Knight");
+        let inside = sprite("Knight/Idle/Idle-0-1.png", 1, 0, 0, 0, 0, false);
+        assert_eq!(unique_sprites(&sprite_info(&[inside.clone(), inside.clone()]), anim_path).unwrap(), vec![inside.clone()]);
+
+        let absolute = std::env::temp_dir().join("Idle-0-1.png").display().to_string();
+        let outside = [
+            sprite("../Idle-0-1.png", 1, 0, 0, 0, 0, false),
+            sprite("Knight/../../Idle-0-1.png", 1, 0, 0, 0, 0, false),
+            sprite(&absolute, 1, 0, 0, 0, 0, false),
+            Sprite { collection_name: "../Knight Cln".to_string(), ..inside.clone() },
+        ];
+        for sprite in outside {
+            let error = unique_sprites(&sprite_info(&[inside.clone(), sprite.clone()]), anim_path).unwrap_err();
+            assert_eq!(error.kind(), "Json", "{}", sprite.path);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::{PackerError, Result};
+use crate::packer::open_sprite;
+use crate::packer::pack::{atlas_position, pack_sprites};
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::Sprite;
+use image::{DynamicImage, GenericImageView};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The problems found with a single sprite while verifying a pack
+#[derive(Clone, Debug, Serialize)]
+pub struct SpriteVerification {
+    pub sprite: Sprite,
+    /// The number of pixels of the trimmed rect that lie outside the source frame, which are never packed
+    #[serde(rename = "overflowPixels")]
+    pub overflow_pixels: u32,
+    /// The number of pixels of the trimmed rect that would be written outside the atlas, which are clipped
+    #[serde(rename = "outOfBoundsPixels")]
+    pub out_of_bounds_pixels: u32,
+    /// The number of pixels in the sprite's region of the packed atlas that differ from the original atlas
+    #[serde(rename = "atlasMismatches")]
+    pub atlas_mismatches: u32,
+    /// The number of pixels in the sprite's region of the packed atlas that differ from the source frame,
+    /// e.g. because another sprite was packed over them
+    #[serde(rename = "frameMismatches")]
+    pub frame_mismatches: u32,
+}
+
+impl SpriteVerification {
+    /// Whether the sprite's pixels would be lost or clipped when packing
+    pub fn has_errors(&self) -> bool {
+        self.overflow_pixels > 0 || self.out_of_bounds_pixels > 0 || self.frame_mismatches > 0
+    }
+
+    /// Whether anything was found, including frames that changed since the atlas was packed
+    pub fn has_issues(&self) -> bool {
+        self.has_errors() || self.atlas_mismatches > 0
+    }
+}
+
+/// The result of packing a collection in memory and comparing it with its original atlas and frames
+#[derive(Clone, Debug, Serialize)]
+pub struct VerifyReport {
+    #[serde(rename = "collectionName")]
+    pub collection_name: String,
+    /// The number of frame files that were checked
+    #[serde(rename = "checkedSprites")]
+    pub checked_sprites: u32,
+    /// The sprites with issues, in the order they appear in the collection
+    pub sprites: Vec<SpriteVerification>,
+}
+
+impl VerifyReport {
+    /// Whether no sprite's pixels would be lost or clipped when packing
+    pub fn is_ok(&self) -> bool {
+        self.sprites.iter().all(|sprite| !sprite.has_errors())
+    }
+}
+
+/// Pack a collection in memory and compare each sprite's region of the result with the original atlas and its frame
+/// # Arguments
+/// * `collection` - The collection to verify
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `VerifyReport` - The sprites whose packed pixels don't round-trip
+pub fn verify_collection(collection: &Collection, sprites_path: &Path) -> Result<VerifyReport> {
+    let atlas = image::open(&collection.path).map_err(|e| PackerError::image(&collection.path, e))?;
+    let packed = match pack_sprites(atlas.clone(), &collection.sprites, sprites_path, |_| {}, || false)? {
+        Some(packed) => packed,
+        // A pack only stops without an atlas when it is cancelled, and nothing cancels this one
+        None => unreachable!("Verifying collection {} packed it without cancelling", collection.name),
+    };
+
+    let mut sprites: Vec<&Sprite> = Vec::new();
+    let mut seen_paths: HashSet<&str> = HashSet::new();
+    for sprite in &collection.sprites {
+        if seen_paths.insert(&sprite.path) {
+            sprites.push(sprite);
+        }
+    }
+
+    let verifications = sprites
+        .par_iter()
+        .map(|sprite| verify_sprite(sprite, &atlas, &packed, sprites_path))
+        .collect::<Result<Vec<SpriteVerification>>>()?;
+
+    Ok(VerifyReport {
+        collection_name: collection.name.clone(),
+        checked_sprites: sprites.len() as u32,
+        sprites: verifications.into_iter().filter(SpriteVerification::has_issues).collect(),
+    })
+}
+
+/// Compare a sprite's region of a packed atlas with the original atlas and its frame
+/// # Arguments
+/// * `sprite` - The sprite to verify
+/// * `atlas` - The original atlas
+/// * `packed` - The atlas the collection was packed into in memory
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `SpriteVerification` - The problems found with the sprite
+fn verify_sprite(sprite: &Sprite, atlas: &DynamicImage, packed: &DynamicImage, sprites_path: &Path) -> Result<SpriteVerification> {
+    let (_, frame) = open_sprite(sprite, sprites_path)?;
+    let (frame_width, frame_height) = (frame.width() as i32, frame.height() as i32);
+    let (atlas_width, atlas_height) = (packed.width() as i32, packed.height() as i32);
+    let mut verification = SpriteVerification {
+        sprite: sprite.clone(),
+        overflow_pixels: 0,
+        out_of_bounds_pixels: 0,
+        atlas_mismatches: 0,
+        frame_mismatches: 0,
+    };
+
+    for di in 0..sprite.width.max(0) {
+        for dj in 0..sprite.height.max(0) {
+            let (i, j) = (sprite.xr + di, sprite.yr + dj);
+            if i < 0 || i >= frame_width || j < 0 || j >= frame_height {
+                verification.overflow_pixels += 1;
+                continue;
+            }
+
+            let (x, y) = atlas_position(sprite, di, dj, atlas_height);
+            if x < 0 || x >= atlas_width || y < 0 || y >= atlas_height {
+                verification.out_of_bounds_pixels += 1;
+                continue;
+            }
+
+            let packed_pixel = packed.get_pixel(x as u32, y as u32);
+            if packed_pixel != atlas.get_pixel(x as u32, y as u32) {
+                verification.atlas_mismatches += 1;
+            }
+            if packed_pixel != frame.get_pixel(i as u32, (frame_height - j - 1) as u32) {
+                verification.frame_mismatches += 1;
+            }
+        }
+    }
+
+    Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, TempDir};
+    use image::{Rgba, RgbaImage};
+    use std::fs;
+
+    /// A sprite whose rect starts at the bottom left of its frame, placed at (x, y) in the atlas
+    fn sprite(name: &str, x: i32, y: i32, width: i32, height: i32) -> Sprite {
+        Sprite { x, y, xr: 0, yr: 0, width, height, ..test_support::sprite(name) }
+    }
+
+    #[test]
+    fn finds_each_kind_of_problem() {
+        let sprites_path = TempDir::new("verify");
+        fs::create_dir_all(sprites_path.join("Knight/Idle")).unwrap();
+        fs::create_dir_all(sprites_path.join("Knight/0.Atlases")).unwrap();
+
+        let sprites = [
+            // Packed into the atlas as it is now
+            (sprite("Idle-0-1.png", 0, 0, 2, 2), Rgba([255, 0, 0, 255])),
+            // Changed since the atlas was packed
+            (sprite("Idle-1-2.png", 4, 0, 2, 2), Rgba([0, 255, 0, 255])),
+            // A 3x2 rect in a 2x2 frame
+            (sprite("Idle-2-3.png", 0, 4, 3, 2), Rgba([0, 0, 255, 255])),
+            // Hanging off the right of the 8x8 atlas
+            (sprite("Idle-3-4.png", 7, 2, 2, 2), Rgba([255, 255, 0, 255])),
+            // Overlapping each other by one column
+            (sprite("Idle-4-5.png", 3, 6, 2, 2), Rgba([255, 255, 255, 255])),
+            (sprite("Idle-5-6.png", 4, 6, 2, 2), Rgba([0, 0, 0, 255])),
+        ];
+        for (sprite, color) in &sprites {
+            RgbaImage::from_pixel(2, 2, *color).save(sprites_path.join(&sprite.path)).unwrap();
+        }
+        let mut atlas = RgbaImage::new(8, 8);
+        let (first, color) = &sprites[0];
+        for (di, dj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let (x, y) = atlas_position(first, di, dj, 8);
+            atlas.put_pixel(x as u32, y as u32, *color);
+        }
+        let collection = test_support::collection(&sprites_path, sprites.iter().map(|(sprite, _)| sprite.clone()).collect());
+        atlas.save(&collection.path).unwrap();
+
+        let report = verify_collection(&collection, &sprites_path).unwrap();
+
+        assert_eq!(report.checked_sprites, 6);
+        assert!(!report.is_ok());
+        let found = |name: &str| report.sprites.iter().find(|verification| verification.sprite.name == name);
+        assert!(found("Idle-0-1.png").is_none());
+
+        let changed = found("Idle-1-2.png").unwrap();
+        assert_eq!((changed.atlas_mismatches, changed.frame_mismatches), (4, 0));
+        assert!(!changed.has_errors());
+        assert_eq!(found("Idle-2-3.png").unwrap().overflow_pixels, 2);
+        assert_eq!(found("Idle-3-4.png").unwrap().out_of_bounds_pixels, 2);
+        // Whichever of the overlapping sprites is packed last, the other loses the column they share
+        let overlapped = ["Idle-4-5.png", "Idle-5-6.png"].map(|name| found(name).map_or(0, |verification| verification.frame_mismatches));
+        assert_eq!(overlapped.iter().sum::<u32>(), 2);
+    }
+}
@@ -0,0 +1,502 @@
+use image::RgbaImage;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The largest width or height a lossless WebP image can have
+pub const MAX_DIMENSION: u32 = 1 << 14;
+
+/// The number of literal symbols in each channel's prefix code
+const NUM_LITERALS: usize = 256;
+/// The number of backward reference length symbols that follow the literals in the green prefix code
+const NUM_LENGTH_CODES: usize = 24;
+/// The number of symbols in the distance prefix code
+const NUM_DISTANCE_CODES: usize = 40;
+/// The longest backward reference
+const MAX_LENGTH: usize = 4096;
+/// The shortest run that is cheaper to encode as a backward reference than as literals
+const MIN_LENGTH: usize = 3;
+/// The longest code a prefix code of pixels may have
+const MAX_CODE_LENGTH: u8 = 15;
+/// The longest code the prefix code of code lengths may have
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+/// The order the code lengths of the code length code are written in
+const CODE_LENGTH_ORDER: [usize; 19] = [17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+/// The distance code of the pixel above, from the table of nearby pixels
+const DISTANCE_CODE_ABOVE: u32 = 1;
+/// The distance code of the pixel to the left, from the table of nearby pixels
+const DISTANCE_CODE_LEFT: u32 = 2;
+
+/// A frame of an animated WebP, which covers the whole canvas
+pub struct WebpFrame<'a> {
+    pub image: &'a RgbaImage,
+    /// How long the frame is shown for, in milliseconds
+    pub duration: u32,
+}
+
+/// Build an animated WebP file of lossless frames that loops forever
+/// # Arguments
+/// * `frames` - The frames, all the size of the first frame
+/// # Returns
+/// * `Vec<u8>` - The contents of the file
+pub fn encode_animation(frames: &[WebpFrame]) -> Vec<u8> {
+    const FLAG_ANIMATION: u8 = 0x02;
+    const FLAG_ALPHA: u8 = 0x10;
+    const NO_BLEND_AND_DISPOSE: u8 = 0x03;
+
+    let (width, height) = frames.first().map_or((1, 1), |frame| frame.image.dimensions());
+    let mut canvas = vec![FLAG_ANIMATION | FLAG_ALPHA, 0, 0, 0];
+    canvas.extend(&(width - 1).to_le_bytes()[..3]);
+    canvas.extend(&(height - 1).to_le_bytes()[..3]);
+
+    let mut body = b"WEBP".to_vec();
+    push_chunk(&mut body, b"VP8X", &canvas);
+    // A transparent background, looping forever
+    push_chunk(&mut body, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+    for frame in frames {
+        let mut data = vec![0; 6];
+        data.extend(&(frame.image.width() - 1).to_le_bytes()[..3]);
+        data.extend(&(frame.image.height() - 1).to_le_bytes()[..3]);
+        data.extend(&frame.duration.min(0xFFFFFF).to_le_bytes()[..3]);
+        data.push(NO_BLEND_AND_DISPOSE);
+        push_chunk(&mut data, b"VP8L", &encode_lossless(frame.image));
+        push_chunk(&mut body, b"ANMF", &data);
+    }
+
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend((body.len() as u32).to_le_bytes());
+    bytes.extend(body);
+    bytes
+}
+
+/// Append a RIFF chunk, padded to an even size
+fn push_chunk(bytes: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    bytes.extend(fourcc);
+    bytes.extend((data.len() as u32).to_le_bytes());
+    bytes.extend(data);
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+/// A pixel, or a run of pixels copied from nearby
+#[derive(Clone, Copy)]
+enum Token {
+    Literal([u8; 4]),
+    Copy { length: usize, distance_code: u32 },
+}
+
+/// Encode an image as a lossless WebP bitstream, the contents of a `VP8L` chunk
+///
+/// Runs of pixels that repeat the pixel to the left or above are copied, and the rest are written with prefix codes
+/// built from how often each value occurs. No transforms or color cache are used.
+/// # Arguments
+/// * `image` - The image, at most `MAX_DIMENSION` pixels wide and high
+/// # Returns
+/// * `Vec<u8>` - The bitstream
+pub fn encode_lossless(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let pixels = image.pixels().map(|pixel| pixel.0).collect::<Vec<[u8; 4]>>();
+    let tokens = tokenize(&pixels, width as usize);
+
+    let mut green = vec![0u32; NUM_LITERALS + NUM_LENGTH_CODES];
+    let (mut red, mut blue, mut alpha) = (vec![0u32; NUM_LITERALS], vec![0u32; NUM_LITERALS], vec![0u32; NUM_LITERALS]);
+    let mut distance = vec![0u32; NUM_DISTANCE_CODES];
+    for token in &tokens {
+        match *token {
+            Token::Literal([r, g, b, a]) => {
+                green[g as usize] += 1;
+                red[r as usize] += 1;
+                blue[b as usize] += 1;
+                alpha[a as usize] += 1;
+            }
+            Token::Copy { length, distance_code } => {
+                green[NUM_LITERALS + prefix(length as u32).0 as usize] += 1;
+                distance[prefix(distance_code).0 as usize] += 1;
+            }
+        }
+    }
+
+    let mut writer = BitWriter::default();
+    writer.write(0x2F, 8);
+    writer.write(width - 1, 14);
+    writer.write(height - 1, 14);
+    writer.write(pixels.iter().any(|pixel| pixel[3] < u8::MAX) as u32, 1);
+    writer.write(0, 3);
+    // No transforms, no color cache and a single group of prefix codes for the whole image
+    writer.write(0, 1);
+    writer.write(0, 1);
+    writer.write(0, 1);
+
+    let codes = [&green, &red, &blue, &alpha, &distance].map(|counts| write_prefix_code(&mut writer, counts));
+    let [green_codes, red_codes, blue_codes, alpha_codes, distance_codes] = &codes;
+    for token in &tokens {
+        match *token {
+            Token::Literal([r, g, b, a]) => {
+                writer.write_code(green_codes[g as usize]);
+                writer.write_code(red_codes[r as usize]);
+                writer.write_code(blue_codes[b as usize]);
+                writer.write_code(alpha_codes[a as usize]);
+            }
+            Token::Copy { length, distance_code } => {
+                let (length_symbol, length_bits, length_extra) = prefix(length as u32);
+                writer.write_code(green_codes[NUM_LITERALS + length_symbol as usize]);
+                writer.write(length_extra, length_bits);
+                let (distance_symbol, distance_bits, distance_extra) = prefix(distance_code);
+                writer.write_code(distance_codes[distance_symbol as usize]);
+                writer.write(distance_extra, distance_bits);
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// Split pixels into literals and runs that repeat the pixels to the left or above
+fn tokenize(pixels: &[[u8; 4]], width: usize) -> Vec<Token> {
+    let run = |start: usize, distance: usize| {
+        pixels[start..]
+            .iter()
+            .zip(&pixels[start - distance..])
+            .take(MAX_LENGTH)
+            .take_while(|(pixel, source)| pixel == source)
+            .count()
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < pixels.len() {
+        let left = if i >= 1 { run(i, 1) } else { 0 };
+        let above = if i >= width { run(i, width) } else { 0 };
+        let (length, distance_code) = if left >= above {
+            (left, DISTANCE_CODE_LEFT)
+        } else {
+            (above, DISTANCE_CODE_ABOVE)
+        };
+        if length >= MIN_LENGTH {
+            tokens.push(Token::Copy { length, distance_code });
+            i += length;
+        } else {
+            tokens.push(Token::Literal(pixels[i]));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Split a length or distance code into its prefix symbol, number of extra bits and extra bits
+fn prefix(value: u32) -> (u32, u32, u32) {
+    let value = value - 1;
+    if value < 4 {
+        return (value, 0, 0);
+    }
+    let highest_bit = 31 - value.leading_zeros();
+    let second_bit = (value >> (highest_bit - 1)) & 1;
+    let extra_bits = highest_bit - 1;
+    (2 * highest_bit + second_bit, extra_bits, value & ((1 << extra_bits) - 1))
+}
+
+/// A prefix code for a symbol, with the bits in the order they are read
+#[derive(Clone, Copy, Default)]
+struct Code {
+    bits: u32,
+    length: u32,
+}
+
+/// Write the prefix code of an alphabet with the given symbol counts
+/// # Returns
+/// * `Vec<Code>` - The code of each symbol
+fn write_prefix_code(writer: &mut BitWriter, counts: &[u32]) -> Vec<Code> {
+    let symbols = (0..counts.len()).filter(|&symbol| counts[symbol] > 0).collect::<Vec<usize>>();
+
+    // A simple code of one or two 8-bit symbols, where a single symbol takes no bits
+    if symbols.len() <= 2 && symbols.iter().all(|&symbol| symbol < NUM_LITERALS) {
+        let first = symbols.first().copied().unwrap_or(0);
+        writer.write(1, 1);
+        writer.write(symbols.len().max(1) as u32 - 1, 1);
+        if first < 2 {
+            writer.write(0, 1);
+            writer.write(first as u32, 1);
+        } else {
+            writer.write(1, 1);
+            writer.write(first as u32, 8);
+        }
+        let mut lengths = vec![0; counts.len()];
+        if let Some(&second) = symbols.get(1) {
+            writer.write(second as u32, 8);
+            lengths[first] = 1;
+            lengths[second] = 1;
+        }
+        return canonical_codes(&lengths);
+    }
+
+    let lengths = code_lengths(counts, MAX_CODE_LENGTH);
+    let length_tokens = code_length_tokens(&lengths);
+    let mut length_counts = [0u32; 19];
+    for &(symbol, _, _) in &length_tokens {
+        length_counts[symbol] += 1;
+    }
+    let length_code_lengths = code_lengths(&length_counts, MAX_CODE_LENGTH_CODE_LENGTH);
+    let length_codes = canonical_codes(&length_code_lengths);
+
+    let num_code_lengths = CODE_LENGTH_ORDER
+        .iter()
+        .rposition(|&symbol| length_code_lengths[symbol] > 0)
+        .map_or(4, |position| (position + 1).max(4));
+    writer.write(0, 1);
+    writer.write(num_code_lengths as u32 - 4, 4);
+    for &symbol in &CODE_LENGTH_ORDER[..num_code_lengths] {
+        writer.write(length_code_lengths[symbol] as u32, 3);
+    }
+    // Every symbol of the alphabet has a code length
+    writer.write(0, 1);
+    for (symbol, extra, extra_bits) in length_tokens {
+        writer.write_code(length_codes[symbol]);
+        writer.write(extra, extra_bits);
+    }
+
+    canonical_codes(&lengths)
+}
+
+/// Split code lengths into code length symbols with their extra bits, using 17 and 18 for runs of zeros
+fn code_length_tokens(lengths: &[u8]) -> Vec<(usize, u32, u32)> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let zeros = lengths[i..].iter().take(138).take_while(|&&length| length == 0).count();
+        if zeros >= 11 {
+            tokens.push((18, zeros as u32 - 11, 7));
+            i += zeros;
+        } else if zeros >= 3 {
+            tokens.push((17, zeros as u32 - 3, 3));
+            i += zeros;
+        } else {
+            tokens.push((lengths[i] as usize, 0, 0));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Build Huffman code lengths of at most `limit` bits for symbol counts
+///
+/// Counts are raised towards each other until the longest code fits, and a lone symbol is paired with another so
+/// that the code is complete.
+fn code_lengths(counts: &[u32], limit: u8) -> Vec<u8> {
+    let mut lengths = vec![0; counts.len()];
+    let symbols = (0..counts.len()).filter(|&symbol| counts[symbol] > 0).collect::<Vec<usize>>();
+    match symbols.len() {
+        0 => return lengths,
+        1 => {
+            lengths[symbols[0]] = 1;
+            lengths[if symbols[0] == 0 { 1 } else { 0 }] = 1;
+            return lengths;
+        }
+        _ => {}
+    }
+
+    let mut floor = 1;
+    loop {
+        // Each node is a leaf symbol or a pair of nodes, ordered by count and then by creation
+        let mut heap = BinaryHeap::new();
+        let mut children: Vec<Option<(usize, usize)>> = Vec::new();
+        for &symbol in &symbols {
+            heap.push(Reverse((counts[symbol].max(floor) as u64, children.len())));
+            children.push(None);
+        }
+        while heap.len() > 1 {
+            let Reverse((count_a, a)) = heap.pop().expect("The heap has two nodes");
+            let Reverse((count_b, b)) = heap.pop().expect("The heap has two nodes");
+            heap.push(Reverse((count_a + count_b, children.len())));
+            children.push(Some((a, b)));
+        }
+
+        let mut depths = vec![0u8; children.len()];
+        for node in (0..children.len()).rev() {
+            if let Some((a, b)) = children[node] {
+                depths[a] = depths[node] + 1;
+                depths[b] = depths[node] + 1;
+            }
+        }
+        if depths[..symbols.len()].iter().all(|&depth| depth <= limit) {
+            for (leaf, &symbol) in symbols.iter().enumerate() {
+                lengths[symbol] = depths[leaf];
+            }
+            return lengths;
+        }
+        floor *= 2;
+    }
+}
+
+/// Assign canonical codes to code lengths, shorter codes and then lower symbols first
+fn canonical_codes(lengths: &[u8]) -> Vec<Code> {
+    let max_length = lengths.iter().copied().max().unwrap_or(0) as usize;
+    let mut length_counts = vec![0u32; max_length + 1];
+    for &length in lengths.iter().filter(|&&length| length > 0) {
+        length_counts[length as usize] += 1;
+    }
+    let mut next_code = vec![0u32; max_length + 2];
+    for length in 1..=max_length {
+        next_code[length + 1] = (next_code[length] + length_counts[length]) << 1;
+    }
+
+    lengths
+        .iter()
+        .map(|&length| {
+            if length == 0 {
+                return Code::default();
+            }
+            let code = next_code[length as usize];
+            next_code[length as usize] += 1;
+            // Codes are read from their highest bit, but bits are packed from the lowest
+            Code { bits: code.reverse_bits() >> (32 - length as u32), length: length as u32 }
+        })
+        .collect()
+}
+
+/// Packs bits into bytes from the lowest bit up
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64 & ((1 << count) - 1)) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn write_code(&mut self, code: Code) {
+        self.write(code.bits, code.length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::webp::WebPDecoder;
+    use image::{AnimationDecoder, DynamicImage, Rgba};
+    use std::io::Cursor;
+
+    /// Wrap a lossless bitstream in a still WebP file
+    fn still(image: &RgbaImage) -> Vec<u8> {
+        let mut body = b"WEBP".to_vec();
+        push_chunk(&mut body, b"VP8L", &encode_lossless(image));
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    fn decode_still(image: &RgbaImage) -> RgbaImage {
+        let decoder = WebPDecoder::new(Cursor::new(still(image))).unwrap();
+        DynamicImage::from_decoder(decoder).unwrap().to_rgba8()
+    }
+
+    /// An image whose pixels are scrambled enough that no runs are copied and every channel uses many values
+    fn noise(width: u32, height: u32, seed: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let value = (x * 7919 + y * 104_729 + seed * 15_485_863).wrapping_mul(2_654_435_761);
+            Rgba(value.to_le_bytes())
+        })
+    }
+
+    #[test]
+    fn stills_round_trip() {
+        for image in [noise(1, 1, 0), noise(7, 5, 1), noise(64, 33, 2)] {
+            assert_eq!(decode_still(&image), image);
+        }
+    }
+
+    #[test]
+    fn partial_alpha_round_trips() {
+        let image = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 16, y as u8 * 16, 90, (x * 16 + y) as u8]));
+        assert_eq!(decode_still(&image), image);
+    }
+
+    #[test]
+    fn long_copy_runs_round_trip() {
+        // 10000 pixels of one color need several runs of the longest length, and the odd pixel out breaks one
+        // run and starts copies from above on the next row
+        let mut image = RgbaImage::from_pixel(100, 100, Rgba([30, 60, 90, 255]));
+        image.put_pixel(57, 41, Rgba([200, 10, 10, 128]));
+        let tokens = tokenize(&image.pixels().map(|pixel| pixel.0).collect::<Vec<[u8; 4]>>(), 100);
+        assert!(tokens.iter().any(|token| matches!(token, Token::Copy { length: MAX_LENGTH, .. })));
+        assert!(tokens.iter().any(|token| matches!(token, Token::Copy { distance_code: DISTANCE_CODE_ABOVE, .. })));
+        assert_eq!(decode_still(&image), image);
+    }
+
+    #[test]
+    fn one_and_two_symbol_alphabets_round_trip() {
+        // A single pixel has one symbol in every channel, and a checkerboard of two colors has two, with
+        // symbols on both sides of 2 since simple codes store those in fewer bits
+        let single = RgbaImage::from_pixel(1, 1, Rgba([0, 1, 200, 255]));
+        let checkerboard = RgbaImage::from_fn(9, 7, |x, y| {
+            if (x + y) % 2 == 0 { Rgba([0, 1, 200, 255]) } else { Rgba([1, 250, 3, 255]) }
+        });
+        for image in [single, checkerboard] {
+            assert_eq!(decode_still(&image), image);
+        }
+    }
+
+    #[test]
+    fn code_lengths_are_complete_for_degenerate_alphabets() {
+        // A complete prefix code has no unused codes, so the fractions of the code space its codes take add up to 1
+        let kraft_sum = |lengths: &[u8]| {
+            lengths.iter().filter(|&&length| length > 0).map(|&length| 1.0 / (1u64 << length) as f64).sum::<f64>()
+        };
+
+        assert_eq!(code_lengths(&[0, 0, 0], MAX_CODE_LENGTH), vec![0, 0, 0]);
+        // A lone symbol is paired with another so that its code has a bit to read
+        assert_eq!(code_lengths(&[0, 0, 5, 0], MAX_CODE_LENGTH), vec![1, 0, 1, 0]);
+        assert_eq!(code_lengths(&[5, 0, 0], MAX_CODE_LENGTH), vec![1, 1, 0]);
+        assert_eq!(code_lengths(&[0, 3, 0, 9], MAX_CODE_LENGTH), vec![0, 1, 0, 1]);
+
+        // Counts that would need codes longer than the limit without raising the smallest ones
+        let fibonacci = (0..20).scan((1u32, 1u32), |state, _| {
+            let count = state.0;
+            *state = (state.1, state.0 + state.1);
+            Some(count)
+        });
+        let counts = fibonacci.collect::<Vec<u32>>();
+        for limit in [MAX_CODE_LENGTH_CODE_LENGTH, MAX_CODE_LENGTH] {
+            let lengths = code_lengths(&counts, limit);
+            assert!(lengths.iter().all(|&length| (1..=limit).contains(&length)));
+            assert_eq!(kraft_sum(&lengths), 1.0);
+        }
+    }
+
+    #[test]
+    fn animations_round_trip() {
+        for images in [vec![noise(6, 4, 3)], vec![noise(6, 4, 4), RgbaImage::from_pixel(6, 4, Rgba([9, 8, 7, 100])), noise(6, 4, 5)]] {
+            let frames = images
+                .iter()
+                .enumerate()
+                .map(|(index, image)| WebpFrame { image, duration: 100 + index as u32 * 50 })
+                .collect::<Vec<WebpFrame>>();
+            let decoder = WebPDecoder::new(Cursor::new(encode_animation(&frames))).unwrap();
+            let decoded = decoder.into_frames().collect_frames().unwrap();
+
+            assert_eq!(decoded.len(), images.len());
+            for (index, (frame, image)) in decoded.iter().zip(&images).enumerate() {
+                assert_eq!(frame.buffer(), image);
+                assert_eq!(frame.delay().numer_denom_ms(), (100 + index as u32 * 50, 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::tk2d::cln::Collection;
+use crate::tk2d::sprite::Sprite;
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A folder in the system's temporary folder that is removed with everything in it when dropped, so that failing tests
+/// clean up too
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create an empty folder named after the test and the process
+    /// # Arguments
+    /// * `name` - The name of the test
+    /// # Returns
+    /// * `TempDir` - The guard of the folder
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("sprite-packer-{}-{}", name, std::process::id()));
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // Nothing is left to report a failure to while dropping
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A sprite of Knight Cln in the Knight/Idle folder, with a 3x2 rect at (1, 1) from the bottom left of its frame, placed
+/// unflipped at the top left of the atlas
+pub(crate) fn sprite(name: &str) -> Sprite {
+    Sprite {
+        id: 1,
+        x: 0,
+        y: 0,
+        xr: 1,
+        yr: 1,
+        width: 3,
+        height: 2,
+        collection_name: "Knight Cln".to_string(),
+        name: name.to_string(),
+        path: format!("Knight/Idle/{}", name),
+        flipped: false,
+    }
+}
+
+/// Knight Cln with its atlas in the Knight/0.Atlases folder
+pub(crate) fn collection(sprites_path: &Path, sprites: Vec<Sprite>) -> Collection {
+    Collection {
+        name: "Knight Cln".to_string(),
+        path: sprites_path.join("Knight/0.Atlases/Knight Cln.png"),
+        sprites,
+    }
+}
+
+/// A 6x5 frame with a distinct color at each pixel, so that any rotation or offset changes it
+pub(crate) fn frame(seed: u8) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(6, 5, |x, y| Rgba([seed, x as u8 * 40, y as u8 * 50, 255])))
+}
@@ -7,11 +7,7 @@ pub struct Animation {
     pub name: String,
 }
 
-impl Eq for Animation {
-    fn assert_receiver_is_total_eq(&self) {
-        self.name.assert_receiver_is_total_eq();
-    }
-}
+impl Eq for Animation {}
 
 impl Ord for Animation {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
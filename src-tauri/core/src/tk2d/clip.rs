@@ -45,11 +45,7 @@ impl Clip {
     }
 }
 
-impl Eq for Clip {
-    fn assert_receiver_is_total_eq(&self) {
-        self.name.assert_receiver_is_total_eq();
-    }
-}
+impl Eq for Clip {}
 
 impl Ord for Clip {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
@@ -9,11 +9,7 @@ pub struct Collection {
     pub sprites: Vec<Sprite>,
 }
 
-impl Eq for Collection {
-    fn assert_receiver_is_total_eq(&self) {
-        self.name.assert_receiver_is_total_eq();
-    }
-}
+impl Eq for Collection {}
 
 impl Ord for Collection {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
@@ -0,0 +1,135 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// How strictly the pixels of two sprites must match for the sprites to be considered identical
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum CompareMode {
+    /// Every channel of every pixel must be equal
+    Exact,
+    /// Like `Exact`, but the color of fully transparent pixels is ignored
+    IgnoreTransparentRgb,
+    /// No channel may differ by more than `tolerance`; fully transparent pixels always match
+    Tolerance { tolerance: u8 },
+    /// The CIE76 color difference, plus the alpha difference scaled to the same 0 to 100 range,
+    /// may not exceed `threshold`; fully transparent pixels always match
+    Perceptual { threshold: f32 },
+}
+
+impl Default for CompareMode {
+    fn default() -> Self {
+        CompareMode::Exact
+    }
+}
+
+impl CompareMode {
+    /// Whether two pixels match under this mode
+    pub fn pixels_match(&self, pixel1: &Rgba<u8>, pixel2: &Rgba<u8>) -> bool {
+        if pixel1 == pixel2 {
+            return true;
+        }
+
+        match *self {
+            CompareMode::Exact => false,
+            _ if pixel1[3] == 0 && pixel2[3] == 0 => true,
+            CompareMode::IgnoreTransparentRgb => false,
+            CompareMode::Tolerance { tolerance } => pixel1
+                .0
+                .iter()
+                .zip(pixel2.0.iter())
+                .all(|(channel1, channel2)| channel1.abs_diff(*channel2) <= tolerance),
+            CompareMode::Perceptual { threshold } => perceptual_distance(pixel1, pixel2) <= threshold,
+        }
+    }
+
+    /// Whether two images have the same size and all of their pixels match under this mode
+    pub fn images_match(&self, image1: &RgbaImage, image2: &RgbaImage) -> bool {
+        image1.dimensions() == image2.dimensions()
+            && image1.pixels().zip(image2.pixels()).all(|(pixel1, pixel2)| self.pixels_match(pixel1, pixel2))
+    }
+}
+
+/// Get the perceptual distance between two pixels
+/// # Returns
+/// * `f32` - The CIE76 color difference, weighted by the more opaque pixel's alpha, plus the alpha difference scaled to 0 to 100
+fn perceptual_distance(pixel1: &Rgba<u8>, pixel2: &Rgba<u8>) -> f32 {
+    let [l1, a1, b1] = to_lab(pixel1);
+    let [l2, a2, b2] = to_lab(pixel2);
+    let color_distance = ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt();
+    let opacity = pixel1[3].max(pixel2[3]) as f32 / 255.0;
+    let alpha_distance = pixel1[3].abs_diff(pixel2[3]) as f32 * 100.0 / 255.0;
+    color_distance * opacity + alpha_distance
+}
+
+/// Convert the color of an sRGB pixel to CIELAB with a D65 white point
+fn to_lab(pixel: &Rgba<u8>) -> [f32; 3] {
+    let linear = |channel: u8| {
+        let channel = channel as f32 / 255.0;
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance_allows_each_channel_to_differ_by_up_to_the_tolerance() {
+        let mode = CompareMode::Tolerance { tolerance: 3 };
+        let pixel = Rgba([100, 100, 100, 200]);
+        assert!(mode.pixels_match(&pixel, &Rgba([103, 97, 100, 203])));
+        assert!(!mode.pixels_match(&pixel, &Rgba([104, 100, 100, 200])));
+        assert!(!mode.pixels_match(&pixel, &Rgba([100, 100, 100, 196])));
+        // Fully transparent pixels match whatever their color, but not partly transparent ones
+        assert!(mode.pixels_match(&Rgba([0, 0, 0, 0]), &Rgba([255, 255, 255, 0])));
+        assert!(!mode.pixels_match(&Rgba([0, 0, 0, 1]), &Rgba([255, 255, 255, 1])));
+        assert!(!CompareMode::Exact.pixels_match(&Rgba([0, 0, 0, 0]), &Rgba([255, 255, 255, 0])));
+    }
+
+    #[test]
+    fn perceptual_threshold_is_on_a_0_to_100_scale() {
+        let (black, white) = (Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255]));
+        // Black and white are as far apart in lightness as colors get
+        assert!(!CompareMode::Perceptual { threshold: 99.9 }.pixels_match(&black, &white));
+        assert!(CompareMode::Perceptual { threshold: 100.1 }.pixels_match(&black, &white));
+
+        // A single step of one channel is hard to see
+        assert!(CompareMode::Perceptual { threshold: 1.0 }.pixels_match(&Rgba([100, 100, 100, 255]), &Rgba([101, 100, 100, 255])));
+
+        // Going from opaque to half transparent is half of the scale, whatever the color
+        let (opaque, half) = (Rgba([30, 160, 90, 255]), Rgba([30, 160, 90, 128]));
+        assert!(!CompareMode::Perceptual { threshold: 49.0 }.pixels_match(&opaque, &half));
+        assert!(CompareMode::Perceptual { threshold: 50.0 }.pixels_match(&opaque, &half));
+
+        // Colors of nearly transparent pixels count for little, and fully transparent pixels always match
+        let (red, blue) = (Rgba([255, 0, 0, 5]), Rgba([0, 0, 255, 5]));
+        assert!(CompareMode::Perceptual { threshold: 5.0 }.pixels_match(&red, &blue));
+        assert!(!CompareMode::Perceptual { threshold: 5.0 }.pixels_match(&Rgba([255, 0, 0, 255]), &Rgba([0, 0, 255, 255])));
+        assert!(CompareMode::Perceptual { threshold: 0.0 }.pixels_match(&Rgba([255, 0, 0, 0]), &Rgba([0, 0, 255, 0])));
+    }
+
+    #[test]
+    fn images_of_different_sizes_do_not_match() {
+        let image = RgbaImage::from_pixel(2, 3, Rgba([1, 2, 3, 255]));
+        assert!(CompareMode::Tolerance { tolerance: 255 }.images_match(&image, &image));
+        assert!(!CompareMode::Tolerance { tolerance: 255 }.images_match(&image, &RgbaImage::from_pixel(3, 2, Rgba([1, 2, 3, 255]))));
+    }
+}
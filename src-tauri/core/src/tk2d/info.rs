@@ -39,19 +39,20 @@ pub struct SpriteInfo {
 
 impl SpriteInfo {
     pub fn at(&self, index: usize) -> Option<Sprite> {
-        let sprite_name = PathBuf::from(self.path[index].clone()).file_name()?.to_str()?.to_string();
+        let path = self.path.get(index)?;
+        let sprite_name = PathBuf::from(path).file_name()?.to_str()?.to_string();
         Some(Sprite {
-            id: self.id[index],
-            x: self.x[index],
-            y: self.y[index],
-            xr: self.xr[index],
-            yr: self.yr[index],
-            width: self.width[index],
-            height: self.height[index],
-            collection_name: self.collection_name[index].clone(),
+            id: *self.id.get(index)?,
+            x: *self.x.get(index)?,
+            y: *self.y.get(index)?,
+            xr: *self.xr.get(index)?,
+            yr: *self.yr.get(index)?,
+            width: *self.width.get(index)?,
+            height: *self.height.get(index)?,
+            collection_name: self.collection_name.get(index)?.clone(),
             name: sprite_name,
-            path: self.path[index].clone(),
-            flipped: self.flipped[index],
+            path: path.clone(),
+            flipped: *self.flipped.get(index)?,
         })
     }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 pub mod anim;
 pub mod clip;
 pub mod cln;
+pub mod compare;
 pub mod info;
 pub mod sprite;
\ No newline at end of file
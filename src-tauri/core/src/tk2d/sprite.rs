@@ -0,0 +1,162 @@
+use image::{DynamicImage, GenericImageView, SubImage};
+use log::info;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PackerError, Result};
+use crate::tk2d::compare::CompareMode;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Sprite {
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub xr: i32,
+    pub yr: i32,
+    pub width: i32,
+    pub height: i32,
+    pub collection_name: String,
+    pub name: String,
+    pub path: String,
+    pub flipped: bool,
+}
+
+impl Sprite {
+    /// Get the width and height of the sprite's rect in its atlas
+    ///
+    /// `width` and `height` are measured in the frame, which is stored the right way up even if the sprite is
+    /// flipped, so a flipped sprite takes up its trimmed rect turned sideways in the atlas.
+    pub fn atlas_size(&self) -> (i32, i32) {
+        if self.flipped {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+}
+
+/// Parse a sprite's ID from its frame file name, e.g. `Idle-0-12.png` has the ID 12
+/// # Arguments
+/// * `sprite_name` - The file name of the sprite's frame
+/// # Returns
+/// * `u32` - The sprite's ID
+pub fn parse_sprite_id(sprite_name: &str) -> Result<u32> {
+    let sprite_data = sprite_name.split('-').collect::<Vec<&str>>();
+    let sprite_id_string = sprite_data[sprite_data.len() - 1].replace(".png", "");
+    sprite_id_string
+        .parse::<u32>()
+        .map_err(|_| PackerError::InvalidSpriteId(sprite_name.to_string()))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteImage {
+    pub sprite: Sprite,
+    pub image: DynamicImage,
+}
+
+impl SpriteImage {
+    pub fn equals(&self, other: &Self) -> Result<bool> {
+        self.equals_with(other, CompareMode::Exact)
+    }
+
+    /// Whether the trimmed rects of two sprites match under a comparison mode
+    ///
+    /// The rects are compared the way up they are in the frames, so a flipped sprite matches a sprite that is
+    /// not flipped if their frames show the same pixels, even though the pack rotates one of them in the atlas.
+    pub fn equals_with(&self, other: &Self, mode: CompareMode) -> Result<bool> {
+        if self.sprite.collection_name != other.sprite.collection_name {
+            info!("Collection names don't match: {} vs {}", self.sprite.collection_name, other.sprite.collection_name);
+            return Ok(false);
+        }
+        let frame1 = self.trim()?;
+        let frame2 = other.trim()?;
+        if frame1.width() != frame2.width() || frame1.height() != frame2.height() {
+            info!(
+                "Dimensions don't match: {}x{}{} vs {}x{}{}",
+                frame1.width(), frame1.height(), if self.sprite.flipped { " (flipped)" } else { "" },
+                frame2.width(), frame2.height(), if other.sprite.flipped { " (flipped)" } else { "" },
+            );
+            return Ok(false);
+        }
+
+        Ok((0..frame1.width()).into_par_iter().all(|i| {
+            (0..frame1.height()).all(|j| mode.pixels_match(&frame1.get_pixel(i, j), &frame2.get_pixel(i, j)))
+        }))
+    }
+
+    /// Get the sprite's trimmed rect from its frame, the same way up as in the frame whether or not it is flipped
+    pub fn trim(&self) -> Result<SubImage<&DynamicImage>> {
+        let sprite = &self.sprite;
+        if sprite.xr < 0 || sprite.yr < 0 || sprite.width < 0 || sprite.height < 0
+            || (sprite.xr + sprite.width) as u32 > self.image.width()
+            || (sprite.yr + sprite.height) as u32 > self.image.height()
+        {
+            return Err(PackerError::Geometry(format!(
+                "Rect {}x{} at ({}, {}) of sprite {} does not fit inside its {}x{} frame",
+                sprite.width, sprite.height, sprite.xr, sprite.yr, sprite.path,
+                self.image.width(), self.image.height(),
+            )));
+        }
+        let x_min = sprite.xr as u32;
+        let y_min = self.image.height() - (sprite.yr + sprite.height) as u32;
+        Ok(self.image.view(x_min, y_min, sprite.width as u32, sprite.height as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, frame};
+    use image::Rgba;
+
+    /// A sprite with a 3x2 rect at (1, 1) from the bottom left of a 6x5 frame
+    fn sprite(flipped: bool) -> Sprite {
+        Sprite { flipped, ..test_support::sprite("Idle-0-1.png") }
+    }
+
+    #[test]
+    fn trim_is_in_frame_space_for_flipped_and_unflipped_sprites() {
+        for flipped in [false, true] {
+            let image = SpriteImage { sprite: sprite(flipped), image: frame(7) };
+            let rect = image.trim().unwrap();
+            assert_eq!(rect.dimensions(), (3, 2));
+            // yr is measured from the bottom, so the rect's top row is row 2 of the 5 row frame
+            assert_eq!(rect.get_pixel(0, 0), Rgba([7, 40, 100, 255]));
+            assert_eq!(rect.get_pixel(2, 1), Rgba([7, 120, 150, 255]));
+        }
+    }
+
+    #[test]
+    fn atlas_size_is_turned_sideways_when_flipped() {
+        assert_eq!(sprite(false).atlas_size(), (3, 2));
+        assert_eq!(sprite(true).atlas_size(), (2, 3));
+    }
+
+    #[test]
+    fn equal_frames_match_whether_or_not_either_sprite_is_flipped() {
+        for (flipped1, flipped2) in [(false, false), (true, true), (false, true), (true, false)] {
+            let image1 = SpriteImage { sprite: sprite(flipped1), image: frame(7) };
+            let image2 = SpriteImage { sprite: sprite(flipped2), image: frame(7) };
+            assert!(image1.equals(&image2).unwrap(), "flipped {} vs {}", flipped1, flipped2);
+        }
+    }
+
+    #[test]
+    fn different_frames_do_not_match_whether_or_not_either_sprite_is_flipped() {
+        for (flipped1, flipped2) in [(false, false), (true, true), (false, true), (true, false)] {
+            let image1 = SpriteImage { sprite: sprite(flipped1), image: frame(7) };
+            let image2 = SpriteImage { sprite: sprite(flipped2), image: frame(8) };
+            assert!(!image1.equals(&image2).unwrap(), "flipped {} vs {}", flipped1, flipped2);
+        }
+    }
+
+    #[test]
+    fn transposed_rects_do_not_match() {
+        let image1 = SpriteImage { sprite: sprite(false), image: frame(7) };
+        let image2 = SpriteImage {
+            sprite: Sprite { width: 2, height: 3, ..sprite(true) },
+            image: frame(7),
+        };
+        assert!(!image1.equals(&image2).unwrap());
+    }
+}
@@ -1,10 +1,16 @@
 use crate::app::settings::Settings;
-use crate::tk2d::anim::Animation;
-use crate::tk2d::cln::Collection;
+use sprite_packer_core::packer::load::LoadReport;
+use sprite_packer_core::tk2d::anim::Animation;
+use sprite_packer_core::tk2d::cln::Collection;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 pub struct App {
     pub loaded_collections: Vec<Collection>,
     pub loaded_animations: Vec<Animation>,
+    pub load_report: LoadReport,
+    /// The paths atlases were last saved to, keyed by collection name
+    pub packed_atlas_paths: HashMap<String, PathBuf>,
     pub settings: Settings,
 }
 
@@ -13,6 +19,8 @@ impl Default for App {
         App {
             loaded_collections: Vec::new(),
             loaded_animations: Vec::new(),
+            load_report: LoadReport::default(),
+            packed_atlas_paths: HashMap::new(),
             settings: Settings::default(),
         }
     }
@@ -1,6 +1,13 @@
 use serde::{Deserialize, Serialize};
+use sprite_packer_core::packer::edges::EdgeSettings;
+use sprite_packer_core::packer::format::AtlasFormat;
+use sprite_packer_core::packer::output::{AtlasOutput, DEFAULT_TEMPLATE};
+use sprite_packer_core::packer::texture::TextureOptions;
+use sprite_packer_core::tk2d::compare::CompareMode;
+use std::path::PathBuf;
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
 pub struct Settings {
     #[serde(rename = "Language")]
     pub language: String,
@@ -8,6 +15,12 @@ pub struct Settings {
     pub sprites_path: String,
     #[serde(rename = "Mode")]
     pub mode: String,
+    #[serde(rename = "Comparison Mode")]
+    pub comparison_mode: CompareMode,
+    #[serde(rename = "Output")]
+    pub output: OutputSettings,
+    #[serde(rename = "Edges")]
+    pub edges: EdgeSettings,
 }
 
 impl Default for Settings {
@@ -16,6 +29,71 @@ impl Default for Settings {
             language: "en-US".to_string(),
             sprites_path: String::new(),
             mode: "dark".to_string(),
+            comparison_mode: CompareMode::default(),
+            output: OutputSettings::default(),
+            edges: EdgeSettings::default(),
+        }
+    }
+}
+
+/// Where generated atlases are written
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct OutputSettings {
+    /// The folder atlases are written to; if empty, a save dialog is shown instead
+    #[serde(rename = "Path")]
+    pub path: String,
+    /// The path of each atlas relative to the output folder
+    #[serde(rename = "Atlas Name Template")]
+    pub atlas_name_template: String,
+    /// Whether to back up an existing atlas before overwriting it
+    #[serde(rename = "Backup Existing Atlases")]
+    pub backup_existing_atlases: bool,
+    /// Whether to overwrite the original atlas in `0.Atlases` after backing it up
+    #[serde(rename = "Overwrite Original Atlases")]
+    pub overwrite_original_atlases: bool,
+    /// The pixel format to write atlases in
+    #[serde(rename = "Atlas Format")]
+    pub atlas_format: AtlasFormat,
+    /// The compressed textures to write next to each atlas
+    #[serde(rename = "Textures")]
+    pub textures: TextureOptions,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            atlas_name_template: DEFAULT_TEMPLATE.to_string(),
+            backup_existing_atlases: true,
+            overwrite_original_atlases: false,
+            atlas_format: AtlasFormat::default(),
+            textures: TextureOptions::default(),
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Get where to write atlases, if atlases should be written without a save dialog
+    pub fn atlas_output(&self) -> Option<AtlasOutput> {
+        if self.path.is_empty() && !self.overwrite_original_atlases {
+            return None;
+        }
+
+        Some(self.atlas_output_in(PathBuf::from(&self.path)))
+    }
+
+    /// Get where to write atlases inside a given folder
+    /// # Arguments
+    /// * `directory` - The folder to write atlases to
+    pub fn atlas_output_in(&self, directory: PathBuf) -> AtlasOutput {
+        AtlasOutput {
+            directory,
+            template: self.atlas_name_template.clone(),
+            backup_existing: self.backup_existing_atlases,
+            overwrite_original: self.overwrite_original_atlases,
+            format: self.atlas_format,
+            textures: self.textures,
         }
     }
 }
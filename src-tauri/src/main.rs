@@ -10,28 +10,40 @@
 
 mod app;
 mod macros;
-mod tk2d;
 
 use app::app::App;
-use app::settings::Settings;
+use app::settings::{OutputSettings, Settings};
 use notify::event::ModifyKind;
-use tk2d::anim::*;
-use tk2d::clip::Clip;
-use tk2d::cln::Collection;
-use tk2d::info::{AnimInfo, SpriteInfo};
-use tk2d::sprite::Sprite;
-use image::{GenericImage, GenericImageView};
+use sprite_packer_core::packer::{self, batch, check as checker, diff, incremental, lint, load, pack, repack, replace, resolve, twins, unpack, verify};
+use sprite_packer_core::packer::backup::{BackupSet, BackupStore};
+use sprite_packer_core::packer::diff::SpriteDiff;
+use sprite_packer_core::packer::edges::{self, EdgeOptions, EdgeSettings};
+use sprite_packer_core::packer::format::{self, AtlasFormat};
+use sprite_packer_core::packer::lint::BleedReport;
+use sprite_packer_core::packer::preview::{self, PreviewOptions};
+use sprite_packer_core::packer::output::AtlasOutput;
+use sprite_packer_core::packer::repack::RepackOptions;
+use sprite_packer_core::packer::sheet::{self, SheetFile, SheetLayout};
+use sprite_packer_core::packer::texture::{self, TextureOptions};
+use sprite_packer_core::packer::replace::ReplacementPreview;
+use sprite_packer_core::packer::resolve::{ResolutionPlan, ResolveStrategy};
+use sprite_packer_core::packer::twins::TwinGroup;
+use sprite_packer_core::packer::unpack::UnpackSummary;
+use sprite_packer_core::packer::verify::VerifyReport;
+use sprite_packer_core::packer::load::LoadReport;
+use sprite_packer_core::tk2d::anim::*;
+use sprite_packer_core::tk2d::clip::Clip;
+use sprite_packer_core::tk2d::cln::Collection;
+use sprite_packer_core::tk2d::compare::CompareMode;
+use sprite_packer_core::tk2d::sprite::{parse_sprite_id, Sprite};
+use sprite_packer_core::PackerError;
 use log::{error, info, LevelFilter, warn};
 use notify::{EventKind, RecursiveMode, Watcher, PollWatcher};
 use rayon::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use simple_logging;
-use std::collections::HashMap;
-use std::fs;
-use std::ops::ControlFlow;
 use std::path::{PathBuf, Path};
-use std::str::FromStr;
-use std::sync::{Mutex, Arc};
+use std::sync::Mutex;
 use std::sync::mpsc::{Receiver, self, Sender};
 use std::time::Instant;
 use tauri::{AppHandle, command, CustomMenuItem, Manager, Menu, MenuItem, State, Submenu, Window};
@@ -39,14 +51,40 @@ use tauri::api::dialog::blocking::FileDialogBuilder;
 use tauri::async_runtime;
 use tauri::RunEvent::{ExitRequested, Ready};
 
-use crate::tk2d::sprite::SpriteImage;
-
 /// A data structure containing the current pack progress
 #[derive(Clone, Serialize)]
 struct ProgressPayload {
     progress: usize
 }
 
+/// A data structure containing an error to show in the frontend
+#[derive(Clone, Serialize)]
+struct ErrorPayload {
+    kind: String,
+    message: String,
+}
+
+impl From<&PackerError> for ErrorPayload {
+    fn from(e: &PackerError) -> Self {
+        Self {
+            kind: e.kind().to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// The collections to pack in a batch
+#[derive(Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "camelCase")]
+enum BatchTarget {
+    /// The collections with the given names
+    Collections(Vec<String>),
+    /// Every collection containing a changed sprite
+    Changed,
+    /// Every collection used by the animation with the given name
+    Animation(String),
+}
+
 struct AppState(Mutex<App>);
 
 /// A list containing all sprites that were changed
@@ -67,71 +105,322 @@ const APP_NAME: &str = "sprite-packer";
 /// # Returns
 /// A list of duplicate sprites that are not identical
 #[command]
-fn check(state: State<AppState>) -> Vec<Sprite> {
+fn check(state: State<AppState>) -> Result<Vec<Sprite>, PackerError> {
     let app_state = state.0.lock().expect("Failed to lock app state.");
-    let sprites_path = app_state.settings.sprites_path.clone();
-    let mut problem_sprites = Vec::new();
-    for collection in &app_state.loaded_collections {
-        let mut sprite_map: HashMap<u32, Vec<Sprite>> = HashMap::new();
-        for sprite in &collection.sprites {
-            if sprite_map.contains_key(&sprite.id) && !sprite_map[&sprite.id].contains(sprite) {
-                for existing_sprite in &sprite_map[&sprite.id] {
-                    let existing_sprite_path = existing_sprite.path.clone();
-                    let mut path1 = PathBuf::from(sprites_path.clone()).join(existing_sprite_path.clone());
-                    if !path1.exists() {
-                        path1 = PathBuf::from(existing_sprite_path.clone());
-                    }
-                    let mut path2 = PathBuf::from(sprites_path.clone()).join(sprite.path.clone());
-                    if !path2.exists() {
-                        path2 = PathBuf::from(sprite.path.clone());
-                    }
-                    let image1 = match image::open(path1.clone()) {
-                        Ok(image) => image,
-                        Err(e) => log_panic!("Failed to open image at path {:?}: {}", path1.display(), e),
-                    };
-                    let image2 = match image::open(path2.clone()) {
-                        Ok(image) => image,
-                        Err(e) => log_panic!("Failed to open image at path {:?}: {}", path2.display(), e),
-                    };
-
-                    let sprite_image1 = SpriteImage {
-                        sprite: existing_sprite.clone(),
-                        image: image1,
-                    };
-
-                    let sprite_image2 = SpriteImage {
-                        sprite: sprite.clone(),
-                        image: image2,
-                    };
-
-                    if !sprite_image1.equals(&sprite_image2) {
-                        for sprite in &sprite_map[&sprite.id] {
-                            if !problem_sprites.contains(sprite) {
-                                problem_sprites.push(sprite.clone());
-                            }
-                        }
-                        if !problem_sprites.contains(sprite) {
-                            problem_sprites.push(sprite.clone());
-                        }
-                        break;
-                    }
+    let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+    checker::check(&app_state.loaded_collections, &sprites_path, app_state.settings.comparison_mode).map_err(log_error)
+}
+
+/// Compare the pixels of sprites sharing an ID
+/// # Arguments
+/// * `collection_name` - The name of the collection to compare the sprites of, or `None` for every collection
+/// * `include_masks` - Whether to render an image of the differing pixels of each variant
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<SpriteDiff>` - The groups of sprites sharing an ID that differ, with how each variant differs
+#[command]
+fn diff_duplicate_sprites(
+    collection_name: Option<String>,
+    include_masks: Option<bool>,
+    state: State<AppState>
+) -> Result<Vec<SpriteDiff>, PackerError> {
+    let app_state = state.0.lock().expect("Failed to lock app state.");
+    let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+    let collections = match collection_name {
+        Some(collection_name) => vec![get_collection(&collection_name, &app_state.loaded_collections)?.clone()],
+        None => app_state.loaded_collections.clone(),
+    };
+    diff::diff_collections(&collections, &sprites_path, include_masks.unwrap_or(false)).map_err(log_error)
+}
+
+/// Find frames with matching pixels across every loaded collection, whatever their IDs
+/// # Arguments
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<TwinGroup>` - The groups of matching frames
+#[command]
+fn find_twin_sprites(state: State<AppState>) -> Result<Vec<TwinGroup>, PackerError> {
+    let app_state = state.0.lock().expect("Failed to lock app state.");
+    let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+    twins::find_twins(&app_state.loaded_collections, &sprites_path, app_state.settings.comparison_mode).map_err(log_error)
+}
+
+/// Copy an edited frame to every frame in any collection whose pixels matched it before the edit,
+/// backing up the overwritten frames first
+/// # Arguments
+/// * `edited_sprite` - The sprite whose frame file was edited
+/// * `state` - The application state
+/// # Returns
+/// * `BackupSet` - The backup of the frames before they were overwritten
+#[command]
+fn propagate_to_twins(edited_sprite: Sprite, state: State<AppState>) -> Result<BackupSet, PackerError> {
+    let sprites_path: PathBuf;
+    let collections: Vec<Collection>;
+    let comparison_mode: CompareMode;
+    {
+        let app_state = state.0.lock().expect("Failed to lock app state.");
+        sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+        collections = app_state.loaded_collections.clone();
+        comparison_mode = app_state.settings.comparison_mode;
+    }
+
+    let twins = twins::edit_twins(&edited_sprite, &collections, &sprites_path, comparison_mode).map_err(log_error)?;
+    let paths = twins.iter().map(|twin| twin.path.clone()).collect::<Vec<PathBuf>>();
+    let backup_set = backup_store()
+        .snapshot(&paths, &format!("Propagate {} in {} to its twins", edited_sprite.name, edited_sprite.collection_name))
+        .map_err(log_error)?;
+    info!("Backed up {} frames to backup {}", backup_set.files.len(), backup_set.id);
+
+    without_watcher(&sprites_path, || twins::propagate_edit(&edited_sprite, &twins, &sprites_path)).map_err(log_error)?;
+
+    unsafe {
+        CHANGED_SPRITES.lock().expect("Failed to lock CHANGED_SPRITES")
+            .retain(|sprite| sprite.collection_name != edited_sprite.collection_name || sprite.path != edited_sprite.path);
+    }
+
+    Ok(backup_set)
+}
+
+/// Find frames with painted pixels outside their sprite's trimmed rect, which are never packed
+/// # Arguments
+/// * `collection_name` - The name of the collection to lint, or `None` for every collection
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<BleedReport>` - The pixels outside the rect of each frame that has any
+#[command]
+fn lint_bleeding_sprites(collection_name: Option<String>, state: State<AppState>) -> Result<Vec<BleedReport>, PackerError> {
+    let app_state = state.0.lock().expect("Failed to lock app state.");
+    let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+    let collections = match collection_name {
+        Some(collection_name) => vec![get_collection(&collection_name, &app_state.loaded_collections)?.clone()],
+        None => app_state.loaded_collections.clone(),
+    };
+    lint::lint_collections(&collections, &sprites_path).map_err(log_error)
+}
+
+/// Pack collections in memory and compare the result with their original atlases and frames
+/// # Arguments
+/// * `collection_name` - The name of the collection to verify, or `None` for every collection
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<VerifyReport>` - The sprites with issues in each collection
+#[command]
+fn verify_collections(collection_name: Option<String>, state: State<AppState>) -> Result<Vec<VerifyReport>, PackerError> {
+    let app_state = state.0.lock().expect("Failed to lock app state.");
+    let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+    let collections = match collection_name {
+        Some(collection_name) => vec![get_collection(&collection_name, &app_state.loaded_collections)?.clone()],
+        None => app_state.loaded_collections.clone(),
+    };
+    collections
+        .iter()
+        .map(|collection| verify::verify_collection(collection, &sprites_path))
+        .collect::<Result<Vec<VerifyReport>, PackerError>>()
+        .map_err(log_error)
+}
+
+/// Lay out a collection's sprites in a new atlas and update its sprite info to match
+/// # Arguments
+/// * `collection_name` - The name of the collection to repack
+/// * `options` - How to lay out the sprites, or `None` for the defaults
+/// * `output_path` - The sprites folder to write the atlas and sprite info to, or `None` to replace the
+///   collection's own after backing them up
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<Sprite>` - The collection's sprites with their new rects
+#[command]
+fn repack_collection(
+    collection_name: String,
+    options: Option<RepackOptions>,
+    output_path: Option<String>,
+    state: State<AppState>
+) -> Result<Vec<Sprite>, PackerError> {
+    let sprites_path: PathBuf;
+    let collection: Collection;
+    let format: AtlasFormat;
+    {
+        let app_state = state.0.lock().expect("Failed to lock app state.");
+        sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+        collection = get_collection(&collection_name, &app_state.loaded_collections)?.clone();
+        format = app_state.settings.output.atlas_format;
+    }
+
+    let repack = repack::repack_collection(&collection, &sprites_path, &options.unwrap_or_default(), format)
+        .map_err(log_error)?;
+    match output_path {
+        Some(output_path) => {
+            repack::write_repack(&repack, &collection, Path::new(&output_path)).map_err(log_error)?;
+        }
+        None => {
+            let sprite_info_path = collection.path.with_file_name("SpriteInfo.json");
+            let backup_set = backup_store()
+                .snapshot(&[collection.path.clone(), sprite_info_path], &format!("Repack {}", collection_name))
+                .map_err(log_error)?;
+            info!("Backed up the atlas and sprite info of {} to backup {}", collection_name, backup_set.id);
+
+            without_watcher(&sprites_path, || repack::write_repack(&repack, &collection, &sprites_path)).map_err(log_error)?;
+            load_collections_and_animations(&state)?;
+        }
+    }
+
+    Ok(repack.sprites)
+}
+
+/// Export a clip to an animated image for sharing
+/// # Arguments
+/// * `animation_name` - The name of the animation containing the clip
+/// * `clip_name` - The name of the clip to export
+/// * `options` - The format and scale to export to, or `None` for the defaults
+/// * `output_path` - The path to write the animated image to, or `None` to choose with a save dialog
+/// * `state` - The application state
+/// # Returns
+/// * `Option<PathBuf>` - The path the animated image was written to, or `None` if no path was chosen
+#[command]
+fn export_clip_preview(
+    animation_name: String,
+    clip_name: String,
+    options: Option<PreviewOptions>,
+    output_path: Option<String>,
+    state: State<AppState>
+) -> Result<Option<PathBuf>, PackerError> {
+    let (sprites_path, clip) = find_clip(&animation_name, &clip_name, &state)?;
+    let options = options.unwrap_or_default();
+
+    let path = match output_path {
+        Some(output_path) => PathBuf::from(output_path),
+        None => match FileDialogBuilder::new()
+            .set_directory(&sprites_path)
+            .set_file_name(&format!("{}.{}", clip_name, options.format.extension()))
+            .add_filter("Animated Image", &[options.format.extension()])
+            .save_file() {
+                Some(path) => path,
+                None => {
+                    warn!("No path for clip preview selected.");
+                    return Ok(None);
                 }
-            } else if !sprite_map.contains_key(&sprite.id) {
-                let sprite_data = sprite.name.split("-").collect::<Vec<&str>>();
-                let sprite_id_string = sprite_data[sprite_data.len() - 1].replace(".png", "");
-                let sprite_id = sprite_id_string.parse::<u32>().expect("Failed to convert Sprite ID string to u32.");
-                sprite_map.insert(sprite_id, vec![sprite.clone()]);
-            } else {
-                sprite_map.get_mut(&sprite.id).unwrap().push(sprite.clone());
             }
-        }
+    };
+
+    preview::export_clip(&clip, &sprites_path, &options, &path).map_err(log_error)?;
+    info!("Exported clip {:?} of animation {:?} to {:?}", clip_name, animation_name, path.display());
+    Ok(Some(path))
+}
+
+/// Compose the frames of a clip into a sprite sheet, and write the rect of each frame, the fps and the loop start
+/// next to it
+/// # Arguments
+/// * `animation_name` - The name of the animation containing the clip
+/// * `clip_name` - The name of the clip to compose
+/// * `layout` - How to arrange the cells, or `None` for a horizontal strip
+/// * `output_path` - The path to write the sprite sheet to, or `None` to choose with a save dialog
+/// * `state` - The application state
+/// # Returns
+/// * `Option<SheetFile>` - The metadata written next to the sprite sheet, or `None` if no path was chosen
+#[command]
+fn export_clip_sheet(
+    animation_name: String,
+    clip_name: String,
+    layout: Option<SheetLayout>,
+    output_path: Option<String>,
+    state: State<AppState>
+) -> Result<Option<SheetFile>, PackerError> {
+    let (sprites_path, clip) = find_clip(&animation_name, &clip_name, &state)?;
+
+    let path = match output_path {
+        Some(output_path) => PathBuf::from(output_path),
+        None => match FileDialogBuilder::new()
+            .set_directory(&sprites_path)
+            .set_file_name(&format!("{}.png", clip_name))
+            .add_filter("PNG Image", &["png"])
+            .save_file() {
+                Some(path) => path,
+                None => {
+                    warn!("No path for sprite sheet selected.");
+                    return Ok(None);
+                }
+            }
+    };
+
+    let metadata = sheet::write_sheet(&clip, &sprites_path, &layout.unwrap_or_default(), &path).map_err(log_error)?;
+    info!("Composed clip {:?} of animation {:?} into sprite sheet {:?}", clip_name, animation_name, path.display());
+    Ok(Some(metadata))
+}
+
+/// Get a loaded clip and the sprites path its frames are in
+/// # Arguments
+/// * `animation_name` - The name of the animation containing the clip
+/// * `clip_name` - The name of the clip
+/// * `state` - The application state
+/// # Returns
+/// * `(PathBuf, Clip)` - The sprites path and the clip
+fn find_clip(animation_name: &str, clip_name: &str, state: &State<AppState>) -> Result<(PathBuf, Clip), PackerError> {
+    let app_state = state.0.lock().expect("Failed to lock app state.");
+    let animation = app_state
+        .loaded_animations
+        .iter()
+        .find(|animation| animation.name == animation_name)
+        .ok_or_else(|| log_error(PackerError::MissingAnimation(animation_name.to_string())))?;
+    match animation.clips.iter().find(|clip| clip.name == clip_name) {
+        Some(clip) => Ok((PathBuf::from(app_state.settings.sprites_path.clone()), clip.clone())),
+        None => Err(log_error(PackerError::MissingClip {
+            animation: animation_name.to_string(),
+            clip: clip_name.to_string(),
+        })),
+    }
+}
+
+/// Regenerate the frames of animations from their atlases and sprite info
+/// # Arguments
+/// * `animation_name` - The name of the animation to unpack, or `None` for every animation
+/// * `output_path` - The sprites folder to write the frames to, or `None` to write them into the sprites folder
+/// * `overwrite` - Whether to overwrite frames that already exist, which are backed up first when unpacking in place
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<UnpackSummary>` - The frames written and skipped for each animation
+#[command]
+fn unpack_frames(
+    animation_name: Option<String>,
+    output_path: Option<String>,
+    overwrite: Option<bool>,
+    state: State<AppState>
+) -> Result<Vec<UnpackSummary>, PackerError> {
+    let sprites_path: PathBuf;
+    let animation_names: Vec<String>;
+    {
+        let app_state = state.0.lock().expect("Failed to lock app state.");
+        sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+        animation_names = match animation_name {
+            Some(animation_name) => vec![animation_name],
+            None => app_state.loaded_animations.iter().map(|animation| animation.name.clone()).collect(),
+        };
+    }
+    let overwrite = overwrite.unwrap_or(false);
+    let output_path = output_path.map(PathBuf::from).unwrap_or_else(|| sprites_path.clone());
+
+    if output_path != sprites_path {
+        return animation_names
+            .iter()
+            .map(|animation_name| unpack::unpack_animation(&sprites_path, animation_name, &output_path, overwrite))
+            .collect::<Result<Vec<UnpackSummary>, PackerError>>()
+            .map_err(log_error);
     }
 
-    for sprite in &problem_sprites {
-        warn!("Problem sprite at path {:?} is different from sprites with the same ID.", sprite.path.clone());
+    if overwrite {
+        let mut paths = Vec::new();
+        for animation_name in &animation_names {
+            paths.extend(unpack::existing_frame_paths(&sprites_path, animation_name).map_err(log_error)?);
+        }
+        let backup_set = backup_store().snapshot(&paths, "Unpack frames from atlases").map_err(log_error)?;
+        info!("Backed up {} frames to backup {}", backup_set.files.len(), backup_set.id);
     }
 
-    return problem_sprites;
+    without_watcher(&sprites_path, || {
+        animation_names
+            .iter()
+            .map(|animation_name| unpack::unpack_animation(&sprites_path, animation_name, &sprites_path, overwrite))
+            .collect::<Result<Vec<UnpackSummary>, PackerError>>()
+    })
+    .map_err(log_error)
 }
 
 /// Check for any sprites that have been changed since the application started
@@ -141,123 +430,200 @@ fn check(state: State<AppState>) -> Vec<Sprite> {
 /// # Returns
 /// * `Vec<Sprite>` A list of all changed sprites
 #[command]
-fn check_for_changed_sprites(already_changed_sprites: Vec<Sprite>, state: State<AppState>) -> Vec<Sprite> {
+fn check_for_changed_sprites(already_changed_sprites: Vec<Sprite>, state: State<AppState>) -> Result<Vec<Sprite>, PackerError> {
     let app_state = state.0.lock().expect("Failed to lock app state.");
     unsafe {
         let sprites: &mut Vec<Sprite> = &mut CHANGED_SPRITES.lock().expect("Failed to lock CHANGED_SPRITES");
-        let mut sprites: Vec<Sprite> = sprites.par_iter().map(|sprite| get_collection(
-            sprite.collection_name.clone(),
-            app_state.loaded_collections.clone())
-            .sprites.par_iter().find_first(|s| s.id == sprite.id).unwrap().clone()).collect();
+        let mut sprites = sprites.par_iter().map(|sprite| {
+            get_collection(&sprite.collection_name, &app_state.loaded_collections)?
+                .sprites.par_iter().find_first(|s| s.id == sprite.id).cloned()
+                .ok_or_else(|| log_error(PackerError::MissingSprite(sprite.path.clone())))
+        }).collect::<Result<Vec<Sprite>, PackerError>>()?;
         sprites.retain(|sprite| !already_changed_sprites.contains(sprite));
         info!("Found {} changed sprites.", sprites.len());
-        return sprites;
+        Ok(sprites)
     }
 }
 
-/// Replace all duplicate sprites in a collection
+/// Replace all duplicate sprites in a collection, backing up the overwritten frames first
 /// # Arguments
 /// * `source_sprite` - The sprite to replace duplicates with
 /// * `state` - The application state
+/// # Returns
+/// * `BackupSet` - The backup of the frames before they were replaced
 #[command]
-fn replace_duplicate_sprites(source_sprite: Sprite, state: State<AppState>) {
+fn replace_duplicate_sprites(source_sprite: Sprite, state: State<AppState>) -> Result<BackupSet, PackerError> {
     let sprites_path: PathBuf;
+    let collection: Collection;
     {
         let app_state = state.0.lock().expect("Failed to lock app state.");
         sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+        collection = get_collection(&source_sprite.collection_name, &app_state.loaded_collections)?.clone();
     }
 
+    let paths = replace::duplicate_sprite_paths(&source_sprite, &collection, &sprites_path).map_err(log_error)?;
+    let backup_set = backup_store()
+        .snapshot(&paths, &format!("Replace duplicates of {} in {}", source_sprite.name, source_sprite.collection_name))
+        .map_err(log_error)?;
+    info!("Backed up {} frames to backup {}", backup_set.files.len(), backup_set.id);
+
+    let result = without_watcher(&sprites_path, || {
+        replace::replace_duplicate_sprites(&source_sprite, &collection, &sprites_path)
+    });
+
+    result.map_err(log_error)?;
+
     unsafe {
-        match WATCHER.as_mut() {
-            Some(watcher) => watcher.unwatch(&sprites_path.clone()).expect("Failed to unwatch sprites path."),
-            None => warn!("Watcher is None."),
-        }
+        CHANGED_SPRITES.lock().expect("Failed to lock CHANGED_SPRITES")
+            .retain(|sprite| sprite.collection_name != source_sprite.collection_name || sprite.id != source_sprite.id);
     }
 
-    let source_path = if sprites_path.join(source_sprite.path.clone()).exists() {
-        sprites_path.join(source_sprite.path.clone())
-    } else if PathBuf::from(source_sprite.path.clone()).exists() {
-        PathBuf::from(source_sprite.path.clone())
-    } else {
-        log_panic!("Failed to get a valid path from source sprite at {}", source_sprite.path);
-    };
+    Ok(backup_set)
+}
 
-    let source_image = match image::open(source_path.clone()) {
-        Ok(image) => image,
-        Err(e) => log_panic!("Failed to open image at path {:?}: {}", source_path.display(), e),
-    };
+/// Compute what replacing all duplicate sprites in a collection would change, without writing any files
+/// # Arguments
+/// * `source_sprite` - The sprite to replace duplicates with
+/// * `thumbnail_size` - The size to fit the before and after images in, defaulting to 128 pixels
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<ReplacementPreview>` - The changed pixel count and before and after images of each frame that would be overwritten
+#[command]
+fn preview_replace_duplicate_sprites(
+    source_sprite: Sprite,
+    thumbnail_size: Option<u32>,
+    state: State<AppState>
+) -> Result<Vec<ReplacementPreview>, PackerError> {
+    let app_state = state.0.lock().expect("Failed to lock app state.");
+    let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+    let collection = get_collection(&source_sprite.collection_name, &app_state.loaded_collections)?;
+    replace::preview_duplicate_replacement(&source_sprite, collection, &sprites_path, Some(thumbnail_size.unwrap_or(128)))
+        .map_err(log_error)
+}
 
-    let source_image = SpriteImage {
-        sprite: source_sprite.clone(),
-        image: source_image,
-    };
+/// List the backups of frames overwritten by duplicate replacement
+/// # Returns
+/// * `Vec<BackupSet>` - The backup sets, newest first
+#[command]
+fn list_backups() -> Result<Vec<BackupSet>, PackerError> {
+    backup_store().list().map_err(log_error)
+}
 
-    let collection = get_collection(source_sprite.collection_name.clone(), state.0.lock().expect("Failed to lock app state.").loaded_collections.clone());
-    for sprite in collection.sprites {
-        if sprite.id != source_sprite.id {
-            continue;
-        }
+/// Restore the frames of a backup set, undoing the duplicate replacement it was made before
+/// # Arguments
+/// * `backup_id` - The ID of the backup set
+/// # Returns
+/// * `Vec<PathBuf>` - The paths of the restored frames
+#[command]
+fn restore_backup(backup_id: String) -> Result<Vec<PathBuf>, PackerError> {
+    let restored_paths = backup_store().restore(&backup_id).map_err(log_error)?;
+    info!("Restored {} frames from backup {}", restored_paths.len(), backup_id);
+    Ok(restored_paths)
+}
 
-        let sprite_path = if sprites_path.join(sprite.path.clone()).exists() {
-            sprites_path.join(sprite.path.clone())
-        } else if PathBuf::from(sprite.path.clone()).exists() {
-            PathBuf::from(sprite.path.clone())
-        } else {
-            log_panic!("Failed to get a valid path from sprite at {}", sprite.path);
-        };
+/// Plan how to resolve every group of differing sprites with the same ID
+/// # Arguments
+/// * `strategy` - How to resolve each group
+/// * `collection_name` - The name of the collection to resolve the sprites of, or `None` for every collection
+/// * `state` - The application state
+/// # Returns
+/// * `Vec<ResolutionPlan>` - What resolving each group would do
+#[command]
+fn plan_conflict_resolutions(
+    strategy: ResolveStrategy,
+    collection_name: Option<String>,
+    state: State<AppState>
+) -> Result<Vec<ResolutionPlan>, PackerError> {
+    let app_state = state.0.lock().expect("Failed to lock app state.");
+    let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+    let collections = match collection_name {
+        Some(collection_name) => vec![get_collection(&collection_name, &app_state.loaded_collections)?.clone()],
+        None => app_state.loaded_collections.clone(),
+    };
+    let changed_sprites = unsafe { CHANGED_SPRITES.lock().expect("Failed to lock CHANGED_SPRITES").clone() };
+    resolve::plan_resolutions(&collections, &sprites_path, strategy, &changed_sprites, app_state.settings.comparison_mode)
+        .map_err(log_error)
+}
 
-        let sprite_image = match image::open(sprite_path.clone()) {
-            Ok(image) => image,
-            Err(e) => log_panic!("Failed to open image at path {:?}: {}", sprite_path.display(), e),
-        };
+/// Resolve a group of differing sprites with the same ID, backing up the overwritten frames first
+/// # Arguments
+/// * `strategy` - How to resolve the group
+/// * `collection_name` - The name of the collection containing the sprites
+/// * `sprite_id` - The ID of the sprites
+/// * `state` - The application state
+/// # Returns
+/// * `BackupSet` - The backup of the frames before they were overwritten
+#[command]
+fn apply_conflict_resolution(
+    strategy: ResolveStrategy,
+    collection_name: String,
+    sprite_id: u32,
+    state: State<AppState>
+) -> Result<BackupSet, PackerError> {
+    let sprites_path: PathBuf;
+    let collection: Collection;
+    {
+        let app_state = state.0.lock().expect("Failed to lock app state.");
+        sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
+        collection = get_collection(&collection_name, &app_state.loaded_collections)?.clone();
+    }
 
-        let mut sprite_image = SpriteImage {
-            sprite: sprite.clone(),
-            image: sprite_image,
-        };
+    let group = collection.sprites.iter().filter(|sprite| sprite.id == sprite_id).cloned().collect::<Vec<Sprite>>();
+    if group.is_empty() {
+        return Err(log_error(PackerError::MissingSprite(format!("{} in collection {}", sprite_id, collection_name))));
+    }
+    let changed_sprites = unsafe { CHANGED_SPRITES.lock().expect("Failed to lock CHANGED_SPRITES").clone() };
+    let plan = resolve::plan_resolution(&group, &collection, &sprites_path, strategy, &changed_sprites).map_err(log_error)?;
 
-        replace_sprite(source_image.clone(), &mut sprite_image);
+    let backup_set = backup_store()
+        .snapshot(&plan.targets, &format!("Resolve sprite {} in {} with {:?}", sprite_id, collection_name, strategy))
+        .map_err(log_error)?;
 
-        match sprite_image.image.save(sprite_path.clone()) {
-            Ok(_) => info!("Replaced sprite at path {:?} with sprite at path {:?}.", sprite_path.display(), source_path.display()),
-            Err(e) => log_panic!("Failed to save image at path {:?}: {}", sprite_path.display(), e),
-        }
-    }
+    without_watcher(&sprites_path, || resolve::apply_resolution(&plan, &collection, &sprites_path)).map_err(log_error)?;
 
     unsafe {
         CHANGED_SPRITES.lock().expect("Failed to lock CHANGED_SPRITES")
-            .retain(|sprite| sprite.collection_name != source_sprite.collection_name && sprite.id != source_sprite.id);
+            .retain(|sprite| sprite.collection_name != collection_name || sprite.id != sprite_id);
     }
 
+    Ok(backup_set)
+}
+
+/// Run a function that writes frame files without the watcher marking them as changed
+/// # Arguments
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// * `f` - The function to run
+/// # Returns
+/// * `T` - The function's result
+fn without_watcher<T>(sprites_path: &Path, f: impl FnOnce() -> T) -> T {
+    unsafe {
+        match WATCHER.as_mut() {
+            Some(watcher) => watcher.unwatch(sprites_path).expect("Failed to unwatch sprites path."),
+            None => warn!("Watcher is None."),
+        }
+    }
+
+    let result = f();
+
     unsafe {
         match WATCHER.as_mut() {
-            Some(watcher) => watcher.watch(&sprites_path.clone(), RecursiveMode::Recursive).expect("Failed to unwatch sprites path."),
+            Some(watcher) => watcher.watch(sprites_path, RecursiveMode::Recursive).expect("Failed to watch sprites path."),
             None => warn!("Watcher is None."),
         }
     }
+
+    result
 }
 
-/// Replace a sprite with another sprite
-/// # Arguments
-/// * `source_image` - The sprite to replace with
-/// * `target_image` - The sprite to replace
-fn replace_sprite(source_image: SpriteImage, target_image: &mut SpriteImage) {
-    let target_ptr = Arc::new(Mutex::new(target_image));
-    let sub_image = source_image.trim();
-    (0..sub_image.width()).into_par_iter().for_each(|x| {
-        (0..sub_image.height()).into_par_iter().for_each(|y| {
-            let source_y = sub_image.height() - y - 1;
-            let pixel = sub_image.get_pixel(x, source_y);
-            match target_ptr.lock() {
-                Ok(mut target_image) => {
-                    let target_x = x + target_image.sprite.xr as u32;
-                    let target_y = target_image.image.height() - (y + target_image.sprite.yr as u32) - 1;
-                    target_image.image.put_pixel(target_x, target_y, pixel);
-                },
-                Err(e) => log_panic!("Failed to lock target image mutex: {}", e),
-            }
-        });
-    });
+/// Get the store that frames are backed up to before being overwritten, in the settings folder
+fn backup_store() -> BackupStore {
+    match confy::get_configuration_file_path(APP_NAME, APP_NAME) {
+        Ok(settings_path) => match settings_path.parent() {
+            Some(settings_dir) => BackupStore::new(settings_dir.join("backups")),
+            None => log_panic!("Failed to get parent of settings path: {}", settings_path.display()),
+        },
+        Err(e) => log_panic!("Failed to get settings path: {}", e),
+    }
 }
 
 /// Get a collection by its name
@@ -265,12 +631,19 @@ fn replace_sprite(source_image: SpriteImage, target_image: &mut SpriteImage) {
 /// * `collection_name` - The name of the collection
 /// * `collections` - A list of collections to search throuhg
 /// # Returns
-/// *`Collection` The collection with the given name
-fn get_collection(collection_name: String, collections: Vec<Collection>) -> Collection {
-    return match collections.par_iter().find_first(|cln| cln.name == collection_name) {
-        Some(collection) => collection.clone(),
-        None => log_panic!("Failed to find collection: {}", collection_name),
-    };
+/// *`&Collection` The collection with the given name
+fn get_collection<'a>(collection_name: &str, collections: &'a [Collection]) -> Result<&'a Collection, PackerError> {
+    packer::get_collection(collection_name, collections).map_err(log_error)
+}
+
+/// Log an error before it is returned to the frontend
+/// # Arguments
+/// * `e` - The error to log
+/// # Returns
+/// * `PackerError` - The same error
+fn log_error(e: PackerError) -> PackerError {
+    error!("{}", e);
+    e
 }
 
 fn main() {
@@ -306,22 +679,44 @@ fn setup_app() {
             _ => {}
         })
         .invoke_handler(tauri::generate_handler![
+            apply_conflict_resolution,
             cancel_pack,
             check,
             check_for_changed_sprites,
             debug,
+            diff_duplicate_sprites,
+            export_clip_preview,
+            export_clip_sheet,
+            find_twin_sprites,
             get_animation,
             get_animation_name_from_collection_name,
             get_animation_list,
             get_collection_from_sprite_name,
             get_collections_from_animation_name,
+            get_comparison_mode,
+            get_edge_settings,
             get_language,
+            get_load_report,
+            get_output_settings,
             get_sprites_path,
             get_mode,
+            lint_bleeding_sprites,
+            list_backups,
+            pack_collections,
             pack_single_collection,
+            plan_conflict_resolutions,
+            preview_replace_duplicate_sprites,
+            propagate_to_twins,
+            repack_collection,
             replace_duplicate_sprites,
+            restore_backup,
+            set_comparison_mode,
+            set_edge_options,
             set_language,
-            set_mode
+            set_mode,
+            set_output_settings,
+            unpack_frames,
+            verify_collections
         ])
         .build(tauri::generate_context!())
         .expect("Failed to build tauri application.");
@@ -356,11 +751,16 @@ fn setup_app() {
 
             info!("Sprites path: {:?}", state.0.lock().expect("Failed to lock app_state").settings.sprites_path);
 
-            load_collections_and_animations(&state);
+            if let Err(e) = load_collections_and_animations(&state) {
+                if let Some(window) = app_handle.get_window("main") {
+                    window.emit("packerError", ErrorPayload::from(&e)).expect("Failed to emit packerError event");
+                }
+            }
 
             let sprites_path = state.0.lock().expect("Failed to lock app_state").settings.sprites_path.clone();
+            let app_handle = app_handle.clone();
             async_runtime::spawn(async move {
-                start_watcher(sprites_path);
+                start_watcher(app_handle, sprites_path);
             });
         },
         ExitRequested { api, .. } => {
@@ -376,206 +776,18 @@ fn setup_app() {
     });
 }
 
-/// Load collections and animations from sprite files on disk
+/// Load collections and animations from sprite files on disk, skipping broken animation folders
 /// # Arguments
 /// * `state` - The application state
-fn load_collections_and_animations(state: &AppState) {
+fn load_collections_and_animations(state: &AppState) -> Result<(), PackerError> {
     let mut app_state = state.0.lock().expect("Failed to lock app state");
     let sprites_path = PathBuf::from(app_state.settings.sprites_path.clone());
-    match fs::read_dir(sprites_path.clone()) {
-        Ok(anim_paths) => {
-            for anim_path in anim_paths {
-                match anim_path {
-                    Ok(anim_path) => {
-                        if !anim_path.path().is_dir() {
-                            return;
-                        }
-                        let sprite_info_path =
-                            anim_path.path().join("0.Atlases").join("SpriteInfo.json");
-                        match fs::read_to_string(sprite_info_path) {
-                            Ok(text) => {
-                                let sprite_info: SpriteInfo = match serde_json::from_str(&text) {
-                                    Ok(info) => info,
-                                    Err(e) => log_panic!("Failed to parse SpriteInfo.json: {}", e),
-                                };
-
-                                for i in 0..sprite_info.id.len() {
-                                    match sprite_info.at(i) {
-                                        Some(sprite) => {
-                                            if !sprites_path.join(sprite.path.clone()).exists() && !PathBuf::from(sprite.path.clone()).exists() {
-                                                continue;
-                                            }
-
-                                            match app_state.loaded_collections.iter().find(|cln| cln.name == sprite.collection_name) {
-                                                Some(collection) => {
-                                                    let mut collection = collection.clone();
-                                                    collection.sprites.push(sprite.clone());
-                                                    app_state.loaded_collections.retain(|cln| cln.name != collection.name);
-                                                    app_state.loaded_collections.push(collection);
-                                                }
-                                                None => {
-                                                    let collection_name = sprite.clone().collection_name;
-                                                    let mut cln = Collection {
-                                                        name: collection_name.clone(),
-                                                        path: anim_path.path().join("0.Atlases").join(format!("{}.png", collection_name)),
-                                                        sprites: Vec::new(),
-                                                    };
-                                                    cln.sprites.push(sprite);
-                                                    app_state.loaded_collections.push(cln);
-                                                }
-                                            }
-                                        }
-                                        None => log_panic!("Failed to get sprite at index {}.", i),
-                                    }
-                                }
-
-                                let clips = Mutex::new(Vec::new());
-                                match fs::read_dir(anim_path.path()) {
-                                    Ok(clip_paths) => {
-                                        clip_paths.into_iter().par_bridge().for_each(|clip_path| {
-                                            match clip_path {
-                                                Ok(clip_path) => {
-                                                    if !clip_path.path().is_dir() {
-                                                        return;
-                                                    }
-                                                    match clip_path.path().file_name() {
-                                                        Some(file_name) => {
-                                                            if file_name == "0.Atlases" {
-                                                                return;
-                                                            }
-                                                        },
-                                                        None => log_panic!("Failed to get file name of clip path {:?}", clip_path.path().display()),
-                                                    }
-                                                    let frames = Mutex::new(Vec::new());
-                                                    let fps: Mutex<f32> = Mutex::new(12.0);
-                                                    let loop_start = Mutex::new(0);
-                                                    match fs::read_dir(clip_path.path()) {
-                                                        Ok(frame_paths) => {
-                                                            frame_paths.into_iter().par_bridge().for_each(|frame_path| {
-                                                                match frame_path {
-                                                                    Ok(frame_path) => {
-                                                                        if frame_path.file_name() == "AnimInfo.json" {
-                                                                            match fs::read_to_string(frame_path.path()) {
-                                                                                Ok(text) => {
-                                                                                    let anim_info: AnimInfo = match serde_json::from_str(&text) {
-                                                                                        Ok(anim_info) => anim_info,
-                                                                                        Err(e) => AnimInfo {
-                                                                                            fps: 12.0,
-                                                                                            loop_start: 0,
-                                                                                            num_frames: 0,
-                                                                                            collection_name: "".to_string(),
-                                                                                        },
-                                                                                    };
-                                                                                    match fps.lock() {
-                                                                                        Ok(mut fps) => *fps = anim_info.fps,
-                                                                                        Err(e) => log_panic!("Failed to lock fps: {}", e),
-                                                                                    }
-                                                                                    match loop_start.lock() {
-                                                                                        Ok(mut loop_start) => *loop_start = anim_info.loop_start,
-                                                                                        Err(e) => log_panic!("Failed to lock loop_start: {}", e),
-                                                                                    }
-                                                                                },
-                                                                                Err(e) => log_panic!("Failed to read AnimInfo.json: {}", e),
-                                                                            }
-                                                                            return;
-                                                                        } else if frame_path.path().extension().expect("Failed to get extension of frame path.") != "png" {
-                                                                            return;
-                                                                        }
-
-                                                                        let sprite = match sprite_info.path.par_iter().position_first(|path| frame_path.path().ends_with(path)) {
-                                                                            Some(index) => {
-                                                                                match sprite_info.at(index) {
-                                                                                    Some(sprite) => sprite,
-                                                                                    None => log_panic!("Failed to get sprite at index {}", index),
-                                                                                }
-                                                                            },
-                                                                            None => log_panic!("Failed to find sprite with path {:?}", frame_path.path().display()),
-                                                                        };
-
-                                                                        match frames.lock() {
-                                                                            Ok(mut frames) => frames.push(sprite),
-                                                                            Err(e) => log_panic!("Failed to lock frames: {}", e),
-                                                                        }
-                                                                    }
-                                                                    Err(e) => log_panic!("Failed to get frame path: {}", e),
-                                                                }
-                                                            });
-                                                        }
-                                                        Err(e) => log_panic!(
-                                                            "Failed to read clip directory {:?}: {}",
-                                                            clip_path.path().display(), e
-                                                        ),
-                                                    }
-
-                                                    match clip_path.file_name().to_str() {
-                                                        Some(clip_name) => {
-                                                            let frames = match frames.lock() {
-                                                                Ok(frames) => frames.clone(),
-                                                                Err(e) => log_panic!("Failed to lock frames: {}", e),
-                                                            };
-                                                            let fps = match fps.lock() {
-                                                                Ok(fps) => *fps,
-                                                                Err(e) => log_panic!("Failed to lock fps: {}", e),
-                                                            };
-                                                            let loop_start = match loop_start.lock() {
-                                                                Ok(loop_start) => *loop_start,
-                                                                Err(e) => log_panic!("Failed to lock loop_start: {}", e),
-                                                            };
-                                                            match clips.lock() {
-                                                                Ok(mut clips) => clips.push(Clip::new(
-                                                                    clip_name.to_string(),
-                                                                    frames,
-                                                                    fps,
-                                                                    loop_start,
-                                                                )),
-                                                                Err(e) => log_panic!("Failed to lock clips: {}", e),
-                                                            }
-                                                        }
-                                                        None => log_panic!("Failed to get clip name."),
-                                                    }
-                                                }
-                                                Err(e) => log_panic!(
-                                                    "Failed to get entry from {:?}: {}",
-                                                    anim_path.path().display(), e
-                                                ),
-                                            }
-                                        });
-                                    }
-                                    Err(e) => {
-                                        log_panic!("Failed to read anim directory {:?}: {}", anim_path.path().display(), e)
-                                    }
-                                }
-
-                                let anim_file = anim_path.file_name();
-                                let animation_name = match anim_file.to_str() {
-                                    Some(name) => name,
-                                    None => log_panic!("Failed to get animation name."),
-                                };
-
-                                let mut clips = match clips.lock() {
-                                    Ok(clips) => clips.clone(),
-                                    Err(e) => log_panic!("Failed to lock clips: {}", e),
-                                };
-
-                                clips.par_sort();
-
-                                app_state.loaded_animations.push(Animation {
-                                    name: animation_name.to_string(),
-                                    clips,
-                                });
-                            }
-                            Err(e) => log_panic!("Failed to read SpriteInfo.json: {}", e),
-                        }
-                    }
-                    Err(e) => log_panic!("Error while iterating path: {}", e),
-                }
-            }
-        }
-        Err(e) => log_panic!("Failed to read sprites directory {}: {}", app_state.settings.sprites_path.clone(), e),
-    }
-
-    app_state.loaded_collections.par_sort();
-    app_state.loaded_animations.par_sort();
+    let (collections, animations, load_report) = load::load_collections_and_animations_with_report(&sprites_path)
+        .map_err(log_error)?;
+    app_state.loaded_collections = collections;
+    app_state.loaded_animations = animations;
+    app_state.load_report = load_report;
+    Ok(())
 }
 
 /// Packs a collection of sprites into an atlas
@@ -583,118 +795,171 @@ fn load_collections_and_animations(state: &AppState) {
 /// * `collection` - The collection to pack
 /// * `window` - The window to send events to
 /// * `sprites_path` - The path to sprite files
+/// * `output` - Where to write the atlas, or `None` to choose with a save dialog
+/// * `format` - The pixel format to write the atlas in
+/// * `textures` - The compressed textures to write next to the atlas
+/// * `edge_options` - How to treat the edges of the sprites in the atlas
+/// * `incremental` - Whether to only repack sprites that changed since the collection was last packed
 async fn pack_collection(
     collection: Collection,
     window: Window,
-    sprites_path: String
+    sprites_path: String,
+    output: Option<AtlasOutput>,
+    format: AtlasFormat,
+    textures: TextureOptions,
+    edge_options: EdgeOptions,
+    incremental: bool
 ) {
     let start = Instant::now();
-    let running_task = Mutex::new(true);
-    let atlas = match image::open(collection.path.clone()) {
-        Ok(atlas) => atlas,
-        Err(e) => log_panic!("Failed to open atlas file: {}", e),
+    let previous_atlas_path = window.state::<AppState>().0.lock().expect("Failed to lock app state")
+        .packed_atlas_paths.get(&collection.name).cloned()
+        .or_else(|| output.as_ref().and_then(|output| output.previous_atlas_path(&collection)));
+    let on_progress = |progress| window.emit("progress", ProgressPayload { progress })
+        .expect("Failed to emit progress event");
+    let is_cancelled = || unsafe {
+        match RX_PROGRESS.lock().expect("Failed to lock rx").as_ref() {
+            Some(rx) => rx.try_recv().is_ok(),
+            None => log_panic!("RX_PROGRESS is None"),
+        }
+    };
+    let gen_atlas = if incremental {
+        incremental::pack_collection_incremental(
+            &collection,
+            Path::new(&sprites_path),
+            previous_atlas_path.as_deref(),
+            format,
+            on_progress,
+            is_cancelled,
+        ).map(|pack| pack.map(|pack| (pack.atlas, Some(pack.manifest), pack.repacked)))
+    } else {
+        pack::pack_collection(&collection, Path::new(&sprites_path), format, on_progress, is_cancelled)
+            .map(|atlas| atlas.map(|atlas| (atlas, None, collection.sprites.clone())))
     };
-    let sprite_num = Mutex::new(0 as usize);
-    let atlas_width = atlas.width() as i32;
-    let atlas_height = atlas.height() as i32;
-    let gen_atlas = Mutex::new(atlas);
-    collection.sprites.par_iter().try_for_each(|sprite| {
-        let frame_path = match PathBuf::from_str(sprites_path.as_str()) {
-            Ok(path) => path.join(sprite.path.clone()),
-            Err(e) => log_panic!("Failed to create frame path from string: {}", e),
-        };
-        let frame_image = match image::open(frame_path.clone()) {
-            Ok(image) => image,
-            Err(e) => log_panic!("Failed to open frame image at {:?}: {}", frame_path.display(), e),
-        };
 
-        (0..frame_image.width()).into_par_iter().try_for_each(|i| {
-            (0..frame_image.height()).into_par_iter().try_for_each(|j| {
-                let i = i as i32;
-                let j = j as i32;
-                let x = if sprite.flipped {
-                    sprite.x + j - sprite.yr
-                } else {
-                    sprite.x + i - sprite.xr
-                };
-                let y = if sprite.flipped {
-                    atlas_height - (sprite.y + i) - 1 + sprite.xr
-                } else {
-                    atlas_height - (sprite.y + j) - 1 + sprite.yr
-                };
-                if i >= sprite.xr && i < (sprite.xr + sprite.width)
-                    && j >= sprite.yr && j < (sprite.yr + sprite.height)
-                    && x >= 0 && x < atlas_width as i32 && y >= 0 && y < atlas_height as i32
-                {
-                    match gen_atlas.lock() {
-                        Ok(mut atlas) => {
-                            atlas.put_pixel(
-                                x as u32,
-                                y as u32,
-                                frame_image.get_pixel(i as u32, (frame_image.height() as i32 - j - 1) as u32),
-                            );
-                        }
-                        Err(e) => log_panic!("Failed to lock generated atlas: {}", e),
-                    }
-                }
+    let (mut gen_atlas, manifest, packed_sprites) = match gen_atlas {
+        Ok(Some(atlas)) => atlas,
+        Ok(None) => return,
+        Err(e) => {
+            window.emit("packerError", ErrorPayload::from(&log_error(e))).expect("Failed to emit packerError event");
+            window.emit("enablePack", ()).expect("Failed to emit enablePack event");
+            return;
+        }
+    };
 
-                unsafe {
-                    match RX_PROGRESS.lock().expect("Failed to lock rx").as_ref() {
-                        Some(rx) => {
-                            match rx.try_recv() {
-                                Ok(_) => {
-                                    *running_task.lock().expect("Failed to lock running_task") = false;
-                                    return ControlFlow::Break(());
-                                },
-                                Err(_) => {}
-                            }
-                        }
-                        None => log_panic!("RX_PROGRESS is None"),
-                    }
-                }
+    match format::format_warnings(&packed_sprites, Path::new(&sprites_path), gen_atlas.color()) {
+        Ok(warnings) => for warning in warnings {
+            warn!("Frame {:?} loses {:?} in the atlas's {:?} pixel format", warning.sprite.path, warning.losses, gen_atlas.color());
+        },
+        Err(e) => warn!("Failed to check the pixel formats of the frames: {}", e),
+    }
+    for violation in edges::apply_edges(&mut gen_atlas, &collection.sprites, &edge_options) {
+        warn!("Sprite {:?} has {} painted pixels within its padding", violation.sprite.path, violation.pixels);
+    }
 
-                ControlFlow::Continue(())
-            });
+    let stop = Instant::now();
+    info!("Time to pack collection {:?}: {} ms", collection.name, stop.duration_since(start).as_millis());
 
-            if *running_task.lock().expect("Failed to lock running_task") == false {
-                return ControlFlow::Break(());
+    let atlas_path = match &output {
+        Some(output) => match output.prepare(&collection) {
+            Ok((atlas_path, backup_path)) => {
+                if let Some(backup_path) = backup_path {
+                    info!("Backed up atlas {:?} to {:?}", atlas_path.display(), backup_path.display());
+                }
+                Some(atlas_path)
             }
-
-            ControlFlow::Continue(())
-        });
-
-        match sprite_num.lock() {
-            Ok(mut num) => {
-                *num += 1;
-                window.emit("progress", ProgressPayload { progress: *num * 100 / collection.sprites.len() })
-                    .expect("Failed to emit progress event");
+            Err(e) => {
+                window.emit("packerError", ErrorPayload::from(&log_error(e))).expect("Failed to emit packerError event");
+                None
             }
-            Err(e) => log_panic!("Failed to lock sprite_num: {}", e),
+        },
+        None => {
+            let save_directory = previous_atlas_path
+                .as_ref()
+                .and_then(|path| path.parent())
+                .map_or_else(|| PathBuf::from(&sprites_path), Path::to_path_buf);
+            let atlas_path = FileDialogBuilder::new()
+                .set_directory(&save_directory)
+                .set_file_name(format!("{}.png", collection.name.clone()).as_str())
+                .add_filter("PNG Image", &["png"])
+                .save_file();
+            if atlas_path.is_none() {
+                warn!("Generated atlas not saved.");
+            }
+            atlas_path
         }
+    };
 
-        if *running_task.lock().expect("Failed to lock running_task") == false {
-            return ControlFlow::Break(());
+    if let Some(atlas_path) = atlas_path {
+        let saved = gen_atlas.save(&atlas_path).map_err(|e| PackerError::image(&atlas_path, e))
+            .and_then(|_| manifest.map_or(Ok(()), |manifest| manifest.save(&atlas_path)))
+            .and_then(|_| texture::write_textures(&gen_atlas, &atlas_path, &textures));
+        match saved {
+            Ok(texture_paths) => {
+                info!("Saved atlas of collection {:?} to {:?}", collection.name, atlas_path.display());
+                for texture_path in texture_paths {
+                    info!("Saved texture of collection {:?} to {:?}", collection.name, texture_path.display());
+                }
+                window.state::<AppState>().0.lock().expect("Failed to lock app state")
+                    .packed_atlas_paths.insert(collection.name.clone(), atlas_path);
+            }
+            Err(e) => window.emit("packerError", ErrorPayload::from(&log_error(e)))
+                .expect("Failed to emit packerError event"),
         }
-
-        ControlFlow::Continue(())
-    });
-
-    if *running_task.lock().expect("Failed to lock running_task") == false {
-        return;
     }
 
-    let stop = Instant::now();
-    info!("Time to pack collection {:?}: {} ms", collection.name, stop.duration_since(start).as_millis());
+    window.emit("enablePack", ()).expect("Failed to emit enablePack event");
+}
 
-    match FileDialogBuilder::new()
-        .set_directory(&sprites_path)
-        .set_file_name(format!("{}.png", collection.name.clone()).as_str())
-        .add_filter("PNG Image", &["png"])
-        .save_file() {
-            Some(atlas_path) => gen_atlas.lock().expect("Failed to lock generated atlas")
-                .save(atlas_path.clone()).expect("Failed to save atlas."),
-            None => warn!("Generated atlas not saved.")
+/// Packs several collections concurrently and writes their atlases
+/// # Arguments
+/// * `collections` - The collections to pack
+/// * `window` - The window to send events to
+/// * `sprites_path` - The path to sprite files
+/// * `output` - Where to write the atlases
+/// * `edges` - How to treat the edges of the sprites in each collection's atlas
+/// * `incremental` - Whether to only repack sprites that changed since the atlases at the output paths were generated
+async fn pack_collection_batch(
+    collections: Vec<Collection>,
+    window: Window,
+    sprites_path: String,
+    output: AtlasOutput,
+    edges: EdgeSettings,
+    incremental: bool
+) {
+    let summary = batch::pack_collections(
+        &collections,
+        Path::new(&sprites_path),
+        &output,
+        &edges,
+        incremental,
+        |progress| window.emit("batchProgress", progress)
+            .expect("Failed to emit batchProgress event"),
+        || unsafe {
+            match RX_PROGRESS.lock().expect("Failed to lock rx").as_ref() {
+                Some(rx) => rx.try_recv().is_ok(),
+                None => log_panic!("RX_PROGRESS is None"),
+            }
+        },
+    );
+
+    match summary {
+        Ok(summary) => {
+            info!("Packed {} of {} collections in {} ms", summary.packed.len(), collections.len(), summary.millis);
+            for failure in &summary.failed {
+                error!("Failed to pack collection {:?}: {}", failure.collection_name, failure.error);
+            }
+            {
+                let state = window.state::<AppState>();
+                let mut app_state = state.0.lock().expect("Failed to lock app state");
+                for packed in &summary.packed {
+                    app_state.packed_atlas_paths.insert(packed.collection_name.clone(), packed.path.clone());
+                }
+            }
+            window.emit("batchSummary", summary).expect("Failed to emit batchSummary event");
         }
+        Err(e) => window.emit("packerError", ErrorPayload::from(&log_error(e)))
+            .expect("Failed to emit packerError event"),
+    }
 
     window.emit("enablePack", ()).expect("Failed to emit enablePack event");
 }
@@ -723,8 +988,9 @@ fn select_sprites_path(state: &AppState) {
 
 /// Begin watching sprites path for changes
 /// # Arguments
-/// * `state` - The application state
-fn start_watcher(sprites_path: String) {
+/// * `app_handle` - The application handle
+/// * `sprites_path` - The path to the folder containing the animation folders
+fn start_watcher(app_handle: AppHandle, sprites_path: String) {
     let (tx_watcher, rx_watcher) = mpsc::channel();
     let config = notify::Config::default().with_compare_contents(true).with_poll_interval(std::time::Duration::from_secs(1));
 
@@ -757,19 +1023,18 @@ fn start_watcher(sprites_path: String) {
                                             let path = path.strip_prefix(sprites_path.clone()).expect("Failed to strip prefix from path.");
                                             let path_string = match path.to_str() {
                                                 Some(str) => String::from(str),
-                                                None => log_panic!("Failed to convert path to string."),
+                                                None => continue,
                                             };
                                             let paths = path_string.split(['/', '\\']).collect::<Vec<&str>>();
-                                            let sprite_info_path = PathBuf::from(sprites_path.clone()).join(paths[0]).join("0.Atlases").join("SpriteInfo.json");
-                                            let collection_name = match fs::read_to_string(sprite_info_path) {
-                                                Ok(text) => {
-                                                    let sprite_info: SpriteInfo = match serde_json::from_str(&text) {
-                                                        Ok(info) => info,
-                                                        Err(e) => log_panic!("Failed to parse SpriteInfo.json: {}", e),
-                                                    };
-                                                    sprite_info.collection_name[0].to_string()
+                                            let collection_name = match load::load_sprite_info(&PathBuf::from(sprites_path.clone()).join(paths[0])) {
+                                                Ok(sprite_info) => match sprite_info.collection_name.first() {
+                                                    Some(collection_name) => collection_name.to_string(),
+                                                    None => continue,
                                                 },
-                                                Err(e) => log_panic!("Failed to read SpriteInfo.json: {}", e),
+                                                Err(e) => {
+                                                    warn!("{}", e);
+                                                    continue;
+                                                }
                                             };
                                             if paths.len() < 3 {
                                                 continue;
@@ -779,11 +1044,17 @@ fn start_watcher(sprites_path: String) {
                                             if sprite_data.len() < 3 {
                                                 continue;
                                             }
-                                            let sprite_id_string = sprite_data[sprite_data.len() - 1].replace(".png", "");
-                                            let sprite_id = match sprite_id_string.parse::<u32>() {
+                                            let sprite_id = match parse_sprite_id(&sprite_name) {
                                                 Ok(id) => id,
-                                                Err(e) => log_panic!("Failed to parse sprite id {}: {}",sprite_id_string, e),
+                                                Err(e) => {
+                                                    warn!("{}", e);
+                                                    continue;
+                                                }
                                             };
+                                            if !differs_from_duplicates(&app_handle, &collection_name, path, &sprites_path) {
+                                                info!("Changed sprite {:?} still matches its duplicates.", path_string);
+                                                continue;
+                                            }
                                             let sprite = Sprite {
                                                 id: sprite_id,
                                                 name: sprite_name.to_string(),
@@ -820,6 +1091,34 @@ fn start_watcher(sprites_path: String) {
     }
 }
 
+/// Check whether a changed frame file no longer matches the other frames with the same sprite ID
+/// # Arguments
+/// * `app_handle` - The application handle
+/// * `collection_name` - The name of the collection containing the frame
+/// * `path` - The path to the frame file, relative to the sprites path
+/// * `sprites_path` - The path to the folder containing the animation folders
+/// # Returns
+/// * `bool` - Whether the frame differs under the configured comparison mode, or `true` if it could not be compared
+fn differs_from_duplicates(app_handle: &AppHandle, collection_name: &str, path: &Path, sprites_path: &str) -> bool {
+    let state = app_handle.state::<AppState>();
+    let (collection, mode) = {
+        let app_state = state.0.lock().expect("Failed to lock app state");
+        match app_state.loaded_collections.iter().find(|collection| collection.name == collection_name) {
+            Some(collection) => (collection.clone(), app_state.settings.comparison_mode),
+            None => return true,
+        }
+    };
+    let sprite = match collection.sprites.iter().find(|sprite| Path::new(&sprite.path) == path) {
+        Some(sprite) => sprite,
+        None => return true,
+    };
+
+    checker::differs_from_duplicates(sprite, &collection, Path::new(sprites_path), mode).unwrap_or_else(|e| {
+        warn!("{}", e);
+        true
+    })
+}
+
 /// Cancel the currently running pack task
 #[command]
 fn cancel_pack() {
@@ -846,27 +1145,10 @@ fn debug(msg: String) {
 /// # Returns
 /// * `Vec<Collection>` - The collections used by the animation
 #[command]
-fn get_collections_from_animation_name(animation_name: String, state: State<AppState>) -> Vec<Collection> {
-    let animation = get_animation(animation_name.clone(), state.clone());
-    let mut collection_names = animation
-        .clips
-        .par_iter()
-        .map(|clip| {
-            clip.frames
-                .par_iter()
-                .map(|frame| frame.collection_name.clone())
-        })
-        .flatten()
-        .collect::<Vec<String>>();
-    collection_names.par_sort();
-    collection_names.dedup();
+fn get_collections_from_animation_name(animation_name: String, state: State<AppState>) -> Result<Vec<Collection>, PackerError> {
+    let animation = get_animation(animation_name, state.clone())?;
     let app_state = state.0.lock().expect("Failed to lock app state");
-    let collections = collection_names
-        .par_iter()
-        .map(|collection_name| get_collection(collection_name.clone(), app_state.loaded_collections.clone()))
-        .collect::<Vec<Collection>>();
-
-    return collections;
+    packer::get_animation_collections(&animation, &app_state.loaded_collections).map_err(log_error)
 }
 
 /// Get an animation by its name
@@ -876,15 +1158,15 @@ fn get_collections_from_animation_name(animation_name: String, state: State<AppS
 /// # Returns
 /// * `Animation` - The returned animation
 #[command]
-fn get_animation(animation_name: String, state: State<AppState>) -> Animation {
+fn get_animation(animation_name: String, state: State<AppState>) -> Result<Animation, PackerError> {
     let app_state = state.0.lock().expect("Failed to lock app state");
     match app_state
         .loaded_animations
         .par_iter()
         .find_first(|anim| anim.name == animation_name)
     {
-        Some(animation) => animation.clone(),
-        None => log_panic!("Failed to find animation with name: {}", animation_name),
+        Some(animation) => Ok(animation.clone()),
+        None => Err(log_error(PackerError::MissingAnimation(animation_name))),
     }
 }
 
@@ -895,7 +1177,7 @@ fn get_animation(animation_name: String, state: State<AppState>) -> Animation {
 /// # Returns
 /// * `String` - The returned name of the animation
 #[command]
-fn get_animation_name_from_collection_name(collection_name: String, state: State<AppState>) -> String {
+fn get_animation_name_from_collection_name(collection_name: String, state: State<AppState>) -> Result<String, PackerError> {
     let app_state = state.0.lock().expect("Failed to lock app state");
     let animation = match app_state.loaded_animations.par_iter().find_map_first(|anim| {
         anim.clips.par_iter().find_map_first(|clip| {
@@ -909,10 +1191,10 @@ fn get_animation_name_from_collection_name(collection_name: String, state: State
         })
     }) {
         Some(anim) => anim.clone(),
-        None => log_panic!("Failed to find animation from collection name {:?}", collection_name),
+        None => return Err(log_error(PackerError::MissingCollection(collection_name))),
     };
 
-    animation.name
+    Ok(animation.name)
 }
 
 #[command]
@@ -922,7 +1204,7 @@ fn get_animation_name_from_collection_name(collection_name: String, state: State
 /// * `state` - The application state
 /// # Returns
 /// * `Collection` - The found collection
-fn get_collection_from_sprite_name(sprite_name: String, state: State<AppState>) -> Collection {
+fn get_collection_from_sprite_name(sprite_name: String, state: State<AppState>) -> Result<Collection, PackerError> {
     let app_state = state.0.lock().expect("Failed to lock app state");
     let collection = match app_state.loaded_collections.par_iter().find_map_first(|collection| {
         collection.sprites.par_iter().find_map_first(|sprite| {
@@ -934,10 +1216,10 @@ fn get_collection_from_sprite_name(sprite_name: String, state: State<AppState>)
         })
     }) {
         Some(collection) => collection.clone(),
-        None => log_panic!("Failed to find collection from sprite name {:?}", sprite_name),
+        None => return Err(log_error(PackerError::MissingSprite(sprite_name))),
     };
 
-    collection
+    Ok(collection)
 }
 
 /// Get a list of animation names
@@ -973,6 +1255,50 @@ fn get_language(state: State<AppState>) -> String {
     app_state.settings.language.clone()
 }
 
+/// Get the problems found while loading the sprites folder
+/// # Arguments
+/// * `state` - The application state
+/// # Returns
+/// * `LoadReport` - The skipped animations and missing or orphan frames of each animation
+#[command]
+fn get_load_report(state: State<AppState>) -> LoadReport {
+    let app_state = state.0.lock().expect("Failed to lock app state");
+    app_state.load_report.clone()
+}
+
+/// Get where generated atlases are written
+/// # Arguments
+/// * `state` - The application state
+/// # Returns
+/// * `OutputSettings` - The output settings
+#[command]
+fn get_output_settings(state: State<AppState>) -> OutputSettings {
+    let app_state = state.0.lock().expect("Failed to lock app state");
+    app_state.settings.output.clone()
+}
+
+/// Get how the edges of sprites are treated when packing
+/// # Arguments
+/// * `state` - The application state
+/// # Returns
+/// * `EdgeSettings` - The edge options for every collection and for specific collections
+#[command]
+fn get_edge_settings(state: State<AppState>) -> EdgeSettings {
+    let app_state = state.0.lock().expect("Failed to lock app state");
+    app_state.settings.edges.clone()
+}
+
+/// Get how strictly duplicate sprites must match
+/// # Arguments
+/// * `state` - The application state
+/// # Returns
+/// * `CompareMode` - The comparison mode used by checks and the watcher
+#[command]
+fn get_comparison_mode(state: State<AppState>) -> CompareMode {
+    let app_state = state.0.lock().expect("Failed to lock app state");
+    app_state.settings.comparison_mode
+}
+
 /// Get the path to the sprites folder
 /// # Arguments
 /// * `state` - The application state
@@ -998,27 +1324,152 @@ fn get_mode(state: State<AppState>) -> String {
 /// Pack a single collection
 /// # Arguments
 /// * `collection_name` - The name of the collection
+/// * `incremental` - Whether to only repack sprites that changed since the collection was last packed
 /// * `app_handle` - The application handle
 /// * `state` - The application state
 #[command]
-fn pack_single_collection(collection_name: String, app_handle: AppHandle, state: State<AppState>) {
+fn pack_single_collection(
+    collection_name: String,
+    incremental: Option<bool>,
+    app_handle: AppHandle,
+    state: State<AppState>
+) -> Result<(), PackerError> {
     let app_state = state.0.lock().expect("Failed to lock app state");
-    let collection = get_collection(collection_name.clone(), app_state.loaded_collections.clone());
+    let collection = get_collection(&collection_name, &app_state.loaded_collections)?.clone();
     let window = match app_handle.get_window("main") {
         Some(window) => window,
         None => log_panic!("Failed to get main window"),
     };
 
     let sprites_path = app_state.settings.sprites_path.clone();
+    let output = app_state.settings.output.atlas_output();
+    let format = app_state.settings.output.atlas_format;
+    let textures = app_state.settings.output.textures;
+    let edge_options = app_state.settings.edges.for_collection(&collection.name);
+
+    unsafe {
+        let (tx, rx) = mpsc::channel();
+        TX_PROGRESS = Mutex::new(Some(tx));
+        RX_PROGRESS = Mutex::new(Some(rx));
+        async_runtime::spawn(pack_collection(collection, window, sprites_path, output, format, textures, edge_options, incremental.unwrap_or(false)));
+    }
+
+    Ok(())
+}
+
+/// Pack several collections at once
+/// # Arguments
+/// * `target` - The collections to pack
+/// * `output_path` - The folder to write the atlases to, or `None` to use the output settings
+/// * `incremental` - Whether to only repack sprites that changed since the atlases at the output paths were generated
+/// * `app_handle` - The application handle
+/// * `state` - The application state
+#[command]
+fn pack_collections(
+    target: BatchTarget,
+    output_path: Option<String>,
+    incremental: Option<bool>,
+    app_handle: AppHandle,
+    state: State<AppState>
+) -> Result<(), PackerError> {
+    let collection_names = match target {
+        BatchTarget::Collections(collection_names) => collection_names,
+        BatchTarget::Changed => unsafe {
+            let mut collection_names = CHANGED_SPRITES.lock().expect("Failed to lock CHANGED_SPRITES")
+                .iter()
+                .map(|sprite| sprite.collection_name.clone())
+                .collect::<Vec<String>>();
+            collection_names.sort();
+            collection_names.dedup();
+            collection_names
+        },
+        BatchTarget::Animation(animation_name) => get_collections_from_animation_name(animation_name, state.clone())?
+            .into_iter()
+            .map(|collection| collection.name)
+            .collect(),
+    };
+    let collections = {
+        let app_state = state.0.lock().expect("Failed to lock app state");
+        collection_names
+            .iter()
+            .map(|collection_name| get_collection(collection_name, &app_state.loaded_collections).cloned())
+            .collect::<Result<Vec<Collection>, PackerError>>()?
+    };
+
+    let (sprites_path, output_settings, edges) = {
+        let app_state = state.0.lock().expect("Failed to lock app state");
+        (app_state.settings.sprites_path.clone(), app_state.settings.output.clone(), app_state.settings.edges.clone())
+    };
+    let output = match output_path {
+        Some(output_path) => output_settings.atlas_output_in(PathBuf::from(output_path)),
+        None => match output_settings.atlas_output() {
+            Some(output) => output,
+            None => match FileDialogBuilder::new()
+                .set_directory(&sprites_path)
+                .set_title("Choose folder to save atlases to")
+                .pick_folder() {
+                    Some(output_path) => output_settings.atlas_output_in(output_path),
+                    None => {
+                        warn!("No output folder for batch pack selected.");
+                        return Ok(());
+                    }
+                }
+        }
+    };
+
+    let window = match app_handle.get_window("main") {
+        Some(window) => window,
+        None => log_panic!("Failed to get main window"),
+    };
+
+    info!("Packing {} collections to {}", collections.len(), output.directory.display());
 
     unsafe {
         let (tx, rx) = mpsc::channel();
         TX_PROGRESS = Mutex::new(Some(tx));
         RX_PROGRESS = Mutex::new(Some(rx));
-        async_runtime::spawn(pack_collection(collection, window, sprites_path));
+        async_runtime::spawn(pack_collection_batch(collections, window, sprites_path, output, edges, incremental.unwrap_or(false)));
+    }
+
+    Ok(())
+}
+
+/// Change how the edges of sprites are treated when packing
+/// # Arguments
+/// * `collection_name` - The collection to change the options of, or `None` to change the options for every collection
+/// * `options` - The options to use, or `None` to make the collection use the options for every collection
+/// * `state` - The application state
+#[command]
+fn set_edge_options(collection_name: Option<String>, options: Option<EdgeOptions>, state: State<AppState>) {
+    let mut app_state = state.0.lock().expect("Failed to lock app state");
+    let edges = &mut app_state.settings.edges;
+    match (collection_name, options) {
+        (Some(collection_name), Some(options)) => {
+            info!("Edge options of collection {:?} set to {:?}", collection_name, options);
+            edges.collections.insert(collection_name, options);
+        }
+        (Some(collection_name), None) => {
+            info!("Edge options of collection {:?} reset", collection_name);
+            edges.collections.remove(&collection_name);
+        }
+        (None, options) => {
+            edges.default = options.unwrap_or_default();
+            info!("Edge options set to {:?}", edges.default);
+        }
     }
 }
 
+/// Change how strictly duplicate sprites must match
+/// # Arguments
+/// * `comparison_mode` - The comparison mode to use in checks and the watcher
+/// * `state` - The application state
+#[command]
+fn set_comparison_mode(comparison_mode: CompareMode, state: State<AppState>) {
+    let mut app_state = state.0.lock().expect("Failed to lock app state");
+    info!("Comparison mode set to {:?}", comparison_mode);
+    app_state.settings.comparison_mode = comparison_mode;
+}
+
 #[command]
 /// Change the application's language
 /// # Arguments
@@ -1048,6 +1499,17 @@ fn set_language(language: String, menu_items: Vec<String>, app_handle: AppHandle
     app_state.settings.language = language;
 }
 
+/// Change where generated atlases are written
+/// # Arguments
+/// * `output_settings` - The new output settings
+/// * `state` - The application state
+#[command]
+fn set_output_settings(output_settings: OutputSettings, state: State<AppState>) {
+    let mut app_state = state.0.lock().expect("Failed to lock app state");
+    info!("Output settings set to {:?}", output_settings);
+    app_state.settings.output = output_settings;
+}
+
 #[command]
 /// Set the [theme] mode of the application
 /// # Arguments
@@ -88,6 +88,170 @@ export class Sprite {
   }
 }
 
+export class BackupSet {
+  id: string;
+  created: string;
+  description: string;
+  files: Array<{ original: string; stored: string }>;
+
+  constructor(
+    id: string,
+    created: string,
+    description: string,
+    files: Array<{ original: string; stored: string }>
+  ) {
+    this.id = id;
+    this.created = created;
+    this.description = description;
+    this.files = files;
+  }
+}
+
+export interface BleedReport {
+  sprite: Sprite;
+  pixels: number;
+  boundingBox: { x: number; y: number; width: number; height: number };
+  coordinates: Array<[number, number]>;
+}
+
+export class BatchProgress {
+  collectionName: string;
+  progress: number;
+  totalProgress: number;
+
+  constructor(collectionName: string, progress: number, totalProgress: number) {
+    this.collectionName = collectionName;
+    this.progress = progress;
+    this.totalProgress = totalProgress;
+  }
+}
+
+export interface PaddingViolation {
+  sprite: Sprite;
+  pixels: number;
+}
+
+export class BatchSummary {
+  packed: Array<{
+    collectionName: string;
+    path: string;
+    paddingViolations: Array<PaddingViolation>;
+    formatWarnings: Array<FormatWarning>;
+    textures: Array<string>;
+    millis: number;
+  }>;
+  failed: Array<{ collectionName: string; error: string }>;
+  cancelled: boolean;
+  millis: number;
+
+  constructor(
+    packed: Array<{
+      collectionName: string;
+      path: string;
+      paddingViolations: Array<PaddingViolation>;
+      formatWarnings: Array<FormatWarning>;
+      textures: Array<string>;
+      millis: number;
+    }>,
+    failed: Array<{ collectionName: string; error: string }>,
+    cancelled: boolean,
+    millis: number
+  ) {
+    this.packed = packed;
+    this.failed = failed;
+    this.cancelled = cancelled;
+    this.millis = millis;
+  }
+}
+
+export type BatchTarget =
+  | { kind: 'collections'; value: Array<string> }
+  | { kind: 'changed' }
+  | { kind: 'animation'; value: string };
+
+export type CompareMode =
+  | { kind: 'exact' }
+  | { kind: 'ignoreTransparentRgb' }
+  | { kind: 'tolerance'; tolerance: number }
+  | { kind: 'perceptual'; threshold: number };
+
+export class EdgeOptions {
+  padding: number;
+  extrude: number;
+  bleed: boolean;
+
+  constructor(padding = 0, extrude = 0, bleed = false) {
+    this.padding = padding;
+    this.extrude = extrude;
+    this.bleed = bleed;
+  }
+}
+
+export type AtlasFormat = 'original' | 'rgba8' | 'rgba16' | 'rgb8';
+
+export type ColorLoss = 'alpha' | 'color' | 'precision';
+
+export interface FormatWarning {
+  sprite: Sprite;
+  losses: Array<ColorLoss>;
+}
+
+export type BlockCompression = 'bc3' | 'bc7';
+
+export class TextureOptions {
+  dds: BlockCompression | null;
+  ktx2: BlockCompression | null;
+  mipmaps: boolean;
+  linear: boolean;
+
+  constructor(
+    dds: BlockCompression | null = null,
+    ktx2: BlockCompression | null = null,
+    mipmaps = false,
+    linear = false
+  ) {
+    this.dds = dds;
+    this.ktx2 = ktx2;
+    this.mipmaps = mipmaps;
+    this.linear = linear;
+  }
+}
+
+export class OutputSettings {
+  Path: string;
+  'Atlas Name Template': string;
+  'Backup Existing Atlases': boolean;
+  'Overwrite Original Atlases': boolean;
+  'Atlas Format': AtlasFormat;
+  Textures: TextureOptions;
+
+  constructor(
+    path: string,
+    atlasNameTemplate: string,
+    backupExistingAtlases: boolean,
+    overwriteOriginalAtlases: boolean,
+    atlasFormat: AtlasFormat = 'original',
+    textures: TextureOptions = new TextureOptions()
+  ) {
+    this.Path = path;
+    this['Atlas Name Template'] = atlasNameTemplate;
+    this['Backup Existing Atlases'] = backupExistingAtlases;
+    this['Overwrite Original Atlases'] = overwriteOriginalAtlases;
+    this['Atlas Format'] = atlasFormat;
+    this.Textures = textures;
+  }
+}
+
+export class PackerError {
+  kind: string;
+  message: string;
+
+  constructor(kind: string, message: string) {
+    this.kind = kind;
+    this.message = message;
+  }
+}
+
 export class ProgressPayload {
   progress: number;
 
@@ -96,6 +260,145 @@ export class ProgressPayload {
   }
 }
 
+export interface PixelDiff {
+  sizeMismatch: boolean;
+  differingPixels: number;
+  boundingBox: { x: number; y: number; width: number; height: number } | null;
+  maxChannelDelta: number;
+  mask: string | null;
+}
+
+export interface SpriteDiff {
+  collectionName: string;
+  id: number;
+  reference: Sprite;
+  variants: Array<{ sprite: Sprite; diff: PixelDiff }>;
+}
+
+export type PreviewFormat = 'gif' | 'apng' | 'webp';
+
+export class PreviewOptions {
+  format: PreviewFormat;
+  scale: number;
+  loopRepeats: number;
+
+  constructor(format: PreviewFormat = 'gif', scale = 1, loopRepeats = 3) {
+    this.format = format;
+    this.scale = scale;
+    this.loopRepeats = loopRepeats;
+  }
+}
+
+export type SheetLayout = { kind: 'strip' } | { kind: 'grid'; columns: number };
+
+export interface SheetRect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface SheetFrame {
+  index: number;
+  name: string;
+  path: string;
+  cell: SheetRect;
+  frame: SheetRect;
+  spriteRect: SheetRect;
+}
+
+export interface SheetFile {
+  clipName: string;
+  image: string;
+  width: number;
+  height: number;
+  cellWidth: number;
+  cellHeight: number;
+  columns: number;
+  rows: number;
+  fps: number;
+  loopStart: number;
+  frames: Array<SheetFrame>;
+}
+
+export class RepackOptions {
+  padding: number;
+  allowFlip: boolean;
+  powerOfTwo: boolean;
+  maxSize: number;
+
+  constructor(padding = 2, allowFlip = false, powerOfTwo = true, maxSize = 4096) {
+    this.padding = padding;
+    this.allowFlip = allowFlip;
+    this.powerOfTwo = powerOfTwo;
+    this.maxSize = maxSize;
+  }
+}
+
+export class ReplacementPreview {
+  path: string;
+  changedPixels: number;
+  totalPixels: number;
+  before: string;
+  after: string;
+
+  constructor(
+    path: string,
+    changedPixels: number,
+    totalPixels: number,
+    before: string,
+    after: string
+  ) {
+    this.path = path;
+    this.changedPixels = changedPixels;
+    this.totalPixels = totalPixels;
+    this.before = before;
+    this.after = after;
+  }
+}
+
+export interface ResolutionPlan {
+  collectionName: string;
+  id: number;
+  strategy: ResolveStrategy;
+  source: Sprite | null;
+  targets: Array<string>;
+  conflicts: number;
+  unresolved: string | null;
+  preview: string | null;
+}
+
+export type ResolveStrategy =
+  | 'newestModified'
+  | 'majorityVote'
+  | 'preferred'
+  | 'mergeNonConflicting';
+
+export interface TwinGroup {
+  width: number;
+  height: number;
+  sprites: Array<Sprite>;
+}
+
+export interface UnpackSummary {
+  written: Array<string>;
+  skipped: Array<string>;
+}
+
+export interface SpriteVerification {
+  sprite: Sprite;
+  overflowPixels: number;
+  outOfBoundsPixels: number;
+  atlasMismatches: number;
+  frameMismatches: number;
+}
+
+export interface VerifyReport {
+  collectionName: string;
+  checkedSprites: number;
+  sprites: Array<SpriteVerification>;
+}
+
 export enum Theme {
   Dark,
   Light,
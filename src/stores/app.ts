@@ -1,13 +1,31 @@
 import { defineStore } from 'pinia';
 import { invoke } from '@tauri-apps/api/tauri';
+import { Notify } from 'quasar';
 import packageJson from '../../package.json';
 import {
   Animation,
+  BackupSet,
+  BatchTarget,
+  BleedReport,
   Clip,
   Collection,
+  CompareMode,
+  EdgeOptions,
   InspectMode,
+  PackerError,
+  PreviewOptions,
+  RepackOptions,
+  ReplacementPreview,
+  ResolutionPlan,
+  ResolveStrategy,
+  SheetFile,
+  SheetLayout,
   Sprite,
+  SpriteDiff,
   Theme,
+  TwinGroup,
+  UnpackSummary,
+  VerifyReport,
 } from 'src/data/classes';
 import { Ref, ref } from 'vue';
 
@@ -25,19 +43,48 @@ interface AppState {
   duplicateSprites: Ref<Array<string>>;
   inspectMode: Ref<InspectMode>;
   isPacking: Ref<boolean>;
+  lastBackup: Ref<BackupSet | null>;
   packProgress: Ref<number>;
   theme: Ref<Theme>;
 
+  applyConflictResolution: (strategy: ResolveStrategy, plan: ResolutionPlan) => void;
   cancelPack: () => void;
   changeMode: () => void;
   checkForChangedSprites: () => void;
   check: () => void;
+  diffDuplicates: (collectionName?: string, includeMasks?: boolean) => Promise<Array<SpriteDiff>>;
+  exportClipPreview: (
+    animationName: string,
+    clipName: string,
+    options?: PreviewOptions,
+    outputPath?: string
+  ) => Promise<string | null>;
+  exportClipSheet: (
+    animationName: string,
+    clipName: string,
+    layout?: SheetLayout,
+    outputPath?: string
+  ) => Promise<SheetFile | null>;
+  findTwins: () => Promise<Array<TwinGroup>>;
   incrementFrameIndex: () => void;
-  packCollections: () => void;
+  lintBleedingSprites: (collectionName?: string) => Promise<Array<BleedReport>>;
+  listBackups: () => Promise<Array<BackupSet>>;
+  packBatch: (target: BatchTarget, outputPath?: string, incremental?: boolean) => void;
+  packCollections: (incremental?: boolean) => void;
+  planConflictResolutions: (strategy: ResolveStrategy, collectionName?: string) => Promise<Array<ResolutionPlan>>;
+  previewReplaceDuplicates: () => Promise<Array<ReplacementPreview>>;
+  propagateToTwins: () => void;
+  repackCollection: (collectionName: string, options?: RepackOptions, outputPath?: string) => void;
   replaceDuplicates: () => void;
+  restoreBackup: (backupId: string) => void;
+  setComparisonMode: (comparisonMode: CompareMode) => void;
+  setEdgeOptions: (options?: EdgeOptions, collectionName?: string) => void;
   setCurrentSprite: (spriteName: string) => void;
   setLanguage(language: string): void;
   setMode(mode: string): void;
+  showError: (error: PackerError) => void;
+  unpackFrames: (animationName?: string, outputPath?: string, overwrite?: boolean) => Promise<Array<UnpackSummary>>;
+  verifyCollections: (collectionName?: string) => Promise<Array<VerifyReport>>;
 }
 
 export const useAppStore = defineStore('app', (): AppState => {
@@ -67,10 +114,27 @@ export const useAppStore = defineStore('app', (): AppState => {
 
   const isPacking = ref<boolean>(false);
 
+  const lastBackup = ref<BackupSet | null>(null);
+
   const packProgress = ref<number>(0);
 
   const theme = ref(Theme.Dark);
 
+  const applyConflictResolution = (strategy: ResolveStrategy, plan: ResolutionPlan): void => {
+    invoke('apply_conflict_resolution', {
+      strategy,
+      collectionName: plan.collectionName,
+      spriteId: plan.id,
+    }).then((backupSet: BackupSet) => {
+      lastBackup.value = backupSet;
+      changedSprites.value = changedSprites.value.filter(
+        (sprite) =>
+          sprite.id !== plan.id ||
+          sprite.collectionName !== plan.collectionName
+      );
+    }).catch(showError);
+  };
+
   const cancelPack = (): void => {
     isPacking.value = false;
     invoke('cancel_pack');
@@ -93,7 +157,7 @@ export const useAppStore = defineStore('app', (): AppState => {
           changedSprites.value.push(sprite);
         }
       }
-    });
+    }).catch(showError);
   };
 
   const check = (): void => {
@@ -104,6 +168,60 @@ export const useAppStore = defineStore('app', (): AppState => {
           changedSprites.value.push(sprite);
         }
       }
+    }).catch(showError);
+  };
+
+  const diffDuplicates = (
+    collectionName?: string,
+    includeMasks = false
+  ): Promise<Array<SpriteDiff>> => {
+    return invoke<Array<SpriteDiff>>('diff_duplicate_sprites', {
+      collectionName: collectionName ?? null,
+      includeMasks,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return [];
+    });
+  };
+
+  const exportClipPreview = (
+    animationName: string,
+    clipName: string,
+    options?: PreviewOptions,
+    outputPath?: string
+  ): Promise<string | null> => {
+    return invoke<string | null>('export_clip_preview', {
+      animationName,
+      clipName,
+      options: options ?? null,
+      outputPath: outputPath ?? null,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return null;
+    });
+  };
+
+  const exportClipSheet = (
+    animationName: string,
+    clipName: string,
+    layout?: SheetLayout,
+    outputPath?: string
+  ): Promise<SheetFile | null> => {
+    return invoke<SheetFile | null>('export_clip_sheet', {
+      animationName,
+      clipName,
+      layout: layout ?? null,
+      outputPath: outputPath ?? null,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return null;
+    });
+  };
+
+  const findTwins = (): Promise<Array<TwinGroup>> => {
+    return invoke<Array<TwinGroup>>('find_twin_sprites').catch((error: PackerError) => {
+      showError(error);
+      return [];
     });
   };
 
@@ -118,18 +236,107 @@ export const useAppStore = defineStore('app', (): AppState => {
     }
   };
 
-  const packCollections = (): void => {
+  const lintBleedingSprites = (collectionName?: string): Promise<Array<BleedReport>> => {
+    return invoke<Array<BleedReport>>('lint_bleeding_sprites', {
+      collectionName: collectionName ?? null,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return [];
+    });
+  };
+
+  const listBackups = (): Promise<Array<BackupSet>> => {
+    return invoke<Array<BackupSet>>('list_backups').catch((error: PackerError) => {
+      showError(error);
+      return [];
+    });
+  };
+
+  const packBatch = (
+    target: BatchTarget,
+    outputPath?: string,
+    incremental = false
+  ): void => {
+    packProgress.value = 0;
+    isPacking.value = true;
+    invoke('pack_collections', {
+      target,
+      outputPath: outputPath ?? null,
+      incremental,
+    }).catch((error: PackerError) => {
+      isPacking.value = false;
+      showError(error);
+    });
+  };
+
+  const packCollections = (incremental = false): void => {
     packProgress.value = 0;
     isPacking.value = true;
     invoke('pack_single_collection', {
       collectionName: currentCollection.value?.name,
+      incremental,
+    }).catch((error: PackerError) => {
+      isPacking.value = false;
+      showError(error);
+    });
+  };
+
+  const planConflictResolutions = (
+    strategy: ResolveStrategy,
+    collectionName?: string
+  ): Promise<Array<ResolutionPlan>> => {
+    return invoke<Array<ResolutionPlan>>('plan_conflict_resolutions', {
+      strategy,
+      collectionName: collectionName ?? null,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return [];
     });
   };
 
+  const previewReplaceDuplicates = (): Promise<Array<ReplacementPreview>> => {
+    return invoke<Array<ReplacementPreview>>('preview_replace_duplicate_sprites', {
+      sourceSprite: currentFrame.value,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return [];
+    });
+  };
+
+  const propagateToTwins = (): void => {
+    invoke('propagate_to_twins', {
+      editedSprite: currentFrame.value,
+    }).then((backupSet: BackupSet) => {
+      lastBackup.value = backupSet;
+      changedSprites.value = changedSprites.value.filter(
+        (sprite) =>
+          sprite.path !== currentFrame.value?.path ||
+          sprite.collectionName !== currentFrame.value?.collectionName
+      );
+    }).catch(showError);
+  };
+
+  const repackCollection = (
+    collectionName: string,
+    options?: RepackOptions,
+    outputPath?: string
+  ): void => {
+    invoke<Array<Sprite>>('repack_collection', {
+      collectionName,
+      options: options ?? null,
+      outputPath: outputPath ?? null,
+    }).then((sprites: Array<Sprite>) => {
+      if (!outputPath && currentCollection.value?.name === collectionName) {
+        currentCollection.value.sprites = sprites;
+      }
+    }).catch(showError);
+  };
+
   const replaceDuplicates = (): void => {
     invoke('replace_duplicate_sprites', {
       sourceSprite: currentFrame.value,
-    }).then(() => {
+    }).then((backupSet: BackupSet) => {
+      lastBackup.value = backupSet;
       const filteredSprites = changedSprites.value.filter(
         (sprite) =>
           sprite.id !== currentFrame.value?.id ||
@@ -137,7 +344,24 @@ export const useAppStore = defineStore('app', (): AppState => {
       );
 
       changedSprites.value = filteredSprites;
-    });
+    }).catch(showError);
+  };
+
+  const restoreBackup = (backupId: string): void => {
+    invoke('restore_backup', { backupId }).then(() => {
+      if (lastBackup.value?.id === backupId) {
+        lastBackup.value = null;
+      }
+      check();
+    }).catch(showError);
+  };
+
+  const setComparisonMode = (comparisonMode: CompareMode): void => {
+    invoke('set_comparison_mode', { comparisonMode }).then(check);
+  };
+
+  const setEdgeOptions = (options?: EdgeOptions, collectionName?: string): void => {
+    invoke('set_edge_options', { collectionName, options }).catch(showError);
   };
 
   const setCurrentSprite = (spriteName: string): void => {
@@ -149,7 +373,7 @@ export const useAppStore = defineStore('app', (): AppState => {
       // currentFrame.value = collection.sprites.find(
       //   (sprite) => sprite.name === spriteName
       // );
-    });
+    }).catch(showError);
   };
 
   const setLanguage = (language: string): void => {
@@ -162,6 +386,37 @@ export const useAppStore = defineStore('app', (): AppState => {
     invoke('set_mode', { mode });
   };
 
+  const showError = (error: PackerError): void => {
+    Notify.create({
+      type: 'negative',
+      message: error.message,
+    });
+  };
+
+  const unpackFrames = (
+    animationName?: string,
+    outputPath?: string,
+    overwrite = false
+  ): Promise<Array<UnpackSummary>> => {
+    return invoke<Array<UnpackSummary>>('unpack_frames', {
+      animationName: animationName ?? null,
+      outputPath: outputPath ?? null,
+      overwrite,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return [];
+    });
+  };
+
+  const verifyCollections = (collectionName?: string): Promise<Array<VerifyReport>> => {
+    return invoke<Array<VerifyReport>>('verify_collections', {
+      collectionName: collectionName ?? null,
+    }).catch((error: PackerError) => {
+      showError(error);
+      return [];
+    });
+  };
+
   return {
     version,
     allowedToPack,
@@ -176,18 +431,37 @@ export const useAppStore = defineStore('app', (): AppState => {
     duplicateSprites,
     inspectMode,
     isPacking,
+    lastBackup,
     packProgress,
     theme,
 
+    applyConflictResolution,
     cancelPack,
     changeMode,
     checkForChangedSprites,
     check,
+    diffDuplicates,
+    exportClipPreview,
+    exportClipSheet,
+    findTwins,
     incrementFrameIndex,
+    lintBleedingSprites,
+    listBackups,
+    packBatch,
     packCollections,
+    planConflictResolutions,
+    previewReplaceDuplicates,
+    propagateToTwins,
+    repackCollection,
     replaceDuplicates,
+    restoreBackup,
+    setComparisonMode,
+    setEdgeOptions,
     setCurrentSprite,
     setLanguage,
     setMode,
+    showError,
+    unpackFrames,
+    verifyCollections,
   };
 });